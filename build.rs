@@ -1,7 +1,14 @@
 // build.rs - ビルド時の設定スクリプト
 // Visual C++ランタイムを静的リンクして、DLL依存を完全に排除
+// locales フォルダの翻訳カタログを埋め込む
+
+use std::env;
+use std::fs;
+use std::path::Path;
 
 fn main() {
+    embed_locales();
+
     // Windows MSVCターゲットの場合のみ実行
     if cfg!(target_os = "windows") && cfg!(target_env = "msvc") {
         // static_vcruntimeクレートが自動的に処理
@@ -20,4 +27,29 @@ fn main() {
             println!("cargo:rustc-link-arg=/DEBUG:NONE");
         }
     }
-}
\ No newline at end of file
+}
+
+// locales/<言語>.ini をすべて埋め込む（言語の追加はファイルを置くだけでよい）
+fn embed_locales() {
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("locales");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .expect("locales フォルダがありません")
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ini"))
+        .collect();
+    files.sort();
+
+    let mut code = String::from("pub(crate) const CATALOG_FILES: &[(&str, &str)] = &[\n");
+    for path in &files {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let language = path.file_stem().unwrap().to_string_lossy();
+        code.push_str(&format!("    ({:?}, include_str!({:?})),\n", language, path.display().to_string()));
+    }
+    code.push_str("];\n");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("locales.rs");
+    fs::write(out, code).unwrap();
+}
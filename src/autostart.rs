@@ -0,0 +1,510 @@
+//! OSごとの自動起動の登録
+//!
+//! - `registry`: Windowsのレジストリ（HKCU\...\Run）
+//! - `systemd`: systemd のユーザーユニット（`systemctl --user`）
+//! - `xdg`: デスクトップ環境の自動起動（~/.config/autostart/*.desktop）
+//!
+//! 設定ファイルの `AutoStartBackend` で選択し、`auto` の場合は
+//! 実行環境に合わせて選ぶ。
+//!
+//! `--config` や `MG_CONFIG` で指定したプロファイルは、プロファイルごとに別の名前で登録する
+//! （例: `metaguard-tanaka.service`）。登録したコマンドには `--config <パス>` を付ける。
+
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::ConfigLocation;
+use crate::lock::fnv1a;
+use crate::t;
+
+const APP_NAME: &str = "MetaGuard";
+const SYSTEMD_UNIT_NAME: &str = "metaguard";
+const XDG_DESKTOP_FILE_NAME: &str = "MetaGuard";
+
+/// 自動起動で実行するコマンド
+#[derive(Debug, Clone)]
+pub struct AutoStartCommand {
+    pub exe: PathBuf,
+    pub args: Vec<String>,
+    /// プロファイル名（既定の設定ファイルなら None）
+    pub profile: Option<String>,
+}
+
+impl AutoStartCommand {
+    /// 現在の実行ファイルを `--auto` で起動するコマンド（プロファイルなら `--config` も付ける）
+    pub fn current(location: &ConfigLocation) -> Result<Self, Box<dyn Error>> {
+        let profile = location.profile();
+        let mut args = vec!["--auto".to_string()];
+        if profile.is_some() {
+            args.push("--config".to_string());
+            args.push(location.path.to_string_lossy().to_string());
+        }
+        Ok(AutoStartCommand {
+            exe: std::env::current_exe()?,
+            args,
+            profile,
+        })
+    }
+
+    fn words(&self) -> Vec<String> {
+        std::iter::once(self.exe.to_string_lossy().to_string())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+}
+
+/// 自動起動の登録方法
+pub trait AutoStartBackend {
+    /// 設定ファイルで使う名前
+    fn name(&self) -> &'static str;
+    /// 画面に表示する説明
+    fn description(&self) -> String;
+    /// この環境で使えるかどうか
+    fn is_available(&self) -> bool;
+    fn enable(&self, command: &AutoStartCommand) -> Result<(), Box<dyn Error>>;
+    fn disable(&self, profile: Option<&str>) -> Result<(), Box<dyn Error>>;
+    /// 実際に登録されているかどうか
+    fn is_enabled(&self, profile: Option<&str>) -> bool;
+}
+
+/// プロファイルごとの登録名（例: `metaguard` → `metaguard-tanaka`）
+///
+/// ファイル名に使えない文字は `_` にし、そのときは名前が重ならないように元の名前のハッシュを付ける。
+pub fn entry_name(base: &str, profile: Option<&str>) -> String {
+    let Some(profile) = profile else {
+        return base.to_string();
+    };
+    let safe: String = profile
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if safe == profile {
+        format!("{}-{}", base, safe)
+    } else {
+        format!("{}-{}-{:08x}", base, safe, fnv1a(profile.as_bytes()) as u32)
+    }
+}
+
+/// 設定値 `AutoStartBackend` から登録方法を選ぶ
+///
+/// 不明な値の場合は `None` を返す。
+pub fn backend_for(name: &str) -> Option<Box<dyn AutoStartBackend>> {
+    match name.trim().to_lowercase().as_str() {
+        "" | "auto" => Some(detect()),
+        "registry" | "windows" => Some(Box::new(RunKeyBackend)),
+        "systemd" => Some(Box::new(SystemdUserBackend)),
+        "xdg" => Some(Box::new(XdgAutostartBackend)),
+        _ => None,
+    }
+}
+
+/// 実行環境に合った登録方法
+pub fn detect() -> Box<dyn AutoStartBackend> {
+    if cfg!(windows) {
+        Box::new(RunKeyBackend)
+    } else if SystemdUserBackend.is_available() {
+        Box::new(SystemdUserBackend)
+    } else {
+        Box::new(XdgAutostartBackend)
+    }
+}
+
+fn config_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+fn unavailable(backend: &dyn AutoStartBackend) -> Box<dyn Error> {
+    t!("autostart.unavailable", description = backend.description()).into()
+}
+
+//------------------------------------------------------------
+// Windows レジストリ
+//------------------------------------------------------------
+
+/// HKCU\Software\Microsoft\Windows\CurrentVersion\Run への登録
+pub struct RunKeyBackend;
+
+/// レジストリの値の名前（例: `MetaGuard (tanaka)`）
+#[cfg(windows)]
+fn run_value_name(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("{} ({})", APP_NAME, profile),
+        None => APP_NAME.to_string(),
+    }
+}
+
+#[cfg(windows)]
+const RUN_KEY_PATH: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+
+impl AutoStartBackend for RunKeyBackend {
+    fn name(&self) -> &'static str {
+        "registry"
+    }
+
+    fn description(&self) -> String {
+        t!("autostart.registry")
+    }
+
+    fn is_available(&self) -> bool {
+        cfg!(windows)
+    }
+
+    #[cfg(windows)]
+    fn enable(&self, command: &AutoStartCommand) -> Result<(), Box<dyn Error>> {
+        use winreg::enums::*;
+        use winreg::RegKey;
+
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let (key, _) = hkcu.create_subkey(RUN_KEY_PATH)?;
+
+        let auto_start_command = command
+            .words()
+            .iter()
+            .map(|w| if w.contains(' ') { format!("\"{}\"", w) } else { w.clone() })
+            .collect::<Vec<_>>()
+            .join(" ");
+        key.set_value(&run_value_name(command.profile.as_deref()), &auto_start_command)?;
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn enable(&self, _command: &AutoStartCommand) -> Result<(), Box<dyn Error>> {
+        Err(unavailable(self))
+    }
+
+    #[cfg(windows)]
+    fn disable(&self, profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+        use winreg::enums::*;
+        use winreg::RegKey;
+
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let key = hkcu.open_subkey_with_flags(RUN_KEY_PATH, KEY_SET_VALUE)?;
+        key.delete_value(run_value_name(profile))?;
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn disable(&self, _profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+        Err(unavailable(self))
+    }
+
+    #[cfg(windows)]
+    fn is_enabled(&self, profile: Option<&str>) -> bool {
+        use winreg::enums::*;
+        use winreg::RegKey;
+
+        RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey(RUN_KEY_PATH)
+            .map(|key| key.get_value::<String, _>(run_value_name(profile)).is_ok())
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(windows))]
+    fn is_enabled(&self, _profile: Option<&str>) -> bool {
+        false
+    }
+}
+
+//------------------------------------------------------------
+// systemd ユーザーユニット
+//------------------------------------------------------------
+
+/// ~/.config/systemd/user/metaguard.service として登録
+pub struct SystemdUserBackend;
+
+impl SystemdUserBackend {
+    fn unit_dir() -> Option<PathBuf> {
+        config_home().map(|dir| dir.join("systemd").join("user"))
+    }
+
+    /// ユニット名（例: `metaguard-tanaka.service`）
+    fn unit_name(profile: Option<&str>) -> String {
+        format!("{}.service", entry_name(SYSTEMD_UNIT_NAME, profile))
+    }
+
+    fn systemctl(args: &[&str]) -> std::io::Result<std::process::ExitStatus> {
+        Command::new("systemctl")
+            .arg("--user")
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    }
+}
+
+/// systemd ユニットファイルの内容を作る
+///
+/// MetaGuard自体が異常終了した場合は systemd が再起動する。
+pub fn render_systemd_unit(command: &AutoStartCommand, display: Option<&str>) -> String {
+    let exec_start = command
+        .words()
+        .iter()
+        .map(|w| systemd_quote(w))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    match &command.profile {
+        Some(profile) => unit.push_str(&format!("Description={} ({})\n", t!("autostart.unit_description"), profile)),
+        None => unit.push_str(&format!("Description={}\n", t!("autostart.unit_description"))),
+    }
+    unit.push_str("After=network-online.target graphical-session.target\n");
+    unit.push('\n');
+    unit.push_str("[Service]\n");
+    unit.push_str("Type=simple\n");
+    unit.push_str(&format!("ExecStart={}\n", exec_start));
+    if let Some(display) = display {
+        // Wine上のMT4/MT5はXサーバーが必要
+        unit.push_str(&format!("Environment={}\n", systemd_quote(&format!("DISPLAY={}", display))));
+    }
+    unit.push_str("Restart=on-failure\n");
+    unit.push_str("RestartSec=10\n");
+    unit.push('\n');
+    unit.push_str("[Install]\n");
+    unit.push_str("WantedBy=default.target\n");
+    unit
+}
+
+fn systemd_quote(word: &str) -> String {
+    let escaped = word.replace('%', "%%");
+    if escaped.chars().any(|c| c.is_whitespace() || c == '"' || c == '\\' || c == '\'') {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+impl AutoStartBackend for SystemdUserBackend {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn description(&self) -> String {
+        t!("autostart.systemd")
+    }
+
+    fn is_available(&self) -> bool {
+        !cfg!(windows) && std::path::Path::new("/run/systemd/system").is_dir()
+    }
+
+    fn enable(&self, command: &AutoStartCommand) -> Result<(), Box<dyn Error>> {
+        if !self.is_available() {
+            return Err(unavailable(self));
+        }
+        let dir = Self::unit_dir().ok_or_else(|| t!("autostart.no_home"))?;
+        fs::create_dir_all(&dir)?;
+
+        let display = std::env::var("DISPLAY").ok().filter(|d| !d.is_empty());
+        let unit_name = Self::unit_name(command.profile.as_deref());
+        fs::write(dir.join(&unit_name), render_systemd_unit(command, display.as_deref()))?;
+
+        Self::systemctl(&["daemon-reload"])?;
+        let status = Self::systemctl(&["enable", &unit_name])?;
+        if !status.success() {
+            return Err(t!("autostart.systemctl_failed", command = format!("systemctl --user enable {}", unit_name)).into());
+        }
+        Ok(())
+    }
+
+    fn disable(&self, profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+        if !self.is_available() {
+            return Err(unavailable(self));
+        }
+        let unit_name = Self::unit_name(profile);
+        let status = Self::systemctl(&["disable", &unit_name])?;
+        if let Some(path) = Self::unit_dir().map(|dir| dir.join(&unit_name)).filter(|p| p.exists()) {
+            fs::remove_file(path)?;
+        }
+        Self::systemctl(&["daemon-reload"])?;
+        if !status.success() {
+            return Err(t!("autostart.systemctl_failed", command = format!("systemctl --user disable {}", unit_name)).into());
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self, profile: Option<&str>) -> bool {
+        if !self.is_available() {
+            return false;
+        }
+        let unit_name = Self::unit_name(profile);
+        match Self::systemctl(&["is-enabled", &unit_name]) {
+            Ok(status) => status.success(),
+            // systemctl が実行できない場合は有効化のシンボリックリンクで判断
+            Err(_) => Self::unit_dir()
+                .map(|dir| dir.join("default.target.wants").join(&unit_name).exists())
+                .unwrap_or(false),
+        }
+    }
+}
+
+//------------------------------------------------------------
+// XDG 自動起動
+//------------------------------------------------------------
+
+/// ~/.config/autostart/MetaGuard.desktop として登録
+pub struct XdgAutostartBackend;
+
+impl XdgAutostartBackend {
+    /// .desktop ファイルのパス（例: `MetaGuard-tanaka.desktop`）
+    fn desktop_path(profile: Option<&str>) -> Option<PathBuf> {
+        let file_name = format!("{}.desktop", entry_name(XDG_DESKTOP_FILE_NAME, profile));
+        config_home().map(|dir| dir.join("autostart").join(file_name))
+    }
+}
+
+/// XDG 自動起動用の .desktop ファイルの内容を作る
+pub fn render_desktop_entry(command: &AutoStartCommand) -> String {
+    let exec = command
+        .words()
+        .iter()
+        .map(|w| desktop_quote(w))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut entry = String::new();
+    entry.push_str("[Desktop Entry]\n");
+    entry.push_str("Type=Application\n");
+    match &command.profile {
+        Some(profile) => entry.push_str(&format!("Name={} ({})\n", APP_NAME, profile)),
+        None => entry.push_str(&format!("Name={}\n", APP_NAME)),
+    }
+    entry.push_str(&format!("Comment={}\n", t!("autostart.desktop_comment")));
+    entry.push_str(&format!("Exec={}\n", exec));
+    entry.push_str("Terminal=true\n");
+    entry.push_str("X-GNOME-Autostart-enabled=true\n");
+    entry
+}
+
+fn desktop_quote(word: &str) -> String {
+    let needs_quotes = word
+        .chars()
+        .any(|c| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c));
+    let word = if needs_quotes {
+        let mut quoted = String::from("\"");
+        for c in word.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    } else {
+        word.to_string()
+    };
+    word.replace('%', "%%")
+}
+
+impl AutoStartBackend for XdgAutostartBackend {
+    fn name(&self) -> &'static str {
+        "xdg"
+    }
+
+    fn description(&self) -> String {
+        t!("autostart.xdg")
+    }
+
+    fn is_available(&self) -> bool {
+        !cfg!(windows) && config_home().is_some()
+    }
+
+    fn enable(&self, command: &AutoStartCommand) -> Result<(), Box<dyn Error>> {
+        if !self.is_available() {
+            return Err(unavailable(self));
+        }
+        let path = Self::desktop_path(command.profile.as_deref()).ok_or_else(|| t!("autostart.no_home"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, render_desktop_entry(command))?;
+        Ok(())
+    }
+
+    fn disable(&self, profile: Option<&str>) -> Result<(), Box<dyn Error>> {
+        if !self.is_available() {
+            return Err(unavailable(self));
+        }
+        if let Some(path) = Self::desktop_path(profile).filter(|p| p.exists()) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self, profile: Option<&str>) -> bool {
+        Self::desktop_path(profile)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                !content
+                    .lines()
+                    .any(|l| l.trim() == "Hidden=true" || l.trim() == "X-GNOME-Autostart-enabled=false")
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(exe: &str) -> AutoStartCommand {
+        AutoStartCommand {
+            exe: PathBuf::from(exe),
+            args: vec!["--auto".to_string()],
+            profile: None,
+        }
+    }
+
+    #[test]
+    fn systemd_unit_restarts_on_failure() {
+        let unit = render_systemd_unit(&command("/opt/metaguard/MetaGuard"), Some(":0"));
+        assert!(unit.contains("ExecStart=/opt/metaguard/MetaGuard --auto\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.contains("Environment=DISPLAY=:0\n"));
+        assert!(unit.contains("WantedBy=default.target\n"));
+    }
+
+    #[test]
+    fn systemd_unit_quotes_paths_with_spaces() {
+        let unit = render_systemd_unit(&command("/home/fx/My Tools/MetaGuard"), None);
+        assert!(unit.contains("ExecStart=\"/home/fx/My Tools/MetaGuard\" --auto\n"));
+        assert!(!unit.contains("DISPLAY"));
+    }
+
+    #[test]
+    fn desktop_entry_escapes_exec() {
+        let entry = render_desktop_entry(&command("/home/fx/My Tools/100%/MetaGuard"));
+        assert!(entry.contains("Exec=\"/home/fx/My Tools/100%%/MetaGuard\" --auto\n"));
+    }
+
+    #[test]
+    fn profiles_get_their_own_entry() {
+        let mut profile = command("/opt/metaguard/MetaGuard");
+        profile.args.extend(["--config".to_string(), "/srv/clients/tanaka.toml".to_string()]);
+        profile.profile = Some("tanaka".to_string());
+        let unit = render_systemd_unit(&profile, None);
+        assert!(unit.contains("ExecStart=/opt/metaguard/MetaGuard --auto --config /srv/clients/tanaka.toml\n"));
+        assert!(unit.contains(&format!("Description={} (tanaka)\n", t!("autostart.unit_description"))));
+
+        assert_eq!(entry_name("metaguard", None), "metaguard");
+        assert_eq!(entry_name("metaguard", Some("tanaka")), "metaguard-tanaka");
+        // ファイル名に使えない文字を含む名前は重ならないようにハッシュを付ける
+        let a = entry_name("metaguard", Some("田中"));
+        let b = entry_name("metaguard", Some("佐藤"));
+        assert!(a.starts_with("metaguard-__-") && a != b, "{} {}", a, b);
+    }
+
+    #[test]
+    fn backends_are_selected_by_name() {
+        assert_eq!(backend_for("registry").unwrap().name(), "registry");
+        assert_eq!(backend_for("SYSTEMD").unwrap().name(), "systemd");
+        assert_eq!(backend_for("xdg").unwrap().name(), "xdg");
+        assert!(backend_for("auto").is_some());
+        assert!(backend_for("launchd").is_none());
+    }
+}
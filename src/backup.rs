@@ -0,0 +1,239 @@
+//! 設定ファイルの安全な書き込みとバックアップ
+//!
+//! 設定ファイルは同じフォルダの一時ファイルに書いてから置き換える（[`write_atomic`]）。
+//! 書き込みの途中で落ちたりディスクがいっぱいになったりしても、元のファイルは壊れない。
+//!
+//! 上書きする前の内容は `MetaGuard.ini.20261018-093000.bak` のような日時付きのファイルに残し、
+//! 新しいものから設定の `ConfigBackups` 個（既定5個）だけ残す。版の移行で作ったバックアップ
+//! （`MetaGuard.ini.v1.20261018-093000.bak`）は数えず、消さない。
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+
+/// 残すバックアップの数の既定値
+pub const DEFAULT_KEEP: usize = 5;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// 設定の `ConfigBackups`（残すバックアップの数。0 ならバックアップしない）
+pub fn keep_count(config: &HashMap<String, String>) -> usize {
+    config
+        .get("ConfigBackups")
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(DEFAULT_KEEP)
+}
+
+/// 一時ファイルに書いてから置き換える
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// 今の内容をバックアップしてから書き込む（作ったバックアップを返す）
+///
+/// 内容が変わらなければ何もしない。同じ秒に何度も保存したときは、その秒の最初の内容だけを残す。
+pub fn write_with_backup(path: &Path, content: &str, keep: usize, now: DateTime<Local>) -> io::Result<Option<PathBuf>> {
+    let current = match fs::read_to_string(path) {
+        Ok(current) => Some(current),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if current.as_deref() == Some(content) {
+        return Ok(None);
+    }
+
+    let mut backup = None;
+    if let (Some(current), true) = (current, keep > 0) {
+        let path = backup_path(path, now);
+        if !path.exists() {
+            write_atomic(&path, &current)?;
+        }
+        backup = Some(path);
+    }
+    write_atomic(path, content)?;
+    if keep > 0 {
+        prune(path, keep)?;
+    }
+    Ok(backup)
+}
+
+/// バックアップのファイル名（例: `MetaGuard.ini.20261018-093000.bak`）
+pub fn backup_path(path: &Path, now: DateTime<Local>) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.bak", now.format(TIMESTAMP_FORMAT)));
+    path.with_file_name(name)
+}
+
+/// 1つのバックアップ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: NaiveDateTime,
+    /// 版の移行で作ったバックアップなら移行前の版
+    pub migrated_from: Option<u32>,
+}
+
+/// 設定ファイルのバックアップの一覧（新しい順）
+pub fn list(path: &Path) -> io::Result<Vec<Backup>> {
+    let Some(file_name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+        return Ok(Vec::new());
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(middle) = name
+            .strip_prefix(&file_name)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.strip_suffix(".bak"))
+        else {
+            continue;
+        };
+        let (migrated_from, timestamp) = match middle.split_once('.') {
+            Some((version, timestamp)) => match version.strip_prefix('v').and_then(|v| v.parse().ok()) {
+                Some(version) => (Some(version), timestamp),
+                None => continue,
+            },
+            None => (None, middle),
+        };
+        if let Ok(created) = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT) {
+            backups.push(Backup {
+                path: entry.path(),
+                created,
+                migrated_from,
+            });
+        }
+    }
+    backups.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.path.cmp(&a.path)));
+    Ok(backups)
+}
+
+/// 古いバックアップを消して `keep` 個にする（移行のバックアップは残す）
+fn prune(path: &Path, keep: usize) -> io::Result<()> {
+    for backup in list(path)?.into_iter().filter(|b| b.migrated_from.is_none()).skip(keep) {
+        fs::remove_file(backup.path)?;
+    }
+    Ok(())
+}
+
+/// 2つの設定の違い（`from` を `to` にしたときに何が変わるか）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// 変わる項目（キー・変更前・変更後）
+    pub changed: Vec<(String, String, String)>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// 2つの設定を比べる（キー順）
+pub fn diff(from: &HashMap<String, String>, to: &HashMap<String, String>) -> ConfigDiff {
+    let keys: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+    let mut result = ConfigDiff::default();
+    for key in keys {
+        match (from.get(key), to.get(key)) {
+            (None, Some(_)) => result.added.push(key.clone()),
+            (Some(_), None) => result.removed.push(key.clone()),
+            (Some(a), Some(b)) if a != b => result.changed.push((key.clone(), a.clone(), b.clone())),
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "metaguard-backup-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn at(second: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 18, 9, 30, second).unwrap()
+    }
+
+    #[test]
+    fn saves_keep_a_limited_number_of_backups() {
+        let dir = scratch_dir("rotate");
+        let path = dir.join("MetaGuard.ini");
+        fs::write(dir.join("MetaGuard.ini.v1.20261001-000000.bak"), "old").unwrap();
+
+        assert_eq!(write_with_backup(&path, "CheckInterval=0\n", 2, at(0)).unwrap(), None);
+        for second in 1..=3 {
+            let backup = write_with_backup(&path, &format!("CheckInterval={}\n", second), 2, at(second)).unwrap();
+            assert_eq!(backup, Some(backup_path(&path, at(second))));
+        }
+        // 内容が同じなら書かない
+        assert_eq!(write_with_backup(&path, "CheckInterval=3\n", 2, at(4)).unwrap(), None);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "CheckInterval=3\n");
+        let backups = list(&path).unwrap();
+        let names: Vec<String> = backups
+            .iter()
+            .map(|b| b.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "MetaGuard.ini.20261018-093003.bak",
+                "MetaGuard.ini.20261018-093002.bak",
+                "MetaGuard.ini.v1.20261001-000000.bak",
+            ]
+        );
+        assert_eq!(fs::read_to_string(&backups[0].path).unwrap(), "CheckInterval=2\n");
+        assert_eq!(backups[2].migrated_from, Some(1));
+        // 一時ファイルは残らない
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn diff_lists_added_removed_and_changed_keys() {
+        let map = |entries: &[(&str, &str)]| -> HashMap<String, String> {
+            entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let from = map(&[("CheckInterval", "30"), ("MT_1", "1|A|a.exe"), ("MT_2", "1|B|b.exe")]);
+        let to = map(&[("CheckInterval", "60"), ("MT_1", "1|A|a.exe"), ("MT_3", "1|C|c.exe")]);
+        let result = diff(&from, &to);
+        assert_eq!(result.added, ["MT_3"]);
+        assert_eq!(result.removed, ["MT_2"]);
+        assert_eq!(result.changed, [("CheckInterval".to_string(), "30".to_string(), "60".to_string())]);
+        assert!(diff(&from, &from).is_empty());
+    }
+}
@@ -0,0 +1,807 @@
+//! 設定ファイルの形式（INI・TOML・JSON）
+//!
+//! 監視エンジンは設定を `キー → 値` の表（INI と同じ形）で受け取る。TOML と JSON は
+//! 読み込むときにこの表に直し、書き出すときは表から作る。どの形式も項目と型は同じで、
+//! INI の `MT_番号=ON|表示名|パス` と `MT_番号.項目名=値` は `Instances` の1要素になる。
+//!
+//! ```toml
+//! [Settings]
+//! CheckInterval = 30
+//! AutoStart = true
+//!
+//! [[Instances]]
+//! Key = "MT_1"
+//! Enabled = true
+//! Name = "XM本番口座"
+//! Path = 'C:\Program Files\XM MT4\terminal.exe'
+//! Interval = 10
+//! ```
+//!
+//! 数値や ON/OFF の項目に読めない値があれば、黙って既定値にせずエラーにする
+//! （形式を変換したときに値が消えないようにするため）。
+//!
+//! 設定ファイルの場所は `--config`、環境変数 `MG_CONFIG`、実行ファイルと同じフォルダ、
+//! ユーザーごとの設定フォルダの順に決める（[`locate`]）。`--config` と `MG_CONFIG` で
+//! 指定した設定ファイルは「プロファイル」として扱い、自動起動もプロファイルごとに登録する。
+//!
+//! `Include=` に書いた設定ファイル（カンマ区切りで複数可）は共通の設定として先に読み、
+//! その上にこのファイルの設定を重ねる（[`apply_includes`]）。VPSごとの違いだけを書けばよい。
+//!
+//! ```ini
+//! Include=%USERPROFILE%\MetaGuard\shared.ini
+//! MT_1=ON|XM|D:\MT4\terminal.exe
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::expand::expand;
+use crate::instance::{is_instance_key, is_option_of, key_order, option_key};
+use crate::{migration, t};
+
+/// 設定ファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Ini,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// 拡張子から形式を決める（`.ini` / `.toml` / `.json`。それ以外は None）
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "ini" => Some(ConfigFormat::Ini),
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Ini => "INI",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+        }
+    }
+}
+
+/// 設定ファイルの場所を指定する環境変数
+pub const CONFIG_ENV: &str = "MG_CONFIG";
+
+/// 設定ファイルを探すときの名前（見つかった最初のものを使う）
+pub const CONFIG_FILE_NAMES: &[&str] = &["MetaGuard.ini", "MetaGuard.toml", "MetaGuard.json"];
+
+/// 設定ファイルの場所をどう決めたか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// `--config` で指定
+    Argument,
+    /// 環境変数 `MG_CONFIG` で指定
+    Environment,
+    /// 実行ファイルと同じフォルダ
+    ExeDir,
+    /// ユーザーごとの設定フォルダ
+    UserDir,
+}
+
+/// 使う設定ファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    pub source: ConfigSource,
+}
+
+impl ConfigLocation {
+    /// プロファイル名（`--config` か `MG_CONFIG` で指定したときだけ）
+    ///
+    /// ファイル名（拡張子なし）から作る。`MetaGuard.ini` のように既定の名前なら
+    /// フォルダ名を使う（例: `C:\clients\tanaka\MetaGuard.ini` → `tanaka`）。
+    pub fn profile(&self) -> Option<String> {
+        if !matches!(self.source, ConfigSource::Argument | ConfigSource::Environment) {
+            return None;
+        }
+        let stem = self.path.file_stem()?.to_string_lossy().to_string();
+        let name = if stem.eq_ignore_ascii_case("MetaGuard") {
+            self.path.parent()?.file_name()?.to_string_lossy().to_string()
+        } else {
+            stem
+        };
+        Some(name)
+    }
+}
+
+/// 使う設定ファイルを決める
+///
+/// 1. `--config` で指定したファイル
+/// 2. 環境変数 `MG_CONFIG` で指定したファイル
+/// 3. 実行ファイルと同じフォルダにある設定ファイル
+/// 4. ユーザーごとの設定フォルダにある設定ファイル
+///
+/// どこにもなければ、実行ファイルと同じフォルダに書き込めるならそこに、
+/// 書き込めなければ（Program Files など）ユーザーごとの設定フォルダに作る。
+pub fn locate(
+    argument: Option<&Path>,
+    environment: Option<&Path>,
+    exe_dir: &Path,
+    user_dir: Option<&Path>,
+    writable: impl Fn(&Path) -> bool,
+) -> ConfigLocation {
+    let location = |path: &Path, source| ConfigLocation { path: absolute(path), source };
+    if let Some(path) = argument {
+        return location(path, ConfigSource::Argument);
+    }
+    if let Some(path) = environment.filter(|p| !p.as_os_str().is_empty()) {
+        return location(path, ConfigSource::Environment);
+    }
+    let existing = |dir: &Path| CONFIG_FILE_NAMES.iter().map(|name| dir.join(name)).find(|p| p.exists());
+    if let Some(path) = existing(exe_dir) {
+        return location(&path, ConfigSource::ExeDir);
+    }
+    if let Some(path) = user_dir.and_then(existing) {
+        return location(&path, ConfigSource::UserDir);
+    }
+    match user_dir {
+        Some(dir) if !writable(exe_dir) => location(&dir.join(CONFIG_FILE_NAMES[0]), ConfigSource::UserDir),
+        _ => location(&exe_dir.join(CONFIG_FILE_NAMES[0]), ConfigSource::ExeDir),
+    }
+}
+
+/// ユーザーごとの設定フォルダ（Windows は `%APPDATA%\MetaGuard`、それ以外は `~/.config/metaguard`）
+pub fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("MetaGuard"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("metaguard"))
+    }
+}
+
+/// フォルダにファイルを作れるか（試しに作って消す）
+pub fn is_writable_dir(dir: &Path) -> bool {
+    let probe = dir.join(format!(".metaguard-write-test-{}", std::process::id()));
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// 相対パスは今のフォルダからのパスにする（自動起動やロックで同じファイルを指すように）
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// 共通の設定ファイルを指定するキー
+pub const INCLUDE_KEY: &str = "Include";
+
+/// `Include=` をたどる深さの上限
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+impl ConfigFormat {
+    /// 設定ファイルの形式（拡張子が .toml / .json 以外なら INI）
+    pub fn for_path(path: &Path) -> Self {
+        ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Ini)
+    }
+}
+
+/// 設定ファイルを読んで今の版にする（`Include=` はたどらない）
+pub fn read_file(path: &Path) -> Result<HashMap<String, String>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut config = parse(ConfigFormat::for_path(path), &content)?;
+    migration::migrate(&mut config)?;
+    Ok(config)
+}
+
+/// `Include=` に書かれた設定ファイル（環境変数を展開し、相対パスは `dir` から）
+pub fn include_paths(config: &HashMap<String, String>, dir: &Path) -> Vec<PathBuf> {
+    config
+        .get(INCLUDE_KEY)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|path| expand(path.trim()))
+                .filter(|path| !path.is_empty())
+                .map(|path| dir.join(path))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `Include=` で読み込む共通の設定（後に書いたファイルほど優先）
+pub fn included_settings(config: &HashMap<String, String>, dir: &Path) -> Result<HashMap<String, String>, String> {
+    Ok(load_included(config, dir, &mut Vec::new())?
+        .into_iter()
+        .map(|(key, (value, _))| (key, value))
+        .collect())
+}
+
+/// 共通の設定の項目ごとの、値を書いている共通の設定ファイル
+pub fn included_sources(config: &HashMap<String, String>, dir: &Path) -> Result<HashMap<String, PathBuf>, String> {
+    Ok(load_included(config, dir, &mut Vec::new())?
+        .into_iter()
+        .map(|(key, (_, path))| (key, path))
+        .collect())
+}
+
+/// 共通の設定から来た項目を消していないか確かめる
+///
+/// このファイルから消しても次に読んだときに共通の設定から戻ってしまうため、
+/// 消した項目があれば、その項目を書いている共通の設定ファイルを示してエラーにする。
+pub fn check_included_removals(config: &HashMap<String, String>, dir: &Path) -> Result<(), String> {
+    let sources = included_sources(config, dir)?;
+    let mut removed: Vec<(&String, &PathBuf)> = sources.iter().filter(|(key, _)| !config.contains_key(*key)).collect();
+    removed.sort();
+    match removed.first() {
+        Some((key, path)) => Err(t!("config.included_removal", key = key, path = path.display())),
+        None => Ok(()),
+    }
+}
+
+fn load_included(
+    config: &HashMap<String, String>,
+    dir: &Path,
+    chain: &mut Vec<PathBuf>,
+) -> Result<HashMap<String, (String, PathBuf)>, String> {
+    let mut settings = HashMap::new();
+    for path in include_paths(config, dir) {
+        if chain.contains(&path) || chain.len() >= MAX_INCLUDE_DEPTH {
+            return Err(t!("config.include_loop", path = path.display()));
+        }
+        let mut included = read_file(&path)
+            .map_err(|e| t!("config.include_failed", path = path.display(), error = e))?;
+        chain.push(path.clone());
+        let nested = load_included(&included, path.parent().unwrap_or(dir), chain)?;
+        chain.pop();
+
+        included.remove(INCLUDE_KEY);
+        included.remove(migration::VERSION_KEY);
+        settings.extend(nested);
+        settings.extend(included.into_iter().map(|(key, value)| (key, (value, path.clone()))));
+    }
+    Ok(settings)
+}
+
+/// 共通の設定の上にこの設定を重ねる
+pub fn apply_includes(config: HashMap<String, String>, dir: &Path) -> Result<HashMap<String, String>, String> {
+    let mut merged = included_settings(&config, dir)?;
+    merged.extend(config);
+    Ok(merged)
+}
+
+/// 共通の設定と同じ値を除く（保存するときに使う）
+pub fn strip_included(config: &HashMap<String, String>, included: &HashMap<String, String>) -> HashMap<String, String> {
+    config
+        .iter()
+        .filter(|(key, value)| included.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// 設定の文字列と型付きの値の変換
+trait ConfigValue: Sized {
+    /// 設定の文字列から読む（読めなければ None）
+    fn parse_value(text: &str) -> Option<Self>;
+    fn format_value(&self) -> String;
+    /// 空欄を「未指定」として扱うか（文字列の項目は空欄も値として残す）
+    const EMPTY_IS_UNSET: bool = true;
+}
+
+macro_rules! number_value {
+    ($($ty:ty),*) => {
+        $(
+            impl ConfigValue for $ty {
+                fn parse_value(text: &str) -> Option<Self> {
+                    text.trim().parse().ok()
+                }
+
+                fn format_value(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+number_value!(u32, u64, i32, i64, f32);
+
+impl ConfigValue for bool {
+    fn parse_value(text: &str) -> Option<Self> {
+        match text.trim().to_uppercase().as_str() {
+            "ON" | "TRUE" | "1" => Some(true),
+            "OFF" | "FALSE" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn format_value(&self) -> String {
+        if *self { "1" } else { "0" }.to_string()
+    }
+}
+
+impl ConfigValue for String {
+    fn parse_value(text: &str) -> Option<Self> {
+        Some(text.to_string())
+    }
+
+    fn format_value(&self) -> String {
+        self.clone()
+    }
+
+    const EMPTY_IS_UNSET: bool = false;
+}
+
+fn read_value<T: ConfigValue>(values: &mut BTreeMap<String, String>, name: &str, prefix: &str) -> Result<Option<T>, String> {
+    let Some(text) = values.remove(name) else {
+        return Ok(None);
+    };
+    if T::EMPTY_IS_UNSET && text.trim().is_empty() {
+        return Ok(None);
+    }
+    T::parse_value(&text)
+        .map(Some)
+        .ok_or_else(|| t!("config.invalid_value", key = format!("{}{}", prefix, name), value = text))
+}
+
+/// 型付きの項目と、それ以外の項目（文字列のまま残す）を持つ構造体を作る
+macro_rules! typed_options {
+    ($(#[$meta:meta])* $name:ident { $($(#[$field_meta:meta])* $field:ident: $ty:ty = $key:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+        pub struct $name {
+            $(
+                $(#[$field_meta])*
+                #[serde(rename = $key, default, skip_serializing_if = "Option::is_none")]
+                pub $field: Option<$ty>,
+            )*
+            /// 上記以外の項目
+            #[serde(flatten)]
+            pub other: BTreeMap<String, String>,
+        }
+
+        impl $name {
+            /// 型付きの項目名（書いた順）
+            pub const KEYS: &'static [&'static str] = &[$($key),*];
+
+            /// 項目名 → 値 の表から読む（`prefix` はエラーの表示に付けるキーの前置き）
+            pub(crate) fn from_values(mut values: BTreeMap<String, String>, prefix: &str) -> Result<Self, String> {
+                Ok($name {
+                    $($field: read_value(&mut values, $key, prefix)?,)*
+                    other: values,
+                })
+            }
+
+            /// 項目名 → 値 の表に戻す
+            pub(crate) fn to_values(&self) -> Vec<(String, String)> {
+                let mut values = Vec::new();
+                $(
+                    if let Some(value) = &self.$field {
+                        values.push(($key.to_string(), value.format_value()));
+                    }
+                )*
+                values.extend(self.other.iter().map(|(k, v)| (k.clone(), v.clone())));
+                values
+            }
+        }
+    };
+}
+
+typed_options!(
+    /// 全体の設定（INI の `[Settings]`）
+    Settings {
+        config_version: u32 = "ConfigVersion",
+        include: String = "Include",
+        check_interval: u64 = "CheckInterval",
+        auto_start: bool = "AutoStart",
+        auto_start_backend: String = "AutoStartBackend",
+        start_timeout: u64 = "StartTimeout",
+        max_restarts_per_hour: u32 = "MaxRestartsPerHour",
+        startup_delay: u64 = "StartupDelay",
+        launch_stagger: u64 = "LaunchStagger",
+        stop_timeout: u64 = "StopTimeout",
+        language: String = "Language",
+        config_backups: u32 = "ConfigBackups",
+        time_zone: String = "TimeZone",
+        maintenance: String = "Maintenance",
+        blackout: String = "Blackout",
+        holidays: String = "Holidays",
+        schedule_mode: String = "ScheduleMode",
+        alert_command: String = "AlertCommand",
+    }
+);
+
+typed_options!(
+    /// インスタンスごとの追加項目（INI の `MT_番号.項目名`）
+    InstanceOptions {
+        args: String = "Args",
+        launcher: String = "Launcher",
+        wine_prefix: String = "WinePrefix",
+        wine_binary: String = "WineBinary",
+        interval: u64 = "Interval",
+        priority: i32 = "Priority",
+        stop_timeout: u64 = "StopTimeout",
+        depends_on: String = "DependsOn",
+        group: String = "Group",
+        tags: String = "Tags",
+        maintenance: String = "Maintenance",
+        blackout: String = "Blackout",
+        holidays: String = "Holidays",
+        time_zone: String = "TimeZone",
+        schedule_mode: String = "ScheduleMode",
+        restart_schedule: String = "RestartSchedule",
+        max_memory_mb: u64 = "MaxMemoryMB",
+        max_cpu_percent: f32 = "MaxCpuPercent",
+        max_threads: u32 = "MaxThreads",
+        max_handles: u32 = "MaxHandles",
+        threshold_minutes: i64 = "ThresholdMinutes",
+        threshold_action: String = "ThresholdAction",
+    }
+);
+
+/// 1つのインスタンス（INI の `MT_番号=ON|表示名|パス` と追加項目）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceConfig {
+    #[serde(rename = "Key", default)]
+    pub key: String,
+    #[serde(rename = "Enabled", default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Path")]
+    pub path: String,
+    #[serde(flatten)]
+    pub options: InstanceOptions,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// 設定ファイル全体（TOML・JSON の形）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigFile {
+    #[serde(rename = "Settings", default)]
+    pub settings: Settings,
+    #[serde(rename = "Instances", default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<InstanceConfig>,
+}
+
+impl ConfigFile {
+    /// `キー → 値` の表から作る（型に合わない値があればエラー）
+    pub fn from_map(config: &HashMap<String, String>) -> Result<Self, String> {
+        let mut keys: Vec<&String> = config.keys().filter(|k| is_instance_key(k)).collect();
+        keys.sort_by_key(|k| key_order(k));
+
+        let mut instances = Vec::new();
+        for key in &keys {
+            let value = &config[*key];
+            let parts: Vec<&str> = value.split('|').collect();
+            if parts.len() != 3 {
+                return Err(t!("config.invalid_instance", key = key, value = value));
+            }
+            let options = config
+                .iter()
+                .filter(|(k, _)| is_option_of(k, key))
+                .map(|(k, v)| (k[key.len() + 1..].to_string(), v.clone()))
+                .collect();
+            instances.push(InstanceConfig {
+                key: key.to_string(),
+                enabled: parts[0] == "1",
+                name: parts[1].to_string(),
+                path: parts[2].to_string(),
+                options: InstanceOptions::from_values(options, &format!("{}.", key))?,
+            });
+        }
+
+        // インスタンスのない追加項目は全体の設定の「その他」として残す
+        let settings = config
+            .iter()
+            .filter(|(k, _)| !keys.contains(k) && !keys.iter().any(|key| is_option_of(k, key)))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        Ok(ConfigFile {
+            settings: Settings::from_values(settings, "")?,
+            instances,
+        })
+    }
+
+    /// `キー → 値` の表に戻す
+    pub fn to_map(&self) -> Result<HashMap<String, String>, String> {
+        let mut config: HashMap<String, String> = self.settings.to_values().into_iter().collect();
+        for instance in &self.instances {
+            if !is_instance_key(&instance.key) {
+                return Err(t!("config.invalid_key", key = instance.key));
+            }
+            if config.contains_key(&instance.key) {
+                return Err(t!("config.duplicate_key", key = instance.key));
+            }
+            for (field, value) in [("Name", &instance.name), ("Path", &instance.path)] {
+                if value.contains('|') {
+                    return Err(t!("config.invalid_field", key = instance.key, field = field, value = value));
+                }
+            }
+            let enabled = if instance.enabled { "1" } else { "0" };
+            config.insert(instance.key.clone(), format!("{}|{}|{}", enabled, instance.name, instance.path));
+            for (name, value) in instance.options.to_values() {
+                config.insert(option_key(&instance.key, &name), value);
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// INI 形式の設定を読む（`;` で始まる行と `[セクション]` は無視する）
+///
+/// `AutoStart` とインスタンスの監視の有無は ON/OFF（TRUE/FALSE・1/0 も可）で書かれているので、
+/// 読み込んだ表では 1/0 にする。それ以外の値はそのまま残す（古い版の扱いは [`crate::migration`]）。
+pub fn parse_ini(content: &str) -> HashMap<String, String> {
+    let mut config = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        // コメントと空行をスキップ
+        if line.starts_with(';') || line.starts_with('[') || line.is_empty() {
+            continue;
+        }
+
+        // key=value の形式をパース
+        if let Some(pos) = line.find('=') {
+            let key = line[..pos].trim().to_string();
+            let mut value = line[pos + 1..].trim().to_string();
+
+            if key == "AutoStart" {
+                if let Some(flag) = ini_flag(&value) {
+                    value = flag.to_string();
+                }
+            } else if is_instance_key(&key) {
+                let parts: Vec<&str> = value.split('|').collect();
+                if let (3, Some(flag)) = (parts.len(), ini_flag(parts[0])) {
+                    value = format!("{}|{}|{}", flag, parts[1], parts[2]);
+                }
+            }
+
+            config.insert(key, value);
+        }
+    }
+
+    config
+}
+
+/// INI の ON/OFF を 1/0 にする
+fn ini_flag(value: &str) -> Option<&'static str> {
+    match value.to_uppercase().as_str() {
+        "ON" | "TRUE" | "1" => Some("1"),
+        "OFF" | "FALSE" | "0" => Some("0"),
+        _ => None,
+    }
+}
+
+/// TOML・JSON 形式の設定を読む（INI は [`parse_ini`]）
+pub fn parse(format: ConfigFormat, content: &str) -> Result<HashMap<String, String>, String> {
+    let file: ConfigFile = match format {
+        ConfigFormat::Ini => return Ok(parse_ini(content)),
+        ConfigFormat::Toml => toml::from_str(content).map_err(|e| e.to_string())?,
+        ConfigFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string())?,
+    };
+    file.to_map()
+}
+
+/// TOML・JSON 形式で書き出す（説明付きの INI は画面側で作る）
+pub fn render(format: ConfigFormat, config: &HashMap<String, String>) -> Result<String, String> {
+    let file = ConfigFile::from_map(config)?;
+    match format {
+        ConfigFormat::Ini => Err(t!("config.ini_not_structured")),
+        ConfigFormat::Toml => toml::to_string_pretty(&file).map_err(|e| e.to_string()),
+        ConfigFormat::Json => serde_json::to_string_pretty(&file)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// 変換後の設定に元の値がすべて残っているか（型付きの項目は値として比べる。例: `90.50` と `90.5`）
+///
+/// 変換先に増えた項目（INI に書き出す既定値など）は問わない。
+pub fn preserves(source: &HashMap<String, String>, converted: &HashMap<String, String>) -> Result<bool, String> {
+    let converted: HashMap<String, String> = converted
+        .iter()
+        .filter(|(k, _)| source.contains_key(*k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    Ok(ConfigFile::from_map(source)? == ConfigFile::from_map(&converted)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> HashMap<String, String> {
+        parse_ini(
+            "[Settings]\n\
+             CheckInterval=30\n\
+             AutoStart=OFF\n\
+             TimeZone=Europe/Athens\n\
+             Maintenance=\n\
+             CustomSetting=abc\n\
+             [MT4_MT5]\n\
+             MT_1=ON|XM MT4|C:\\Program Files\\XM MT4\\terminal.exe\n\
+             MT_1.Interval=10\n\
+             MT_1.MaxCpuPercent=90.5\n\
+             MT_1.Args=/portable \"/config:start.ini\"\n\
+             MT_1.Custom=x\n\
+             MT_2=OFF|OANDA|C:\\OANDA\\terminal64.exe\n\
+             MT_3.Args=orphan\n",
+        )
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert_eq!(ConfigFormat::from_path(Path::new("MetaGuard.ini")), Some(ConfigFormat::Ini));
+        assert_eq!(ConfigFormat::from_path(Path::new("/etc/mg/client.TOML")), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_path(Path::new("MetaGuard.json")), Some(ConfigFormat::Json));
+        assert_eq!(ConfigFormat::from_path(Path::new("MetaGuard.conf")), None);
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "metaguard-config-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn config_location_follows_the_precedence() {
+        let exe_dir = scratch_dir("exe");
+        let user_dir = scratch_dir("user");
+        let writable = |_: &Path| true;
+        let read_only = |_: &Path| false;
+
+        let explicit = exe_dir.join("clients").join("tanaka.toml");
+        let env = exe_dir.join("sato").join("MetaGuard.ini");
+        let found = locate(Some(&explicit), Some(&env), &exe_dir, Some(&user_dir), writable);
+        assert_eq!(found.source, ConfigSource::Argument);
+        assert_eq!(found.profile().as_deref(), Some("tanaka"));
+        let found = locate(None, Some(&env), &exe_dir, Some(&user_dir), writable);
+        assert_eq!((found.path.as_path(), found.source), (env.as_path(), ConfigSource::Environment));
+        assert_eq!(found.profile().as_deref(), Some("sato"));
+
+        // どこにもなければ、書き込めるなら実行ファイルの隣、書き込めなければユーザーの設定フォルダ
+        let found = locate(None, None, &exe_dir, Some(&user_dir), writable);
+        assert_eq!(found.path, exe_dir.join("MetaGuard.ini"));
+        assert_eq!(found.profile(), None);
+        let found = locate(None, None, &exe_dir, Some(&user_dir), read_only);
+        assert_eq!(found.path, user_dir.join("MetaGuard.ini"));
+
+        // 既にある設定ファイルを使う
+        std::fs::write(user_dir.join("MetaGuard.toml"), "").unwrap();
+        let found = locate(None, None, &exe_dir, Some(&user_dir), writable);
+        assert_eq!((found.path, found.source), (user_dir.join("MetaGuard.toml"), ConfigSource::UserDir));
+        std::fs::write(exe_dir.join("MetaGuard.json"), "").unwrap();
+        let found = locate(None, None, &exe_dir, Some(&user_dir), read_only);
+        assert_eq!(found.path, exe_dir.join("MetaGuard.json"));
+
+        let _ = std::fs::remove_dir_all(&exe_dir);
+        let _ = std::fs::remove_dir_all(&user_dir);
+    }
+
+    #[test]
+    fn includes_are_overridden_by_the_including_file() {
+        let dir = scratch_dir("include");
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        std::fs::write(
+            dir.join("common").join("shared.ini"),
+            "Include=schedule.toml\nAlertCommand=notify.sh\nCheckInterval=60\nMT_9=ON|Shared|C:\\MT4\\terminal.exe\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("common").join("schedule.toml"), "[Settings]\nMaintenance = \"Sat 00:00-Mon 00:30\"\nCheckInterval = 45\n").unwrap();
+
+        let host = parse_ini("Include=common/shared.ini\nCheckInterval=15\nMT_1=ON|XM|D:\\MT4\\terminal.exe\n");
+        let merged = apply_includes(host.clone(), &dir).unwrap();
+        assert_eq!(merged["CheckInterval"], "15");
+        assert_eq!(merged["AlertCommand"], "notify.sh");
+        assert_eq!(merged["Maintenance"], "Sat 00:00-Mon 00:30");
+        assert_eq!(merged["MT_9"], "1|Shared|C:\\MT4\\terminal.exe");
+        assert_eq!(merged["Include"], "common/shared.ini");
+
+        // 保存するときは共通の設定と同じ値を書かない
+        let included = included_settings(&merged, &dir).unwrap();
+        assert_eq!(strip_included(&merged, &included), host);
+
+        // 共通の設定から来たインスタンスは消せない（保存しても次に読んだときに戻るため）
+        let mut removed = merged.clone();
+        removed.remove("MT_9");
+        let error = check_included_removals(&removed, &dir).unwrap_err();
+        assert!(error.contains("MT_9") && error.contains("shared.ini"), "{}", error);
+        let reloaded = apply_includes(strip_included(&removed, &included), &dir).unwrap();
+        assert!(reloaded.contains_key("MT_9"));
+        // このファイルのものは消せる
+        let mut removed = merged.clone();
+        removed.remove("MT_1");
+        assert!(check_included_removals(&removed, &dir).is_ok());
+        assert!(!apply_includes(strip_included(&removed, &included), &dir).unwrap().contains_key("MT_1"));
+
+        // 自分自身を読み込むとエラー
+        std::fs::write(dir.join("common").join("schedule.toml"), "[Settings]\nInclude = \"shared.ini\"\n").unwrap();
+        assert!(apply_includes(host, &dir).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn map_becomes_typed_schema() {
+        let file = ConfigFile::from_map(&sample()).unwrap();
+        assert_eq!(file.settings.check_interval, Some(30));
+        assert_eq!(file.settings.auto_start, Some(false));
+        assert_eq!(file.settings.maintenance.as_deref(), Some(""));
+        assert_eq!(file.settings.other["CustomSetting"], "abc");
+        // インスタンスのない追加項目も失わない
+        assert_eq!(file.settings.other["MT_3.Args"], "orphan");
+
+        assert_eq!(file.instances.len(), 2);
+        let first = &file.instances[0];
+        assert_eq!((first.key.as_str(), first.enabled, first.name.as_str()), ("MT_1", true, "XM MT4"));
+        assert_eq!(first.options.interval, Some(10));
+        assert_eq!(first.options.max_cpu_percent, Some(90.5));
+        assert_eq!(first.options.other["Custom"], "x");
+        assert!(!file.instances[1].enabled);
+    }
+
+    #[test]
+    fn toml_and_json_round_trip() {
+        let config = sample();
+        for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+            let text = render(format, &config).unwrap();
+            assert_eq!(parse(format, &text).unwrap(), config, "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn toml_uses_native_types() {
+        let text = render(ConfigFormat::Toml, &sample()).unwrap();
+        assert!(text.contains("CheckInterval = 30\n"), "{}", text);
+        assert!(text.contains("AutoStart = false\n"), "{}", text);
+        assert!(text.contains("[[Instances]]"), "{}", text);
+        assert!(text.contains("Interval = 10\n"), "{}", text);
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let mut config = sample();
+        config.insert("MT_1.Interval".to_string(), "ten".to_string());
+        let error = ConfigFile::from_map(&config).unwrap_err();
+        assert!(error.contains("MT_1.Interval"), "{}", error);
+
+        let json = r#"{"Settings": {"CheckInterval": "soon"}}"#;
+        assert!(parse(ConfigFormat::Json, json).is_err());
+
+        let toml = "[[Instances]]\nKey = \"MT_1\"\nName = \"A|B\"\nPath = \"x.exe\"\n";
+        assert!(parse(ConfigFormat::Toml, toml).is_err());
+    }
+
+    #[test]
+    fn conversion_check_ignores_added_defaults() {
+        let mut source = sample();
+        // 空欄の数値は未指定と同じ
+        source.insert("StartupDelay".to_string(), String::new());
+        let mut converted = sample();
+        converted.insert("StartTimeout".to_string(), "60".to_string());
+        converted.insert("MT_1.MaxCpuPercent".to_string(), "90.50".to_string());
+        assert!(preserves(&source, &converted).unwrap());
+
+        converted.remove("MT_1.Interval");
+        assert!(!preserves(&source, &converted).unwrap());
+    }
+}
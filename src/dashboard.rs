@@ -0,0 +1,479 @@
+//! 監視画面（TUI）
+//!
+//! 監視モードでは、インスタンスの一覧（状態・PID・稼働時間・CPU・メモリ・
+//! 再起動回数・最後の出来事）と出来事のログを1画面に表示する。
+//! 画面の内容とキー操作の解釈はここで受け持ち、監視やチェックの実行は
+//! `main.rs` が行う。
+//!
+//! | キー | 操作 |
+//! |------|------|
+//! | ↑ / ↓（k / j） | インスタンスを選ぶ |
+//! | Enter | 詳細を表示する |
+//! | r | 再起動する |
+//! | e | 有効/無効を切り替える |
+//! | c | 今すぐチェックする |
+//! | q / Esc | 監視を終了する |
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Duration, Local, Utc};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+
+use crate::clock::Clock;
+use crate::instance::{LaunchMode, MtInstance};
+use crate::launcher::Launcher;
+use crate::monitor::{InstanceRuntime, InstanceState, Monitor};
+use crate::process::ProcessTable;
+use crate::resources::ResourceUsage;
+use crate::t;
+
+/// ログに残す行数
+const LOG_CAPACITY: usize = 200;
+
+/// キー操作で要求された処理
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    /// 選んだインスタンスを再起動する
+    Restart(String),
+    /// 選んだインスタンスの有効/無効を切り替える
+    Toggle(String),
+    /// 今すぐすべてのインスタンスを確認する
+    Check,
+}
+
+/// 一覧の1行
+#[derive(Debug, Clone)]
+pub struct InstanceRow {
+    pub key: String,
+    pub name: String,
+    pub enabled: bool,
+    pub state: String,
+    /// 状態の表示色
+    pub color: Color,
+    pub pid: Option<u32>,
+    pub uptime: Option<Duration>,
+    pub usage: Option<ResourceUsage>,
+    /// 直近1時間の再起動回数
+    pub restarts: usize,
+    /// 詳細画面に出す行
+    pub details: Vec<String>,
+}
+
+impl InstanceRow {
+    pub fn new(instance: &MtInstance, runtime: Option<&InstanceRuntime>, now: DateTime<Utc>) -> Self {
+        let (state, color) = match runtime {
+            _ if !instance.enabled => (t!("dashboard.state.disabled"), Color::DarkGray),
+            Some(runtime) => (state_label(&runtime.state), state_color(&runtime.state)),
+            None => (state_label(&InstanceState::Unknown), state_color(&InstanceState::Unknown)),
+        };
+        let (pid, since) = match runtime.map(|r| &r.state) {
+            Some(InstanceState::Running { pid, since }) => (Some(*pid), Some(*since)),
+            Some(InstanceState::Starting { pid, .. }) => (Some(*pid), None),
+            Some(InstanceState::Stopping { stop, .. }) => (Some(stop.pid), None),
+            _ => (None, None),
+        };
+        let hour_ago = now - Duration::hours(1);
+        let restarts = runtime.map_or(0, |r| r.restarts.iter().filter(|t| **t > hour_ago).count());
+        let usage = runtime.and_then(|r| r.last_usage).filter(|_| pid.is_some());
+        let uptime = since.map(|since| now - since);
+
+        let mut details = vec![
+            t!("dashboard.detail.key", value = instance.key),
+            t!("dashboard.detail.name", value = instance.name),
+            t!("dashboard.detail.path", value = instance.path),
+        ];
+        if !instance.args.is_empty() {
+            details.push(t!("dashboard.detail.args", value = instance.args.join(" ")));
+        }
+        if instance.launch_mode == LaunchMode::Wine {
+            details.push(t!(
+                "dashboard.detail.launcher",
+                binary = instance.wine_binary,
+                prefix = instance.wine_prefix.clone().unwrap_or_else(|| t!("common.default")),
+            ));
+        }
+        if let Some(interval) = instance.interval {
+            details.push(t!("dashboard.detail.interval", secs = interval));
+        }
+        if instance.priority != 0 {
+            details.push(t!("dashboard.detail.priority", value = instance.priority));
+        }
+        if !instance.depends_on.is_empty() {
+            details.push(t!("dashboard.detail.depends_on", value = instance.depends_on.join(", ")));
+        }
+        details.push(t!("dashboard.detail.state", value = state));
+        if let Some(pid) = pid {
+            details.push(t!("dashboard.detail.pid", value = pid));
+        }
+        if let Some(uptime) = uptime {
+            details.push(t!("dashboard.detail.uptime", value = format_uptime(uptime)));
+        }
+        if let Some(usage) = usage {
+            details.push(t!("dashboard.detail.usage", value = usage));
+        }
+        details.push(t!("dashboard.detail.restarts", count = restarts));
+        if let Some(runtime) = runtime {
+            if runtime.consecutive_failures > 0 {
+                details.push(t!("dashboard.detail.failures", count = runtime.consecutive_failures));
+            }
+            if let Some(error) = &runtime.last_error {
+                details.push(t!("dashboard.detail.last_error", value = error));
+            }
+            if let Some(next) = runtime.next_check {
+                details.push(t!(
+                    "dashboard.detail.next_check",
+                    value = next.with_timezone(&Local).format("%H:%M:%S"),
+                ));
+            }
+        }
+
+        InstanceRow {
+            key: instance.key.clone(),
+            name: instance.name.clone(),
+            enabled: instance.enabled,
+            state,
+            color,
+            pid,
+            uptime,
+            usage,
+            restarts,
+            details,
+        }
+    }
+}
+
+/// 設定のインスタンス（無効なものも含む）と監視情報から一覧を作る
+pub fn rows<P: ProcessTable, L: Launcher, C: Clock>(
+    instances: &[MtInstance],
+    monitor: &Monitor<P, L, C>,
+    now: DateTime<Utc>,
+) -> Vec<InstanceRow> {
+    instances
+        .iter()
+        .map(|i| InstanceRow::new(i, monitor.runtime(&i.key), now))
+        .collect()
+}
+
+/// 状態の表示名
+pub fn state_label(state: &InstanceState) -> String {
+    match state {
+        InstanceState::Unknown => t!("dashboard.state.unknown"),
+        InstanceState::Running { .. } => t!("dashboard.state.running"),
+        InstanceState::Starting { .. } => t!("dashboard.state.starting"),
+        InstanceState::Backoff { .. } => t!("dashboard.state.backoff"),
+        InstanceState::CrashLoop => t!("dashboard.state.crash_loop"),
+        InstanceState::Pending { .. } => t!("dashboard.state.pending"),
+        InstanceState::Suspended => t!("dashboard.state.suspended"),
+        InstanceState::Stopping { .. } => t!("dashboard.state.stopping"),
+    }
+}
+
+/// 状態の表示色
+pub fn state_color(state: &InstanceState) -> Color {
+    match state {
+        InstanceState::Running { .. } => Color::Green,
+        InstanceState::Suspended => Color::DarkGray,
+        InstanceState::Unknown
+        | InstanceState::Starting { .. }
+        | InstanceState::Pending { .. }
+        | InstanceState::Stopping { .. } => Color::Yellow,
+        InstanceState::Backoff { .. } | InstanceState::CrashLoop => Color::Red,
+    }
+}
+
+/// 稼働時間の表示（例: `2日 03:15:00`）
+pub fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.num_seconds().max(0);
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    let clock = format!("{:02}:{:02}:{:02}", rest / 3600, rest % 3600 / 60, rest % 60);
+    if days > 0 {
+        t!("dashboard.days", days = days, clock = clock)
+    } else {
+        clock
+    }
+}
+
+/// 監視画面の状態
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    rows: Vec<InstanceRow>,
+    selected: usize,
+    log: VecDeque<String>,
+    /// インスタンスごとの最後の出来事
+    last_events: HashMap<String, String>,
+    show_details: bool,
+    /// 画面上部に出す文（次回チェックまでの時間など）
+    pub status: String,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Dashboard::default()
+    }
+
+    /// 一覧を入れ替える（選んでいるインスタンスはそのまま）
+    pub fn set_rows(&mut self, rows: Vec<InstanceRow>) {
+        let selected_key = self.selected_key().map(|k| k.to_string());
+        self.rows = rows;
+        self.selected = selected_key
+            .and_then(|key| self.rows.iter().position(|r| r.key == key))
+            .unwrap_or(self.selected)
+            .min(self.rows.len().saturating_sub(1));
+        if self.rows.is_empty() {
+            self.show_details = false;
+        }
+    }
+
+    pub fn rows(&self) -> &[InstanceRow] {
+        &self.rows
+    }
+
+    pub fn selected_key(&self) -> Option<&str> {
+        self.rows.get(self.selected).map(|r| r.key.as_str())
+    }
+
+    /// 出来事をログに追加する（複数行の文は1行にまとめる）
+    pub fn push_event(&mut self, key: &str, at: DateTime<Utc>, message: &str) {
+        let message = message
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.last_events.insert(key.to_string(), message.clone());
+        self.push_log(format!(
+            "{} [{}] {}",
+            at.with_timezone(&Local).format("%H:%M:%S"),
+            key,
+            message
+        ));
+    }
+
+    /// インスタンスに関係しない行をログに追加する
+    pub fn push_log(&mut self, line: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    /// キー操作を解釈する（画面の中だけで済む操作は `None`）
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        if self.show_details {
+            if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                self.show_details = false;
+            }
+            return None;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < self.rows.len() {
+                    self.selected += 1;
+                }
+                None
+            }
+            KeyCode::Enter => {
+                self.show_details = !self.rows.is_empty();
+                None
+            }
+            KeyCode::Char('r') => self
+                .rows
+                .get(self.selected)
+                .filter(|r| r.enabled)
+                .map(|r| Action::Restart(r.key.clone())),
+            KeyCode::Char('e') => self.selected_key().map(|k| Action::Toggle(k.to_string())),
+            KeyCode::Char('c') | KeyCode::F(5) => Some(Action::Check),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            _ => None,
+        }
+    }
+
+    /// 画面を描く
+    pub fn render(&self, frame: &mut Frame) {
+        let [header, table, log, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(t!("dashboard.header", status = self.status))
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+            header,
+        );
+        self.render_table(frame, table);
+        self.render_log(frame, log);
+        frame.render_widget(
+            Paragraph::new(t!("dashboard.footer"))
+                .style(Style::default().fg(Color::DarkGray)),
+            footer,
+        );
+        if self.show_details {
+            self.render_details(frame);
+        }
+    }
+
+    fn render_table(&self, frame: &mut Frame, area: Rect) {
+        let header = Row::new(
+            ["key", "name", "state", "pid", "uptime", "cpu", "memory", "restarts", "last_event"]
+                .map(|column| t!(&format!("dashboard.column.{}", column))),
+        )
+        .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = self.rows.iter().map(|row| {
+            Row::new(vec![
+                Cell::from(row.key.clone()),
+                Cell::from(row.name.clone()),
+                Cell::from(row.state.clone()).style(Style::default().fg(row.color)),
+                Cell::from(row.pid.map(|p| p.to_string()).unwrap_or_default()),
+                Cell::from(row.uptime.map(format_uptime).unwrap_or_default()),
+                Cell::from(row.usage.map(|u| format!("{:.1}%", u.cpu_percent)).unwrap_or_default()),
+                Cell::from(row.usage.map(|u| format!("{}MB", u.memory_mb)).unwrap_or_default()),
+                Cell::from(row.restarts.to_string()),
+                Cell::from(self.last_events.get(&row.key).cloned().unwrap_or_default()),
+            ])
+        });
+        let widths = [
+            Constraint::Length(6),
+            Constraint::Length(16),
+            Constraint::Length(10),
+            Constraint::Length(7),
+            Constraint::Length(12),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title(" MT4/MT5 "))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default().with_selected((!self.rows.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    fn render_log(&self, frame: &mut Frame, area: Rect) {
+        let visible = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(visible))
+            .map(|l| Line::from(l.as_str()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(format!(" {} ", t!("dashboard.log")))),
+            area,
+        );
+    }
+
+    fn render_details(&self, frame: &mut Frame) {
+        let Some(row) = self.rows.get(self.selected) else {
+            return;
+        };
+        let area = frame.area();
+        let height = (row.details.len() as u16 + 2).min(area.height);
+        let width = area.width.saturating_sub(8).min(100);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        let lines: Vec<Line> = row.details.iter().map(|l| Line::from(l.as_str())).collect();
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} ", t!("dashboard.details_title", name = row.name))),
+            ),
+            popup,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::parse_instances;
+    use chrono::TimeZone;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn sample() -> Dashboard {
+        let config: HashMap<String, String> = [
+            ("MT_1", r"1|XM|C:\XM\terminal.exe"),
+            ("MT_2", r"0|Demo|C:\Demo\terminal.exe"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let mut dashboard = Dashboard::new();
+        dashboard.set_rows(
+            parse_instances(&config)
+                .iter()
+                .map(|i| InstanceRow::new(i, None, now))
+                .collect(),
+        );
+        dashboard
+    }
+
+    #[test]
+    fn keys_select_and_act_on_an_instance() {
+        let mut dashboard = sample();
+        assert_eq!(dashboard.handle_key(press(KeyCode::Char('r'))), Some(Action::Restart("MT_1".to_string())));
+
+        dashboard.handle_key(press(KeyCode::Down));
+        // 無効なインスタンスは再起動できないが、有効にはできる
+        assert_eq!(dashboard.handle_key(press(KeyCode::Char('r'))), None);
+        assert_eq!(dashboard.handle_key(press(KeyCode::Char('e'))), Some(Action::Toggle("MT_2".to_string())));
+
+        // 詳細の表示中は q で詳細だけ閉じる
+        dashboard.handle_key(press(KeyCode::Enter));
+        assert_eq!(dashboard.handle_key(press(KeyCode::Char('q'))), None);
+        assert_eq!(dashboard.handle_key(press(KeyCode::Char('q'))), Some(Action::Quit));
+    }
+
+    #[test]
+    fn table_and_log_are_drawn() {
+        let mut dashboard = sample();
+        let at = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        dashboard.push_event("MT_1", at, "✗ XM - 停止中\n  → 起動しました！");
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 24)).unwrap();
+        terminal.draw(|f| dashboard.render(f)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(screen.contains("MT_1"));
+        assert!(screen.contains("Demo"));
+        // 全角文字は2マス使うため、ログは半角の部分で確認する
+        assert!(screen.contains("[MT_1]"));
+    }
+}
@@ -0,0 +1,99 @@
+//! 設定の値に書いた環境変数の展開
+//!
+//! インスタンスのパス・引数・Wineプレフィックスには `%USERNAME%`（Windowsの書き方）と
+//! `${HOME}`（Linuxの書き方）のどちらでも環境変数を書ける。VPSごとに違うドライブや
+//! ユーザー名を同じ設定ファイルで扱うためのもの。
+//!
+//! ```ini
+//! MT_1=ON|XM|%SystemDrive%\Users\%USERNAME%\MT4\terminal.exe
+//! MT_2=ON|OANDA|${MT_ROOT}/OANDA/terminal64.exe
+//! ```
+//!
+//! 定義されていない変数はそのまま残す（Windowsのコマンドプロンプトと同じ）。
+//! `%` や `$` を文字として書くときは `%%`・`$$` とする。
+//! 設定ファイルには展開前の値のまま保存する。
+
+/// 環境変数を展開する
+pub fn expand(value: &str) -> String {
+    expand_with(value, |name| std::env::var(name).ok())
+}
+
+/// `lookup` で変数の値を引いて展開する
+pub fn expand_with(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find(['%', '$']) {
+        result.push_str(&rest[..pos]);
+        let marker = rest.as_bytes()[pos] as char;
+        let after = &rest[pos + 1..];
+
+        // %% と $$ は文字の % と $
+        if after.starts_with(marker) {
+            result.push(marker);
+            rest = &after[1..];
+            continue;
+        }
+
+        let variable = match marker {
+            '%' => after.find('%').map(|end| (&after[..end], end + 1)),
+            _ => after
+                .strip_prefix('{')
+                .and_then(|inner| inner.find('}').map(|end| (&inner[..end], end + 2))),
+        };
+        match variable.filter(|(name, _)| is_variable_name(name)) {
+            Some((name, len)) => {
+                match lookup(name) {
+                    Some(found) => result.push_str(&found),
+                    None => result.push_str(&rest[pos..pos + 1 + len]),
+                }
+                rest = &after[len..];
+            }
+            None => {
+                result.push(marker);
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 変数名に使える文字か（`ProgramFiles(x86)` のような Windows の名前も含む）
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '(' | ')'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "USERNAME" => Some("tanaka".to_string()),
+            "MT_ROOT" => Some("/srv/mt".to_string()),
+            "ProgramFiles(x86)" => Some(r"C:\Program Files (x86)".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn both_styles_are_expanded() {
+        assert_eq!(
+            expand_with(r"C:\Users\%USERNAME%\MT4\terminal.exe", lookup),
+            r"C:\Users\tanaka\MT4\terminal.exe"
+        );
+        assert_eq!(expand_with("${MT_ROOT}/XM/terminal.exe", lookup), "/srv/mt/XM/terminal.exe");
+        assert_eq!(
+            expand_with(r"%ProgramFiles(x86)%\XM\terminal.exe", lookup),
+            r"C:\Program Files (x86)\XM\terminal.exe"
+        );
+    }
+
+    #[test]
+    fn unknown_variables_and_plain_text_are_left_alone() {
+        assert_eq!(expand_with("%NOPE%/${NOPE}", lookup), "%NOPE%/${NOPE}");
+        assert_eq!(expand_with("100% sure, $5 and 50%", lookup), "100% sure, $5 and 50%");
+        assert_eq!(expand_with("%%USERNAME%% costs $$5", lookup), "%USERNAME% costs $5");
+        assert_eq!(expand_with("/config:%USERNAME%.ini ${", lookup), "/config:tanaka.ini ${");
+    }
+}
@@ -0,0 +1,149 @@
+//! 監視の履歴
+//!
+//! 再起動・終了・定期再起動などの出来事を、設定ファイルと同じフォルダの
+//! `MetaGuard.ini.history` に1行ずつ追記する。形式はタブ区切りで
+//! `時刻 キー 種類 表示名 詳細`。
+//!
+//! 定期再起動は `planned_restart` として記録し、クラッシュによる再起動
+//! （`exit` の後の `launch`）とは区別する。リソースの上限超過（`threshold`）は
+//! 測定した値もあわせて記録する。終了させた場合は `stop` として、終了の依頼で
+//! 終わったか（`closed`）強制終了したか（`killed`）と待った秒数を記録する。
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+
+use crate::monitor::{EventKind, MonitorEvent};
+use crate::stop::StopResult;
+
+/// 設定ファイルに対応する履歴ファイルのパス（`MetaGuard.ini.history`。プロファイルごとに別のファイル）
+pub fn history_path_for(config_path: &Path) -> PathBuf {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".history");
+    config_path.with_file_name(name)
+}
+
+/// 履歴に残すイベントなら種類と詳細を返す（実行中の確認などは残さない）
+pub fn describe(event: &MonitorEvent) -> Option<(&'static str, String)> {
+    let described = match &event.kind {
+        EventKind::Launched { pid } => ("launch", format!("PID {}", pid)),
+        EventKind::LaunchFailed { error } => ("launch_failed", error.clone()),
+        EventKind::StartTimedOut { pid } => ("start_timeout", format!("PID {}", pid)),
+        EventKind::Exited { pid, code } => (
+            "exit",
+            match code {
+                Some(code) => format!("PID {} code {}", pid, code),
+                None => format!("PID {}", pid),
+            },
+        ),
+        EventKind::CrashLoop { restarts } => ("crash_loop", format!("{} restarts/hour", restarts)),
+        EventKind::StoppedForSchedule { pid, reason } => ("scheduled_stop", format!("PID {} {}", pid, reason)),
+        EventKind::StopFinished { outcome } => (
+            if outcome.result == StopResult::StillRunning {
+                "stop_failed"
+            } else {
+                "stop"
+            },
+            format!(
+                "PID {} {} {}s{}",
+                outcome.pid,
+                outcome.result.as_str(),
+                outcome.waited.num_seconds(),
+                outcome.error.as_ref().map(|e| format!(" {}", e)).unwrap_or_default()
+            ),
+        ),
+        EventKind::PlannedRestart { pid, at } => (
+            "planned_restart",
+            format!("PID {} {}", pid, at.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+        ),
+        EventKind::ManualRestart { pid } => (
+            "manual_restart",
+            pid.map(|pid| format!("PID {}", pid)).unwrap_or_default(),
+        ),
+        EventKind::PlannedRestartCompleted { pid } => ("planned_restart_done", format!("PID {}", pid)),
+        EventKind::ThresholdExceeded {
+            pid,
+            usage,
+            breaches,
+            restart,
+        } => (
+            "threshold",
+            format!(
+                "PID {} {} ({}){}",
+                pid,
+                breaches.join(", "),
+                usage,
+                if *restart { " restart" } else { "" }
+            ),
+        ),
+        _ => return None,
+    };
+    Some(described)
+}
+
+/// 履歴の1行
+pub fn render_line(event: &MonitorEvent) -> Option<String> {
+    let (kind, detail) = describe(event)?;
+    Some(format!(
+        "{}\t{}\t{}\t{}\t{}\n",
+        event.at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        event.key,
+        kind,
+        event.name,
+        detail.replace(['\t', '\n'], " ")
+    ))
+}
+
+/// 履歴に残すイベントを追記する
+pub fn append(path: &Path, events: &[MonitorEvent]) -> io::Result<()> {
+    let lines: String = events.iter().filter_map(render_line).collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn event(kind: EventKind) -> MonitorEvent {
+        MonitorEvent {
+            key: "MT_1".to_string(),
+            name: "XM".to_string(),
+            at: Utc.with_ymd_and_hms(2026, 10, 17, 3, 0, 0).unwrap(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn profiles_in_one_folder_get_their_own_history() {
+        assert_eq!(
+            history_path_for(Path::new(r"C:\clients\tanaka.json")),
+            Path::new(r"C:\clients\tanaka.json.history")
+        );
+        assert_ne!(
+            history_path_for(Path::new("/srv/tanaka.ini")),
+            history_path_for(Path::new("/srv/tanaka.json"))
+        );
+    }
+
+    #[test]
+    fn planned_restarts_are_told_apart_from_crashes() {
+        let planned = render_line(&event(EventKind::PlannedRestart {
+            pid: 500,
+            at: Utc.with_ymd_and_hms(2026, 10, 17, 3, 0, 0).unwrap(),
+        }))
+        .unwrap();
+        assert!(planned.contains("\tMT_1\tplanned_restart\tXM\tPID 500"));
+
+        let crash = render_line(&event(EventKind::Launched { pid: 501 })).unwrap();
+        assert!(crash.contains("\tlaunch\t"));
+
+        assert!(render_line(&event(EventKind::Running { pid: 500 })).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+//! 表示する文の翻訳
+//!
+//! メニュー・監視の表示・ログ・通知・設定ファイルのコメントなど、利用者が読む文は
+//! `locales/<言語>.ini` のカタログに `キー=文` の形式で書く。文の中の `{name}` は
+//! 呼び出し側で渡した値に置き換え、`\n` は改行、`\\` は `\` になる。
+//!
+//! カタログはビルド時に埋め込む（`build.rs` が `locales` フォルダのファイルを
+//! すべて拾うため、言語の追加はファイルを置くだけでよい）。今の言語のカタログに
+//! ないキーは日本語（`ja`）の文を使う。
+//!
+//! 言語は設定の `Language` で選ぶ（`auto` または空欄ならOSの言語に合わせる）。
+//!
+//! ```ini
+//! Language=en
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+include!(concat!(env!("OUT_DIR"), "/locales.rs"));
+
+/// カタログにない文や、言語を決められなかったときに使う言語
+pub const DEFAULT_LANGUAGE: &str = "ja";
+
+/// 今の言語（`CATALOG_FILES` の位置。未設定なら既定の言語）
+static CURRENT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// 文を翻訳する
+///
+/// ```ignore
+/// println!("{}", t!("menu.title"));
+/// println!("{}", t!("event.running", name = event.name, pid = pid));
+/// ```
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::tr($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::tr_args($key, &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+])
+    };
+}
+
+fn catalogs() -> &'static [HashMap<String, String>] {
+    static CATALOGS: OnceLock<Vec<HashMap<String, String>>> = OnceLock::new();
+    CATALOGS.get_or_init(|| CATALOG_FILES.iter().map(|(_, content)| parse_catalog(content)).collect())
+}
+
+fn index_of(language: &str) -> Option<usize> {
+    CATALOG_FILES.iter().position(|(code, _)| *code == language)
+}
+
+/// カタログのある言語の一覧
+pub fn languages() -> Vec<&'static str> {
+    CATALOG_FILES.iter().map(|(code, _)| *code).collect()
+}
+
+/// 今の言語
+pub fn language() -> &'static str {
+    let index = CURRENT.load(Ordering::Relaxed);
+    CATALOG_FILES
+        .get(index)
+        .map(|(code, _)| *code)
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// 言語を切り替える（カタログがない言語なら false）
+pub fn set_language(language: &str) -> bool {
+    match index_of(language) {
+        Some(index) => {
+            CURRENT.store(index, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 設定の `Language` に従って言語を決める。値が不正なら OS の言語を使い、その値を返す
+pub fn init(config: &HashMap<String, String>) -> Result<(), String> {
+    let setting = config.get("Language").map(|s| s.trim().to_lowercase()).unwrap_or_default();
+    if setting.is_empty() || setting == "auto" {
+        set_language(&resolve(detect().as_deref()));
+        return Ok(());
+    }
+    if set_language(&setting) {
+        Ok(())
+    } else {
+        set_language(&resolve(detect().as_deref()));
+        Err(setting)
+    }
+}
+
+/// OS の言語コード（例: `ja-JP` → `ja`）をカタログのある言語に合わせる
+///
+/// カタログがない言語なら英語、OS の言語が分からなければ既定の言語にする。
+pub fn resolve(system: Option<&str>) -> String {
+    let Some(system) = system else {
+        return DEFAULT_LANGUAGE.to_string();
+    };
+    let code = system
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if index_of(&code).is_some() {
+        code
+    } else {
+        "en".to_string()
+    }
+}
+
+/// OS の言語（環境変数 `LC_ALL`・`LC_MESSAGES`・`LANG` の順に見る）
+#[cfg(not(windows))]
+pub fn detect() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .filter(|value| {
+            let code = value.split('.').next().unwrap_or_default();
+            code != "C" && code != "POSIX"
+        })
+}
+
+/// OS の言語（ユーザーのロケール名。例: `ja-JP`）
+#[cfg(windows)]
+pub fn detect() -> Option<String> {
+    use winapi::um::winnls::GetUserDefaultLocaleName;
+
+    let mut buffer = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(buffer.as_mut_ptr(), buffer.len() as i32) };
+    if len <= 1 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&buffer[..len as usize - 1]))
+}
+
+/// 文を翻訳する（カタログにないキーはキーをそのまま返す）
+pub fn tr(key: &str) -> String {
+    let catalogs = catalogs();
+    let current = CURRENT.load(Ordering::Relaxed);
+    let fallback = index_of(DEFAULT_LANGUAGE);
+    [Some(current), fallback]
+        .into_iter()
+        .flatten()
+        .filter_map(|index| catalogs.get(index))
+        .find_map(|catalog| catalog.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// 文を翻訳し、`{name}` を値に置き換える
+pub fn tr_args(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = tr(key);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
+
+/// カタログを読む（`;` と `#` で始まる行と `[セクション]` は無視する）
+pub fn parse_catalog(content: &str) -> HashMap<String, String> {
+    let mut entries = HashMap::new();
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') || trimmed.starts_with('[') {
+            continue;
+        }
+        if let Some((key, value)) = trimmed.split_once('=') {
+            entries.insert(key.trim().to_string(), unescape(value.trim_end_matches('\r')));
+        }
+    }
+    entries
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('\\') => text.push('\\'),
+            Some(other) => {
+                text.push('\\');
+                text.push(other);
+            }
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn placeholders(text: &str) -> BTreeSet<String> {
+        text.split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}'))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn catalog_values_are_unescaped() {
+        let catalog = parse_catalog("; comment\n[Messages]\ngreeting=  → {name}\\nC:\\\\MT4\\\\terminal.exe\r\n");
+        assert_eq!(catalog["greeting"], "  → {name}\nC:\\MT4\\terminal.exe");
+    }
+
+    #[test]
+    fn every_catalog_has_the_same_keys_and_placeholders() {
+        let base_index = index_of(DEFAULT_LANGUAGE).unwrap();
+        let base = &catalogs()[base_index];
+        for (index, (language, _)) in CATALOG_FILES.iter().enumerate() {
+            let catalog = &catalogs()[index];
+            for (key, text) in base {
+                let translated = catalog
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{}: {} がありません", language, key));
+                assert_eq!(placeholders(translated), placeholders(text), "{}: {}", language, key);
+            }
+            for key in catalog.keys() {
+                assert!(base.contains_key(key), "{}: {} は {} にありません", language, key, DEFAULT_LANGUAGE);
+            }
+        }
+    }
+
+    #[test]
+    fn system_locale_picks_a_catalog() {
+        assert_eq!(resolve(Some("ja_JP.UTF-8")), "ja");
+        assert_eq!(resolve(Some("en-US")), "en");
+        // カタログがない言語は英語
+        assert_eq!(resolve(Some("de_DE.UTF-8")), "en");
+        assert_eq!(resolve(None), DEFAULT_LANGUAGE);
+    }
+}
@@ -0,0 +1,517 @@
+//! 監視対象のMT4/MT5インスタンス
+//!
+//! 設定ファイルでは `MT_番号=監視|表示名|パス` の1行が1インスタンスで、
+//! 追加の項目は `MT_番号.項目名=値` の形で続けて書く。
+//!
+//! `MT_番号` がインスタンスのID で、メニュー・コマンド・ログのどこでもこの番号で指す。
+//! 削除しても他のインスタンスの番号は変わらず、追加するときは一番大きい番号の次を使う
+//! （途中の空いた番号は使わない）。
+//!
+//! ```ini
+//! MT_1=ON|XM本番口座|C:\Program Files\XM MT4\terminal.exe
+//! MT_1.Args=/portable
+//! MT_1.Launcher=wine
+//! MT_1.WinePrefix=/home/fx/.wine
+//! MT_1.Interval=10
+//! MT_1.Priority=10
+//! MT_2.DependsOn=MT_1
+//! MT_2.Group=tanaka
+//! MT_2.Tags=live,broker:XM
+//! ```
+//!
+//! パス・引数・Wineの設定には `%USERNAME%` や `${HOME}` のように環境変数を書ける（[`crate::expand`]）。
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::process::RunningProcess;
+use crate::resources::ResourceLimits;
+use crate::schedule::Schedule;
+use crate::expand::expand;
+use crate::{paths, wine};
+
+/// 起動方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// terminal.exe を直接起動する（Windows）
+    Native,
+    /// `wine terminal.exe` として起動する（Linux）
+    Wine,
+}
+
+impl LaunchMode {
+    /// 実行環境の既定値
+    pub fn platform_default() -> Self {
+        if cfg!(windows) {
+            LaunchMode::Native
+        } else {
+            LaunchMode::Wine
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "native" | "windows" => Some(LaunchMode::Native),
+            "wine" => Some(LaunchMode::Wine),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LaunchMode::Native => "native",
+            LaunchMode::Wine => "wine",
+        }
+    }
+}
+
+/// 1つのMT4/MT5インスタンスの設定
+#[derive(Debug, Clone)]
+pub struct MtInstance {
+    /// 設定ファイル上のキー（例: `MT_1`）
+    pub key: String,
+    pub enabled: bool,
+    pub name: String,
+    pub path: String,
+    /// terminal.exe に渡す引数
+    pub args: Vec<String>,
+    pub launch_mode: LaunchMode,
+    pub wine_prefix: Option<String>,
+    pub wine_binary: String,
+    /// このインスタンスのチェック間隔（秒）。未指定なら全体の CheckInterval
+    pub interval: Option<u64>,
+    /// 再起動の優先度（大きいほど先に起動する）
+    pub priority: i32,
+    /// 終了を依頼してから強制終了するまで待つ時間（秒）。未指定なら全体の StopTimeout
+    pub stop_timeout: Option<u64>,
+    /// 先に実行中になっている必要があるインスタンスのキー
+    pub depends_on: Vec<String>,
+    /// `DependsOn` に書かれたが、設定にないID（設定の問題として表示する）
+    pub unknown_dependencies: Vec<String>,
+    /// 再起動しない時間帯（全体の設定を含む）
+    pub schedule: Schedule,
+    /// リソース使用量の上限
+    pub limits: ResourceLimits,
+    /// グループ（顧客ごとなど。通知先の振り分けにも使う）
+    pub group: Option<String>,
+    /// タグ（例: `live`、`broker:XM`）
+    pub tags: Vec<String>,
+}
+
+/// インスタンスごとに指定できるチェック間隔の範囲（秒）
+pub const INTERVAL_RANGE: std::ops::RangeInclusive<u64> = 5..=3600;
+
+/// `MT_番号` 形式のインスタンス本体のキーかどうか
+pub fn is_instance_key(key: &str) -> bool {
+    key.starts_with("MT_") && !key.contains('.')
+}
+
+/// インスタンスの追加項目のキー（例: `MT_1.Args`）
+pub fn option_key(key: &str, option: &str) -> String {
+    format!("{}.{}", key, option)
+}
+
+/// インスタンスの追加項目のキーかどうか
+pub fn is_option_of(key: &str, instance_key: &str) -> bool {
+    key.strip_prefix(instance_key)
+        .map(|rest| rest.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// インスタンスの番号（`MT_12` なら 12）
+pub fn instance_number(key: &str) -> Option<u32> {
+    key.strip_prefix("MT_").and_then(|n| n.parse().ok())
+}
+
+/// 入力されたID（`3`・`MT_3`・`mt_3`）をキーにする
+pub fn parse_instance_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let number = input
+        .get(..3)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("MT_"))
+        .map_or(input, |_| &input[3..]);
+    number.parse::<u32>().ok().map(|n| format!("MT_{}", n))
+}
+
+/// 入力されたIDを今あるキーから探す
+///
+/// 書いたとおりのキー（大文字・小文字は区別しない）を優先し、なければ番号で比べる
+/// （手で書いた `MT_03` も `3`・`MT_3` で選べる）。
+pub fn resolve_instance_id<'a>(input: &str, keys: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let input = input.trim();
+    let keys: Vec<&str> = keys.into_iter().collect();
+    if let Some(key) = keys.iter().find(|k| k.eq_ignore_ascii_case(input)) {
+        return Some(key);
+    }
+    let number = instance_number(&parse_instance_id(input)?)?;
+    keys.into_iter().find(|k| instance_number(k) == Some(number))
+}
+
+/// インスタンスのキーの並び順（番号順。`MT_2` は `MT_10` より前）
+pub fn key_order(key: &str) -> (u32, &str) {
+    (instance_number(key).unwrap_or(u32::MAX), key)
+}
+
+/// 新しいインスタンスのキー（使われている一番大きい番号の次。途中の空いた番号は使わない）
+pub fn next_key(config: &HashMap<String, String>) -> String {
+    let last = config
+        .keys()
+        .filter(|k| is_instance_key(k))
+        .filter_map(|k| instance_number(k))
+        .max()
+        .unwrap_or(0);
+    format!("MT_{}", last + 1)
+}
+
+/// 設定からインスタンスの一覧を作る（番号順）
+pub fn parse_instances(config: &HashMap<String, String>) -> Vec<MtInstance> {
+    let mut keys: Vec<&String> = config.keys().filter(|k| is_instance_key(k)).collect();
+    keys.sort_by_key(|k| key_order(k));
+
+    keys.into_iter()
+        .filter_map(|key| parse_instance(config, key))
+        .collect()
+}
+
+fn parse_instance(config: &HashMap<String, String>, key: &str) -> Option<MtInstance> {
+    let value = config.get(key)?;
+    let parts: Vec<&str> = value.split('|').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    let opt = |name: &str| {
+        config
+            .get(&option_key(key, name))
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+    };
+
+    // パス・引数・Wineの設定は環境変数を展開する
+    let mut instance = MtInstance::new(key, parts[1], &expand(parts[2]));
+    instance.enabled = parts[0] == "1";
+    instance.args = opt("Args")
+        .map(|args| split_args(args).iter().map(|arg| expand(arg)).collect())
+        .unwrap_or_default();
+    if let Some(mode) = opt("Launcher").and_then(LaunchMode::parse) {
+        instance.launch_mode = mode;
+    }
+    instance.wine_prefix = opt("WinePrefix").map(expand);
+    if let Some(binary) = opt("WineBinary") {
+        instance.wine_binary = expand(binary);
+    }
+    instance.interval = opt("Interval")
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|s| INTERVAL_RANGE.contains(s));
+    instance.priority = opt("Priority")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    instance.stop_timeout = opt("StopTimeout").and_then(|s| s.parse::<u64>().ok());
+    // 依存先はほかのIDの入力と同じく、今あるキーから探す（`1`・`MT_01` なども使える）
+    for dependency in opt("DependsOn").map(split_list).unwrap_or_default() {
+        let keys = config.keys().filter(|k| is_instance_key(k)).map(String::as_str);
+        match resolve_instance_id(&dependency, keys) {
+            Some(found) => instance.depends_on.push(found.to_string()),
+            None => instance.unknown_dependencies.push(dependency),
+        }
+    }
+    instance.schedule = Schedule::for_instance(config, key);
+    instance.limits = ResourceLimits::for_instance(config, key);
+    instance.group = opt("Group").map(str::to_string);
+    instance.tags = opt("Tags").map(split_list).unwrap_or_default();
+    Some(instance)
+}
+
+/// カンマ区切りの一覧（空の要素は除く）
+pub fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// 引数文字列を空白で分割する（ダブルクォートで囲んだ部分は1つの引数）
+pub fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+
+    for c in s.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    args.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if has_token {
+        args.push(current);
+    }
+
+    args
+}
+
+impl MtInstance {
+    /// 追加の項目をすべて既定値にしたインスタンス
+    pub fn new(key: &str, name: &str, path: &str) -> Self {
+        MtInstance {
+            key: key.to_string(),
+            enabled: true,
+            name: name.to_string(),
+            path: path.to_string(),
+            args: Vec::new(),
+            launch_mode: LaunchMode::platform_default(),
+            wine_prefix: None,
+            wine_binary: wine::DEFAULT_WINE_BINARY.to_string(),
+            interval: None,
+            priority: 0,
+            stop_timeout: None,
+            depends_on: Vec::new(),
+            unknown_dependencies: Vec::new(),
+            schedule: Schedule::default(),
+            limits: ResourceLimits::default(),
+            group: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// このグループに属するか（大文字・小文字は区別しない）
+    pub fn in_group(&self, group: &str) -> bool {
+        self.group.as_deref().is_some_and(|g| g.eq_ignore_ascii_case(group))
+    }
+
+    /// このタグが付いているか（大文字・小文字は区別しない）
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// ファイルシステム上の実体のパス（Wineなら drive_c 配下のUnixパス）
+    pub fn host_path(&self) -> PathBuf {
+        match self.launch_mode {
+            LaunchMode::Native => PathBuf::from(&self.path),
+            LaunchMode::Wine => wine::to_unix_path(
+                self.wine_prefix.as_deref().map(std::path::Path::new),
+                &self.path,
+            ),
+        }
+    }
+
+    /// 起動コマンドを組み立てる
+    pub fn command(&self) -> Command {
+        match self.launch_mode {
+            LaunchMode::Native => {
+                let mut command = Command::new(&self.path);
+                command.args(&self.args);
+                command
+            }
+            LaunchMode::Wine => {
+                let mut command = Command::new(&self.wine_binary);
+                command.arg(&self.path).args(&self.args);
+                if let Some(prefix) = &self.wine_prefix {
+                    command.env("WINEPREFIX", prefix);
+                }
+                if let Some(dir) = self.host_path().parent().filter(|d| d.is_dir()) {
+                    command.current_dir(dir);
+                }
+                // wineのデバッグ出力で監視画面が埋まらないようにする
+                command.stdout(Stdio::null()).stderr(Stdio::null());
+                command
+            }
+        }
+    }
+
+    /// このインスタンスに該当するプロセスを探す
+    pub fn find_process<'a>(&self, processes: &'a [RunningProcess]) -> Option<&'a RunningProcess> {
+        match self.launch_mode {
+            LaunchMode::Native => {
+                let key = paths::path_key(&self.path);
+                processes.iter().find(|p| !key.is_empty() && p.exe_key == key)
+            }
+            LaunchMode::Wine => {
+                let mut keys: Vec<String> = wine::path_candidates(&self.path)
+                    .iter()
+                    .chain(wine::path_candidates(&self.host_path().to_string_lossy()).iter())
+                    .map(|c| paths::path_key(c))
+                    .filter(|k| !k.is_empty())
+                    .collect();
+                keys.sort();
+                keys.dedup();
+
+                let prefix_key = self.wine_prefix.as_deref().map(paths::path_key);
+
+                processes.iter().find(|p| {
+                    let path_matches = keys.contains(&p.exe_key)
+                        || p.cmd_key.as_ref().map(|k| keys.contains(k)).unwrap_or(false);
+                    let prefix_matches = match (&prefix_key, &p.wine_prefix) {
+                        (Some(expected), Some(actual)) => *expected == paths::path_key(actual),
+                        _ => true,
+                    };
+                    path_matches && prefix_matches
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn options_are_attached_to_their_instance() {
+        let config = config(&[
+            ("MT_1", "1|XM|C:\\MT4\\terminal.exe"),
+            ("MT_1.Args", "/portable \"/config:my config.ini\""),
+            ("MT_1.Launcher", "wine"),
+            ("MT_1.WinePrefix", "/home/fx/.wine"),
+            ("MT_2", "0|Demo|D:\\MT4\\terminal.exe"),
+            ("CheckInterval", "30"),
+        ]);
+
+        let instances = parse_instances(&config);
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].args, vec!["/portable", "/config:my config.ini"]);
+        assert_eq!(instances[0].launch_mode, LaunchMode::Wine);
+        assert_eq!(instances[0].wine_prefix.as_deref(), Some("/home/fx/.wine"));
+        assert_eq!(instances[0].wine_binary, "wine");
+        assert!(!instances[1].enabled);
+        assert!(instances[1].args.is_empty());
+    }
+
+    #[test]
+    fn scheduling_options_are_optional() {
+        let config = config(&[
+            ("MT_1", "1|Live|C:\\Live\\terminal.exe"),
+            ("MT_1.Interval", "10"),
+            ("MT_1.Priority", "5"),
+            ("MT_2", "1|Research|C:\\Research\\terminal.exe"),
+            ("MT_2.Interval", "1"),
+            ("MT_2.DependsOn", "1, MT_3"),
+            ("MT_04", "1|Hand-written|C:\\Four\\terminal.exe"),
+            ("MT_04.DependsOn", "mt_2,MT_01"),
+        ]);
+
+        let instances = parse_instances(&config);
+        assert_eq!(instances[0].interval, Some(10));
+        assert_eq!(instances[0].priority, 5);
+        // 範囲外の値は無視して全体の間隔を使う
+        assert_eq!(instances[1].interval, None);
+        assert_eq!(instances[1].priority, 0);
+        // 依存先は今あるキーから探し、見つからないIDは問題として残す
+        assert_eq!(instances[1].depends_on, vec!["MT_1"]);
+        assert_eq!(instances[1].unknown_dependencies, vec!["MT_3"]);
+        assert_eq!(instances[2].depends_on, vec!["MT_2", "MT_1"]);
+        assert!(instances[2].unknown_dependencies.is_empty());
+    }
+
+    #[test]
+    fn instances_are_ordered_and_numbered_by_id() {
+        let mut config = config(&[
+            ("MT_10", "1|Ten|C:\\Ten\\terminal.exe"),
+            ("MT_2", "1|Two|C:\\Two\\terminal.exe"),
+            ("MT_1", "1|One|C:\\One\\terminal.exe"),
+        ]);
+        let keys: Vec<String> = parse_instances(&config).into_iter().map(|i| i.key).collect();
+        assert_eq!(keys, ["MT_1", "MT_2", "MT_10"]);
+
+        // 途中の空いた番号は使わない
+        config.remove("MT_2");
+        assert_eq!(next_key(&config), "MT_11");
+        assert_eq!(next_key(&HashMap::new()), "MT_1");
+
+        assert_eq!(parse_instance_id("3").as_deref(), Some("MT_3"));
+        assert_eq!(parse_instance_id(" mt_03 ").as_deref(), Some("MT_3"));
+        assert_eq!(parse_instance_id("live"), None);
+
+        // 手で書いたキーも番号か書いたとおりの名前で選べる
+        let keys = ["MT_1", "MT_03", "MT_XM"];
+        assert_eq!(resolve_instance_id("MT_03", keys), Some("MT_03"));
+        assert_eq!(resolve_instance_id("3", keys), Some("MT_03"));
+        assert_eq!(resolve_instance_id("mt_xm", keys), Some("MT_XM"));
+        assert_eq!(resolve_instance_id("MT_3x", keys), None);
+        assert_eq!(resolve_instance_id("2", keys), None);
+    }
+
+    #[test]
+    fn option_keys_are_not_instances() {
+        assert!(is_instance_key("MT_12"));
+        assert!(!is_instance_key("MT_12.Args"));
+        assert!(is_option_of("MT_1.Args", "MT_1"));
+        assert!(!is_option_of("MT_10.Args", "MT_1"));
+    }
+
+    fn wine_instance(prefix: Option<&str>) -> MtInstance {
+        let mut instance = MtInstance::new("MT_1", "XM", r"C:\Program Files\XM MT4\terminal.exe");
+        instance.launch_mode = LaunchMode::Wine;
+        instance.wine_prefix = prefix.map(|s| s.to_string());
+        instance
+    }
+
+    fn wine_process(arg0: &str, prefix: Option<&str>) -> RunningProcess {
+        RunningProcess {
+            pid: 42,
+            exe_key: paths::path_key("/usr/lib/wine/wine64-preloader"),
+            cmd_key: Some(paths::path_key(arg0)),
+            wine_prefix: prefix.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn wine_processes_are_recognised_by_command_line() {
+        let instance = wine_instance(Some("/home/fx/.wine"));
+        let processes = vec![wine_process(
+            r"C:\Program Files\XM MT4\terminal.exe",
+            Some("/home/fx/.wine"),
+        )];
+        assert!(instance.find_process(&processes).is_some());
+
+        let unix_form = vec![wine_process(
+            r"Z:\home\fx\.wine\drive_c\Program Files\XM MT4\terminal.exe",
+            None,
+        )];
+        assert!(instance.find_process(&unix_form).is_some());
+    }
+
+    #[test]
+    fn wine_processes_in_other_prefixes_do_not_match() {
+        let instance = wine_instance(Some("/home/fx/.wine"));
+        let processes = vec![wine_process(
+            r"C:\Program Files\XM MT4\terminal.exe",
+            Some("/home/fx/.mt4"),
+        )];
+        assert!(instance.find_process(&processes).is_none());
+    }
+
+    #[test]
+    fn wine_command_runs_the_terminal_through_wine() {
+        let mut instance = wine_instance(Some("/home/fx/.wine"));
+        instance.args = vec!["/portable".to_string()];
+        let command = instance.command();
+
+        assert_eq!(command.get_program(), "wine");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, vec![r"C:\Program Files\XM MT4\terminal.exe", "/portable"]);
+        assert!(command
+            .get_envs()
+            .any(|(k, v)| k == "WINEPREFIX" && v == Some("/home/fx/.wine".as_ref())));
+    }
+}
@@ -0,0 +1,257 @@
+//! MT4/MT5の起動
+//!
+//! 監視エンジンは起動をこのトレイト経由で行う。実際の起動は
+//! `CommandLauncher`、テストでは `FakeLauncher` を使う。
+//!
+//! `CommandLauncher` は起動したプロセスごとに終了を待つスレッドを立て、
+//! 終了したらすぐ `ProcessExit` を通知する。
+//!
+//! 終了させる処理（`StopControl`）も起動と同じ実行先が受け持つ。
+
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::instance::MtInstance;
+use crate::process::FakeProcessTable;
+use crate::stop::{self, StopControl};
+use crate::t;
+
+/// 起動したプロセスが終了したことの通知
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessExit {
+    /// インスタンスのキー（例: `MT_1`）
+    pub key: String,
+    pub pid: u32,
+    /// 終了コード（シグナルで終了した場合などは `None`）
+    pub code: Option<i32>,
+}
+
+pub trait Launcher: StopControl {
+    /// インスタンスを起動し、起動したプロセスのPIDを返す
+    fn launch(&mut self, instance: &MtInstance) -> io::Result<u32>;
+
+    /// 起動したプロセスの終了を最大 `timeout` 待つ
+    ///
+    /// 終了通知に対応しない起動方法では、待たずに `None` を返してよい。
+    fn wait_exit(&mut self, timeout: Duration) -> Option<ProcessExit> {
+        let _ = timeout;
+        None
+    }
+}
+
+/// `MtInstance::command` で組み立てたコマンドを実行する
+pub struct CommandLauncher {
+    exit_tx: Sender<ProcessExit>,
+    exit_rx: Receiver<ProcessExit>,
+}
+
+impl CommandLauncher {
+    pub fn new() -> Self {
+        let (exit_tx, exit_rx) = mpsc::channel();
+        CommandLauncher { exit_tx, exit_rx }
+    }
+}
+
+impl Default for CommandLauncher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Launcher for CommandLauncher {
+    fn launch(&mut self, instance: &MtInstance) -> io::Result<u32> {
+        let mut child = instance.command().spawn()?;
+        let pid = child.id();
+
+        // 終了を待つスレッド（終了したプロセスの回収も兼ねる）
+        let tx = self.exit_tx.clone();
+        let key = instance.key.clone();
+        thread::spawn(move || {
+            let code = child.wait().ok().and_then(|status| status.code());
+            let _ = tx.send(ProcessExit { key, pid, code });
+        });
+
+        Ok(pid)
+    }
+
+    fn wait_exit(&mut self, timeout: Duration) -> Option<ProcessExit> {
+        self.exit_rx.recv_timeout(timeout).ok()
+    }
+}
+
+impl StopControl for CommandLauncher {
+    fn request_close(&mut self, pid: u32) -> io::Result<bool> {
+        stop::request_close(pid)
+    }
+
+    fn force_kill(&mut self, pid: u32) -> io::Result<()> {
+        stop::force_kill(pid)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeLauncherState {
+    launched: Vec<String>,
+    failing: HashSet<String>,
+    /// 起動してもプロセス一覧に現れないインスタンス（起動直後に落ちる端末の再現）
+    silent: HashSet<String>,
+    /// 起動したプロセス（キー, PID）
+    running: Vec<(String, u32)>,
+    /// 終了の依頼を無視するPID（応答しない端末の再現）
+    hung: HashSet<u32>,
+    terminated: Vec<u32>,
+    killed: Vec<u32>,
+    exits: VecDeque<ProcessExit>,
+}
+
+/// テスト用の起動処理
+///
+/// 起動すると `FakeProcessTable` にプロセスを追加する。
+/// 複製しても起動履歴を共有する。
+#[derive(Debug, Clone)]
+pub struct FakeLauncher {
+    table: FakeProcessTable,
+    state: Arc<Mutex<FakeLauncherState>>,
+}
+
+impl FakeLauncher {
+    pub fn new(table: FakeProcessTable) -> Self {
+        FakeLauncher {
+            table,
+            state: Arc::default(),
+        }
+    }
+
+    /// 起動したインスタンスのキー（起動順）
+    pub fn launched(&self) -> Vec<String> {
+        self.state.lock().unwrap().launched.clone()
+    }
+
+    /// 終了を依頼したPID（順番どおり）
+    pub fn terminated(&self) -> Vec<u32> {
+        self.state.lock().unwrap().terminated.clone()
+    }
+
+    /// 強制終了したPID（順番どおり）
+    pub fn killed(&self) -> Vec<u32> {
+        self.state.lock().unwrap().killed.clone()
+    }
+
+    /// 指定したPIDが終了の依頼に応じないようにする
+    pub fn hang(&self, pid: u32) {
+        self.state.lock().unwrap().hung.insert(pid);
+    }
+
+    /// 指定したインスタンスの起動を失敗させる
+    pub fn fail(&self, key: &str, failing: bool) {
+        let mut state = self.state.lock().unwrap();
+        if failing {
+            state.failing.insert(key.to_string());
+        } else {
+            state.failing.remove(key);
+        }
+    }
+
+    /// 指定したインスタンスを起動してもプロセスが現れないようにする
+    pub fn silent(&self, key: &str, silent: bool) {
+        let mut state = self.state.lock().unwrap();
+        if silent {
+            state.silent.insert(key.to_string());
+        } else {
+            state.silent.remove(key);
+        }
+    }
+}
+
+impl FakeLauncher {
+    /// 起動したプロセスを終了させ、終了通知を送る
+    pub fn exit(&self, key: &str, code: Option<i32>) {
+        let mut state = self.state.lock().unwrap();
+        let exited: Vec<u32> = state
+            .running
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, pid)| *pid)
+            .collect();
+        state.running.retain(|(k, _)| k != key);
+        for pid in exited {
+            self.table.kill(pid);
+            state.exits.push_back(ProcessExit {
+                key: key.to_string(),
+                pid,
+                code,
+            });
+        }
+    }
+}
+
+impl Launcher for FakeLauncher {
+    fn launch(&mut self, instance: &MtInstance) -> io::Result<u32> {
+        let mut state = self.state.lock().unwrap();
+        if state.failing.contains(&instance.key) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, t!("launcher.not_found")));
+        }
+        state.launched.push(instance.key.clone());
+
+        let pid = self.table.next_pid();
+        if !state.silent.contains(&instance.key) {
+            self.table.start(pid, &instance.path);
+        }
+        state.running.push((instance.key.clone(), pid));
+        Ok(pid)
+    }
+
+    fn wait_exit(&mut self, _timeout: Duration) -> Option<ProcessExit> {
+        self.state.lock().unwrap().exits.pop_front()
+    }
+}
+
+impl StopControl for FakeLauncher {
+    fn request_close(&mut self, pid: u32) -> io::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        state.terminated.push(pid);
+        if !state.hung.contains(&pid) {
+            state.running.retain(|(_, p)| *p != pid);
+            self.table.kill(pid);
+        }
+        Ok(true)
+    }
+
+    fn force_kill(&mut self, pid: u32) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.killed.push(pid);
+        state.running.retain(|(_, p)| *p != pid);
+        self.table.kill(pid);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::LaunchMode;
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_of_a_launched_process_is_reported() {
+        let mut instance = MtInstance::new("MT_1", "sh", "/bin/sh");
+        instance.args = vec!["-c".to_string(), "exit 7".to_string()];
+        instance.launch_mode = LaunchMode::Native;
+
+        let mut launcher = CommandLauncher::new();
+        let pid = launcher.launch(&instance).unwrap();
+        let exit = launcher.wait_exit(Duration::from_secs(10)).unwrap();
+        assert_eq!(
+            exit,
+            ProcessExit {
+                key: "MT_1".to_string(),
+                pid,
+                code: Some(7),
+            }
+        );
+    }
+}
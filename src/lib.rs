@@ -0,0 +1,29 @@
+//! MetaGuard の監視エンジン
+//!
+//! MT4/MT5の検出・起動・状態管理はこのライブラリにまとめ、
+//! `main.rs` はメニューや画面表示だけを受け持つ。
+
+pub mod autostart;
+pub mod backup;
+pub mod clock;
+pub mod config;
+pub mod dashboard;
+pub mod expand;
+pub mod history;
+pub mod i18n;
+pub mod instance;
+pub mod launcher;
+pub mod lock;
+pub mod migration;
+pub mod monitor;
+pub mod notify;
+pub mod paths;
+pub mod process;
+pub mod resources;
+pub mod schedule;
+pub mod selector;
+pub mod shutdown;
+pub mod state;
+pub mod stop;
+pub mod transfer;
+pub mod wine;
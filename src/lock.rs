@@ -0,0 +1,300 @@
+//! 二重起動の防止
+//!
+//! 同じ設定ファイルを監視するMetaGuardが同時に2つ動くと、同じチェック周期で
+//! 両方がMT4/MT5を起動してしまう。監視を始める前に設定ファイルのパスごとの
+//! ロックファイルを作成し、保持しているプロセスのPIDと開始時刻を記録する。
+//!
+//! 保持しているプロセスがすでに終了している（異常終了などで残った）
+//! ロックファイルは自動的に回収する。
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+
+use crate::paths;
+use crate::t;
+
+/// 書き込み途中のロックファイルを有効とみなす時間
+const WRITE_GRACE: Duration = Duration::from_secs(5);
+
+/// ロックを保持しているMetaGuardの情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: u32,
+    /// プロセスの開始時刻（UNIX時間・秒）。PIDの再利用を見分けるために使う
+    pub process_start: u64,
+    /// 監視を開始した時刻（表示用）
+    pub started_at: String,
+    pub config: String,
+}
+
+impl LockHolder {
+    fn parse(content: &str) -> Option<Self> {
+        let mut pid = None;
+        let mut process_start = None;
+        let mut started_at = String::new();
+        let mut config = String::new();
+
+        for line in content.lines() {
+            if let Some((key, value)) = line.split_once('=') {
+                match key.trim() {
+                    "PID" => pid = value.trim().parse().ok(),
+                    "ProcessStart" => process_start = value.trim().parse().ok(),
+                    "StartedAt" => started_at = value.trim().to_string(),
+                    "Config" => config = value.trim().to_string(),
+                    _ => {}
+                }
+            }
+        }
+
+        Some(LockHolder {
+            pid: pid?,
+            process_start: process_start?,
+            started_at,
+            config,
+        })
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "PID={}\nProcessStart={}\nStartedAt={}\nConfig={}\n",
+            self.pid, self.process_start, self.started_at, self.config
+        )
+    }
+
+    /// 記録されたプロセスがまだ動いているかどうか
+    fn is_alive(&self) -> bool {
+        let mut system = System::new();
+        let pid = Pid::from_u32(self.pid);
+        if !system.refresh_process(pid) {
+            return false;
+        }
+        match system.process(pid) {
+            Some(process) => self.process_start == 0 || process.start_time() == self.process_start,
+            None => false,
+        }
+    }
+}
+
+/// ロックの取得に失敗した理由
+#[derive(Debug)]
+pub enum LockError {
+    /// 別のMetaGuardが監視中
+    Held(LockHolder),
+    Io(io::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Held(holder) if holder.pid == 0 => {
+                write!(f, "{}", t!("lock.starting"))
+            }
+            LockError::Held(holder) => write!(
+                f,
+                "{}",
+                t!("lock.held", pid = holder.pid, started = holder.started_at)
+            ),
+            LockError::Io(e) => write!(f, "{}", t!("lock.io", error = e)),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+impl From<io::Error> for LockError {
+    fn from(e: io::Error) -> Self {
+        LockError::Io(e)
+    }
+}
+
+/// 監視中に保持するロック（破棄するとロックファイルを削除する）
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    holder: LockHolder,
+}
+
+impl InstanceLock {
+    /// 設定ファイルに対応するロックを取得する
+    pub fn acquire(config_path: &Path) -> Result<Self, LockError> {
+        Self::acquire_at(lock_path_for(config_path), config_path)
+    }
+
+    fn acquire_at(path: PathBuf, config_path: &Path) -> Result<Self, LockError> {
+        let holder = LockHolder {
+            pid: std::process::id(),
+            process_start: current_process_start(),
+            started_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            config: config_path.to_string_lossy().to_string(),
+        };
+
+        // 古いロックを回収した直後に他のプロセスに取られる場合があるため数回だけ試す
+        for _ in 0..3 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(holder.render().as_bytes())?;
+                    file.sync_all()?;
+                    return Ok(InstanceLock { path, holder });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if let Some(existing) = live_holder(&path) {
+                        return Err(LockError::Held(existing));
+                    }
+                    // 保持者がいない古いロックファイル
+                    match fs::remove_file(&path) {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        match live_holder(&path) {
+            Some(existing) => Err(LockError::Held(existing)),
+            None => Err(io::Error::new(io::ErrorKind::WouldBlock, t!("lock.busy")).into()),
+        }
+    }
+
+    pub fn holder(&self) -> &LockHolder {
+        &self.holder
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // 自分のロックの場合だけ削除する
+        let ours = fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|c| LockHolder::parse(&c))
+            .map(|h| h.pid == self.holder.pid && h.process_start == self.holder.process_start)
+            .unwrap_or(false);
+        if ours {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// 監視中の別のMetaGuardがいればその情報を返す
+pub fn current_holder(config_path: &Path) -> Option<LockHolder> {
+    live_holder(&lock_path_for(config_path))
+}
+
+fn live_holder(path: &Path) -> Option<LockHolder> {
+    let content = fs::read_to_string(path).ok()?;
+    match LockHolder::parse(&content) {
+        Some(holder) if holder.pid == std::process::id() && holder.process_start == current_process_start() => {
+            Some(holder)
+        }
+        Some(holder) => holder.is_alive().then_some(holder),
+        None => {
+            // 作成直後で書き込み途中の可能性がある
+            let recent = fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| SystemTime::now().duration_since(t).ok())
+                .map(|age| age < WRITE_GRACE)
+                .unwrap_or(false);
+            recent.then(|| LockHolder {
+                pid: 0,
+                process_start: 0,
+                started_at: String::new(),
+                config: String::new(),
+            })
+        }
+    }
+}
+
+/// 設定ファイルのパスに対応するロックファイルのパス
+///
+/// 正規化したパスのハッシュを名前に使うため、同じ設定ファイルを
+/// 別の書き方で指定しても同じロックになる。
+pub fn lock_path_for(config_path: &Path) -> PathBuf {
+    let key = paths::path_key(&config_path.to_string_lossy());
+    std::env::temp_dir().join(format!("MetaGuard-{:016x}.lock", fnv1a(key.as_bytes())))
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn current_process_start() -> u64 {
+    let mut system = System::new();
+    let pid = Pid::from_u32(std::process::id());
+    system.refresh_process(pid);
+    system.process(pid).map(|p| p.start_time()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_lock(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "metaguard-lock-test-{}-{}.lock",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn second_acquire_sees_the_live_holder() {
+        let path = scratch_lock("held");
+        let config = Path::new("/tmp/MetaGuard.ini");
+
+        let lock = InstanceLock::acquire_at(path.clone(), config).unwrap();
+        match InstanceLock::acquire_at(path.clone(), config) {
+            Err(LockError::Held(holder)) => {
+                assert_eq!(holder.pid, std::process::id());
+                assert_eq!(&holder, lock.holder());
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        drop(lock);
+        assert!(!path.exists());
+        let again = InstanceLock::acquire_at(path.clone(), config).unwrap();
+        drop(again);
+    }
+
+    #[test]
+    fn stale_lock_is_recovered() {
+        let path = scratch_lock("stale");
+        let stale = LockHolder {
+            pid: u32::MAX - 7,
+            process_start: 1,
+            started_at: "2020-01-01 00:00:00".to_string(),
+            config: "/tmp/MetaGuard.ini".to_string(),
+        };
+        fs::write(&path, stale.render()).unwrap();
+
+        let lock = InstanceLock::acquire_at(path.clone(), Path::new("/tmp/MetaGuard.ini")).unwrap();
+        assert_eq!(lock.holder().pid, std::process::id());
+        drop(lock);
+    }
+
+    #[test]
+    fn lock_path_ignores_spelling_of_config_path() {
+        assert_eq!(
+            lock_path_for(Path::new(r"C:\MetaGuard\MetaGuard.ini")),
+            lock_path_for(Path::new("c:/metaguard//MetaGuard.ini"))
+        );
+        assert_ne!(
+            lock_path_for(Path::new(r"C:\MetaGuard\MetaGuard.ini")),
+            lock_path_for(Path::new(r"C:\ClientB\MetaGuard.ini"))
+        );
+    }
+}
@@ -1,13 +1,63 @@
-use sysinfo::{System, SystemExt, ProcessExt};
 use std::fs;
 use std::thread;
-use std::time::Duration;
-use std::io::{self, Write};
-use chrono::Local;
+use std::time::{Duration, Instant};
+use std::io::{self, IsTerminal, Write};
+use chrono::{Local, Utc};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use sysinfo::{System, SystemExt};
+use crossterm::event::{self as term_event, Event};
 
-const CONFIG_FILE_NAME: &str = "MetaGuard.ini";
-// Visual C++ランタイムは静的リンクされているため、URL定数は不要
+use metaguard::autostart::{self, AutoStartBackend, AutoStartCommand};
+use metaguard::clock::SystemClock;
+use metaguard::config::{self, ConfigFormat, ConfigLocation};
+use metaguard::dashboard::{self, Action, Dashboard};
+use metaguard::instance::{self, is_instance_key, is_option_of, option_key, LaunchMode, MtInstance};
+use metaguard::launcher::{CommandLauncher, Launcher};
+use metaguard::lock::{self, InstanceLock};
+use metaguard::monitor::{EventKind, Monitor, MonitorEvent, RestartPolicy};
+use metaguard::process::{ProcessTable, SysinfoProcessTable};
+use metaguard::selector::Selector;
+use metaguard::transfer::{self, ImportMode, ListFormat, PathRule};
+use metaguard::shutdown::ShutdownToken;
+use metaguard::stop::MonitorRequest;
+use metaguard::notify::CommandNotifier;
+use metaguard::{backup, expand, history, i18n, migration, paths, state, stop, t, wine};
+
+/// 実際のプロセス・起動・時計を使う監視エンジン
+type SystemMonitor = Monitor<SysinfoProcessTable, CommandLauncher, SystemClock>;
+
+/// 使う設定ファイル（起動時に決める）
+static CONFIG_LOCATION: OnceLock<ConfigLocation> = OnceLock::new();
+
+/// 待機中に終了通知と停止要求を確認する間隔
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 監視中のMetaGuardが依頼を取り出すまで待つ時間
+const MONITOR_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// save_config が説明付きで書き出す設定項目
+const KNOWN_SETTINGS: &[&str] = &[
+    "ConfigVersion",
+    "Include",
+    "CheckInterval",
+    "AutoStart",
+    "AutoStartBackend",
+    "StartTimeout",
+    "MaxRestartsPerHour",
+    "StartupDelay",
+    "LaunchStagger",
+    "StopTimeout",
+    "Language",
+    "ConfigBackups",
+    "TimeZone",
+    "Maintenance",
+    "Blackout",
+    "Holidays",
+    "ScheduleMode",
+    "AlertCommand",
+];
 
 /// コンソールウィンドウを最小化して小さいサイズに設定
 #[cfg(windows)]
@@ -48,39 +98,242 @@ fn minimize_and_resize_console() {
     // Windows以外では何もしない
 }
 
-/// 実行ファイルのディレクトリを基準にした設定ファイルの絶対パスを取得
-fn get_config_path() -> std::path::PathBuf {
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            return exe_dir.join(CONFIG_FILE_NAME);
+/// 使う設定ファイル（`--config`、`MG_CONFIG`、実行ファイルの隣、ユーザーの設定フォルダの順）
+fn config_location() -> &'static ConfigLocation {
+    CONFIG_LOCATION.get_or_init(|| locate_config(None))
+}
+
+fn locate_config(argument: Option<&Path>) -> ConfigLocation {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+        // フォールバック: カレントディレクトリ
+        .unwrap_or_default();
+    let environment = std::env::var_os(config::CONFIG_ENV).map(PathBuf::from);
+    config::locate(
+        argument,
+        environment.as_deref(),
+        &exe_dir,
+        config::user_config_dir().as_deref(),
+        config::is_writable_dir,
+    )
+}
+
+fn get_config_path() -> PathBuf {
+    config_location().path.clone()
+}
+
+/// 設定ファイルのフォルダ（`Include=` の相対パスの基準）
+fn config_dir() -> PathBuf {
+    get_config_path().parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// 引数から `--config <パス>`（または `--config=<パス>`）を取り除いて返す
+fn take_config_arg(args: &mut Vec<String>) -> Result<Option<PathBuf>, String> {
+    let Some(index) = args.iter().position(|a| a == "--config" || a.starts_with("--config=")) else {
+        return Ok(None);
+    };
+    let arg = args.remove(index);
+    match arg.strip_prefix("--config=") {
+        Some(path) if !path.is_empty() => Ok(Some(PathBuf::from(path))),
+        Some(_) => Err(t!("main.config_arg_missing")),
+        None if index < args.len() => Ok(Some(PathBuf::from(args.remove(index)))),
+        None => Err(t!("main.config_arg_missing")),
+    }
+}
+
+/// `restore-backup [番号]`: 設定ファイルのバックアップを一覧し、番号を指定すればそれに戻す
+///
+/// `undo` は最新のバックアップ（番号1）に戻す。戻す前の内容もバックアップに残るので、
+/// もう一度 `undo` すると元に戻る。
+fn restore_backup(selection: Option<&str>) -> Result<String, String> {
+    let path = get_config_path();
+    let format = config_format(&path);
+    let backups = backup::list(&path).unwrap_or_default();
+    if backups.is_empty() {
+        return Err(t!("backup.none", path = path.display()));
+    }
+    // 今の設定ファイルが壊れていても戻せるように、読めなければ空として比べる
+    let current = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| config::parse(format, &content).ok())
+        .unwrap_or_default();
+    let read_backup = |backup: &backup::Backup| -> Result<(String, HashMap<String, String>), String> {
+        let content = fs::read_to_string(&backup.path)
+            .map_err(|e| t!("config.load_failed", path = backup.path.display(), error = e))?;
+        let parsed = config::parse(format, &content)
+            .map_err(|e| t!("config.load_failed", path = backup.path.display(), error = e))?;
+        Ok((content, parsed))
+    };
+
+    let Some(selection) = selection else {
+        let mut listing = t!("backup.title", path = path.display());
+        for (i, backup) in backups.iter().enumerate() {
+            listing.push('\n');
+            listing.push_str(&describe_backup(i + 1, backup));
+            match read_backup(backup) {
+                Ok((_, parsed)) => listing.push_str(&diff_summary(&current, &parsed)),
+                Err(e) => listing.push_str(&format!("\n    {}", e)),
+            }
         }
+        listing.push_str(&format!("\n\n{}", t!("backup.restore_hint")));
+        return Ok(listing);
+    };
+
+    let backup = selection
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| backups.get(i))
+        .ok_or_else(|| t!("backup.invalid_choice", count = backups.len()))?;
+    let (content, parsed) = read_backup(backup)?;
+    write_config_file(&path, &content, backup::keep_count(&parsed))?;
+    Ok(format!(
+        "{}{}",
+        t!("backup.restored", file = backup.path.display()),
+        diff_summary(&current, &parsed)
+    ))
+}
+
+/// バックアップの一覧の1行
+fn describe_backup(number: usize, backup: &backup::Backup) -> String {
+    let file = backup.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let created = backup.created.format("%Y-%m-%d %H:%M:%S");
+    match backup.migrated_from {
+        Some(version) => t!("backup.entry_migrated", number = number, created = created, file = file, version = version),
+        None => t!("backup.entry", number = number, created = created, file = file),
+    }
+}
+
+/// 戻したときに変わる内容の要約（先頭に改行を付ける）
+fn diff_summary(current: &HashMap<String, String>, restored: &HashMap<String, String>) -> String {
+    /// 項目ごとの違いを表示する数
+    const MAX_LINES: usize = 5;
+
+    let diff = backup::diff(current, restored);
+    if diff.is_empty() {
+        return format!("\n    {}", t!("backup.same"));
+    }
+    let mut summary = format!("\n    {}", t!("backup.diff",
+        added = diff.added.len(),
+        removed = diff.removed.len(),
+        changed = diff.changed.len()
+    ));
+    let lines: Vec<String> = diff.added.iter().map(|key| format!("+ {}={}", key, restored[key]))
+        .chain(diff.removed.iter().map(|key| format!("- {}", key)))
+        .chain(diff.changed.iter().map(|(key, from, to)| format!("~ {}: {} → {}", key, from, to)))
+        .collect();
+    for line in lines.iter().take(MAX_LINES) {
+        summary.push_str(&format!("\n      {}", line));
     }
-    // フォールバック: カレントディレクトリ
-    std::path::PathBuf::from(CONFIG_FILE_NAME)
+    if lines.len() > MAX_LINES {
+        summary.push_str(&format!("\n      {}", t!("backup.more", count = lines.len() - MAX_LINES)));
+    }
+    summary
+}
+
+/// `convert-config <変換元> <変換先>`: 設定ファイルの形式を変換する
+fn convert_config(args: &[String]) -> Result<String, String> {
+    let [from, to] = args else {
+        return Err(t!("convert.usage"));
+    };
+    let (from, to) = (Path::new(from), Path::new(to));
+    let format_of = |path: &Path| {
+        ConfigFormat::from_path(path).ok_or_else(|| t!("config.unknown_format", path = path.display()))
+    };
+    let (from_format, to_format) = (format_of(from)?, format_of(to)?);
+
+    let content = fs::read_to_string(from)
+        .map_err(|e| t!("config.load_failed", path = from.display(), error = e))?;
+    let mut source = config::parse(from_format, &content)
+        .map_err(|e| t!("config.load_failed", path = from.display(), error = e))?;
+    migration::check_writable(&source)?;
+    migration::migrate(&mut source)?;
+    let converted = render_config(to_format, &source)?;
+
+    // 書き出す前に読み直して、元の値がすべて残っているか確かめる
+    if !config::preserves(&source, &config::parse(to_format, &converted)?)? {
+        return Err(t!("convert.lossy"));
+    }
+    write_config_file(to, &converted, backup::DEFAULT_KEEP)?;
+    Ok(t!("convert.done",
+        from = from.display(),
+        from_format = from_format.name(),
+        to = to.display(),
+        to_format = to_format.name()
+    ))
 }
 
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let config_arg = take_config_arg(&mut args);
+    let _ = CONFIG_LOCATION.set(locate_config(config_arg.as_ref().ok().and_then(|p| p.as_deref())));
+
+    // 表示する言語を決める（設定ファイルがまだないか読めなければOSの言語）
+    apply_language(&read_config_file().map(|(config, _)| config).unwrap_or_default());
+    if let Err(e) = config_arg {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+
+    // コマンド（メニューを出さずに実行して終了する）
+    let command = match args.first().map(|a| a.as_str()) {
+        Some("convert-config") => Some(convert_config(&args[1..])),
+        Some("undo") => Some(restore_backup(Some("1"))),
+        Some("restore-backup") => Some(restore_backup(args.get(1).map(|a| a.as_str()))),
+        Some("list") => Some(list_command(&args[1..])),
+        Some("enable") => Some(set_enabled_command(&args[1..], true)),
+        Some("disable") => Some(set_enabled_command(&args[1..], false)),
+        Some("restart") => Some(stop_command(&args[1..], true)),
+        Some("stop") => Some(stop_command(&args[1..], false)),
+        Some("export") => Some(export_command(&args[1..])),
+        Some("import") => Some(import_command(&args[1..])),
+        _ => None,
+    };
+    if let Some(result) = command {
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     println!("================================");
-    println!("   MetaGuard - MT4/MT5 監視    ");
-    println!("   INI設定版 v0.2.0            ");
+    println!("{}", t!("app.title"));
+    println!("{}", t!("app.edition", version = env!("CARGO_PKG_VERSION")));
     println!("================================\n");
 
     // 初回起動チェック
     let config_path = get_config_path();
     if !config_path.exists() {
-        println!("初回起動を検出しました。");
+        println!("{}", t!("main.first_run"));
         first_run_setup();
     }
 
     let mut config = load_or_create_config();
+    if let Err(e) = migration::check_writable(&config) {
+        eprintln!("{}", e);
+    }
     
     // 自動起動設定をチェックして同期
     sync_auto_start_setting(&config);
     
     // 自動監視モードで起動（引数がある場合）
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 && args[1] == "--auto" {
-        println!("自動監視モードで起動しました");
+    if args.first().map(|a| a.as_str()) == Some("--auto") {
+        // 同じ設定ファイルを監視中のMetaGuardがいれば起動しない
+        let lock = match InstanceLock::acquire(&get_config_path()) {
+            Ok(lock) => lock,
+            Err(e) => {
+                eprintln!("{}", e);
+                eprintln!("{}", t!("main.auto_exit"));
+                return;
+            }
+        };
+        println!("{}", t!("main.auto_started", pid = lock.holder().pid));
         auto_monitoring_mode(&config);
         return;
     }
@@ -101,52 +354,65 @@ fn main() {
             "5" => remove_mt4_instance(&mut config),
             "6" => {
                 search_and_add_mt4(&mut config);
-                save_config(&config);
+                save_config_from_menu(&config);
             },
             "7" => {
                 change_check_interval(&mut config);
-                save_config(&config);
             },
             "8" => toggle_auto_start(),
             "9" => open_config_file(),
             "0" => {
-                println!("\nプログラムを終了します...");
+                println!("{}", t!("main.exit"));
                 break;
             },
             _ => {
-                println!("無効な選択です。Enterキーを押してください...");
+                println!("{}", t!("main.invalid_choice"));
                 wait_for_enter();
             }
         }
         
         // 設定ファイルを再読み込み（外部で編集された場合に対応）
-        config = load_or_create_config();
+        if let Err(e) = reload_config(&mut config) {
+            println!("{}", t!("config.load_failed", path = get_config_path().display(), error = e));
+            wait_for_enter();
+        }
+        apply_language(&config);
+    }
+}
+
+/// 設定の `Language` に従って表示する言語を切り替える
+fn apply_language(config: &HashMap<String, String>) {
+    if let Err(value) = i18n::init(config) {
+        eprintln!("{}", t!("main.invalid_language", value = value));
     }
 }
 
 fn first_run_setup() {
-    println!("\n=== 初回セットアップ ===");
+    println!("{}", t!("setup.title"));
 
     // Visual C++ランタイムは静的リンクされているため、チェック不要
-    println!("\n1. 実行環境を確認中...");
-    println!("   ✓ Visual C++ ランタイムは統合済みです（追加インストール不要）");
+    println!("{}", t!("setup.check_env"));
+    println!("{}", t!("setup.vcruntime"));
 
     // 設定ファイル作成
-    println!("\n2. 設定ファイルを作成中...");
+    println!("{}", t!("setup.create_config"));
     let _config: HashMap<String, String> = HashMap::new();
 
     // MT4/MT5を自動検索
-    println!("\n3. MT4/MT5を自動検索中...");
+    println!("{}", t!("setup.search"));
     let instances = auto_search_mt4();
 
     // 設定ファイルに書き込み
-    save_initial_config(&instances);
-
-    println!("\n✓ 初回セットアップが完了しました！");
-    println!("\n設定ファイル '{}' が作成されました。", get_config_path().display());
-    println!("メモ帳などのテキストエディタで編集できます。");
+    match save_initial_config(&instances) {
+        Ok(()) => {
+            println!("{}", t!("setup.done"));
+            println!("{}", t!("setup.config_created", path = get_config_path().display()));
+            println!("{}", t!("setup.edit_hint"));
+        }
+        Err(e) => eprintln!("{}", e),
+    }
 
-    println!("\nEnterキーを押して続行...");
+    println!("{}", t!("common.press_enter_continue"));
     wait_for_enter();
 }
 
@@ -158,23 +424,47 @@ fn first_run_setup() {
 // fn download_vcruntime_with_fallback() -> bool { ... } // 削除済み
 // fn try_manual_install() { ... } // 削除済み
 
-fn auto_search_mt4() -> Vec<(String, String)> {
+/// 自動検索で見つかったMT4/MT5
+struct FoundTerminal {
+    name: String,
+    path: String,
+    /// Wineプレフィックス内で見つかった場合のプレフィックス
+    wine_prefix: Option<PathBuf>,
+}
+
+/// 検索するフォルダと、そのフォルダが属するWineプレフィックスの一覧
+fn search_locations() -> Vec<(PathBuf, Option<PathBuf>)> {
+    if cfg!(windows) {
+        let user_appdata = expand::expand(r"C:\Users\%USERNAME%\AppData\Roaming");
+        
+        vec![
+            r"C:\Program Files (x86)",
+            r"C:\Program Files",
+            r"D:\Program Files (x86)",
+            r"D:\Program Files",
+            &user_appdata,
+        ]
+        .into_iter()
+        .map(|p| (PathBuf::from(p), None))
+        .collect()
+    } else {
+        // LinuxではWineプレフィックスの drive_c 配下を検索
+        wine::find_prefixes()
+            .into_iter()
+            .flat_map(|prefix| {
+                wine::search_dirs(&prefix)
+                    .into_iter()
+                    .map(move |dir| (dir, Some(prefix.clone())))
+            })
+            .collect()
+    }
+}
+
+fn auto_search_mt4() -> Vec<FoundTerminal> {
     let mut instances = Vec::new();
     
-    let user_appdata = format!(r"C:\Users\{}\AppData\Roaming", 
-        std::env::var("USERNAME").unwrap_or_default()
-    );
-    
-    let search_paths = vec![
-        r"C:\Program Files (x86)",
-        r"C:\Program Files",
-        r"D:\Program Files (x86)",
-        r"D:\Program Files",
-        &user_appdata,
-    ];
-    
-    for base_path in search_paths {
-        if let Ok(entries) = fs::read_dir(base_path) {
+    for (base_path, wine_prefix) in search_locations() {
+        if let Ok(entries) = fs::read_dir(&base_path) {
             for entry in entries.flatten() {
                 let path = entry.path();
                 if path.is_dir() {
@@ -186,22 +476,20 @@ fn auto_search_mt4() -> Vec<(String, String)> {
                         
                         let platform_type = if dir_name.contains("mt5") { "MT5" } else { "MT4" };
                         
-                        if terminal_path.exists() {
-                            let name = format!("{} - {} (32bit)", 
-                                path.file_name().unwrap().to_string_lossy(),
-                                platform_type
-                            );
-                            instances.push((name.clone(), terminal_path.to_string_lossy().to_string()));
-                            println!("   ✓ 発見: {}", name);
-                        }
-                        
-                        if terminal64_path.exists() {
-                            let name = format!("{} - {} (64bit)", 
-                                path.file_name().unwrap().to_string_lossy(),
-                                platform_type
-                            );
-                            instances.push((name.clone(), terminal64_path.to_string_lossy().to_string()));
-                            println!("   ✓ 発見: {}", name);
+                        for (exe_path, bits) in [(terminal_path, "32bit"), (terminal64_path, "64bit")] {
+                            if exe_path.exists() {
+                                let name = format!("{} - {} ({})", 
+                                    path.file_name().unwrap().to_string_lossy(),
+                                    platform_type,
+                                    bits
+                                );
+                                println!("{}", t!("search.found", name = name));
+                                instances.push(FoundTerminal {
+                                    name,
+                                    path: exe_path.to_string_lossy().to_string(),
+                                    wine_prefix: wine_prefix.clone(),
+                                });
+                            }
                         }
                     }
                 }
@@ -212,189 +500,380 @@ fn auto_search_mt4() -> Vec<(String, String)> {
     instances
 }
 
-fn save_initial_config(instances: &Vec<(String, String)>) {
+/// 自動検索で見つかったMT4/MT5を設定に追加する
+fn insert_found_terminal(config: &mut HashMap<String, String>, key: &str, found: &FoundTerminal) {
+    config.insert(key.to_string(), format!("1|{}|{}", found.name, found.path));
+    if let Some(prefix) = &found.wine_prefix {
+        config.insert(option_key(key, "Launcher"), LaunchMode::Wine.as_str().to_string());
+        config.insert(option_key(key, "WinePrefix"), prefix.to_string_lossy().to_string());
+    }
+}
+
+/// 設定ファイルのコメントを書く（複数行の文は1行ずつ「;」を付ける）
+fn push_comment(content: &mut String, text: &str) {
+    for line in text.split('\n') {
+        if line.is_empty() {
+            content.push_str(";\n");
+        } else {
+            content.push_str(&format!("; {}\n", line));
+        }
+    }
+}
+
+/// 設定ファイルの見出し（区切り線で囲む）
+fn push_section(content: &mut String, title: &str) {
+    content.push_str(";------------------------------------------------------------\n");
+    push_comment(content, title);
+    content.push_str(";------------------------------------------------------------\n");
+}
+
+fn save_initial_config(instances: &[FoundTerminal]) -> Result<(), String> {
     let mut content = String::new();
     
     // ヘッダー
     content.push_str(";============================================================\n");
-    content.push_str(";          MetaGuard 設定ファイル (MetaGuard.ini)\n");
+    push_comment(&mut content, &t!("config.title"));
     content.push_str(";============================================================\n");
     content.push_str(";\n");
-    content.push_str("; ■ このファイルの編集方法\n");
-    content.push_str(";   1. メモ帳などのテキストエディタで開く\n");
-    content.push_str(";   2. 設定値を変更（= の右側の値を編集）\n");
-    content.push_str(";   3. ファイルを保存\n");
-    content.push_str(";   4. MetaGuardを再起動または設定を再読み込み\n");
-    content.push_str(";\n");
-    content.push_str("; ■ 注意事項\n");
-    content.push_str(";   - 行頭の「;」はコメント行です（この説明文）\n");
-    content.push_str(";   - 設定値は「=」の右側に記入します\n");
-    content.push_str(";   - パスにはバックスラッシュを2つ使用（例: C:\\\\Program Files\\\\）\n");
+    push_comment(&mut content, &t!("config.howto"));
     content.push_str(";\n");
     content.push_str(";============================================================\n\n");
     
     // 基本設定セクション
-    content.push_str(";------------------------------------------------------------\n");
-    content.push_str("; ▼ 基本設定\n");
-    content.push_str(";------------------------------------------------------------\n");
+    push_section(&mut content, &t!("config.basic"));
     content.push_str("[Settings]\n\n");
     
-    content.push_str("; ● MT4/MT5の監視間隔（単位：秒）\n");
-    content.push_str(";   設定可能範囲: 10～300秒\n");
-    content.push_str(";   推奨値:\n");
-    content.push_str(";     10  = 頻繁にチェック（システム負荷：高）\n");
-    content.push_str(";     30  = 標準設定（推奨）\n");
-    content.push_str(";     60  = ゆっくりチェック（システム負荷：低）\n");
-    content.push_str("CheckInterval=30\n\n");
+    push_comment(&mut content, &setting_comment(migration::VERSION_KEY, false));
+    content.push_str(&format!("{}={}\n\n", migration::VERSION_KEY, migration::CONFIG_VERSION));
+    
+    push_comment(&mut content, &setting_comment(config::INCLUDE_KEY, false));
+    content.push_str(&format!("{}=\n\n", config::INCLUDE_KEY));
+    
+    for (key, value) in [
+        ("CheckInterval", "30"),
+        ("AutoStart", "ON"),
+        ("AutoStartBackend", "auto"),
+        ("StartTimeout", "60"),
+        ("MaxRestartsPerHour", "0"),
+        ("StartupDelay", "0"),
+        ("LaunchStagger", "0"),
+        ("StopTimeout", "30"),
+        ("Language", "auto"),
+        ("ConfigBackups", "5"),
+    ] {
+        push_comment(&mut content, &setting_comment(key, false));
+        content.push_str(&format!("{}={}\n\n", key, value));
+    }
     
-    content.push_str("; ● Windows起動時の自動実行\n");
-    content.push_str(";   ON  = Windows起動時に自動でMetaGuardを起動\n");
-    content.push_str(";   OFF = 手動で起動\n");
-    content.push_str("AutoStart=ON\n\n");
+    push_section(&mut content, &t!("config.schedule_section"));
+    content.push('\n');
+    
+    for (key, value) in [
+        ("TimeZone", ""),
+        ("Maintenance", ""),
+        ("Blackout", ""),
+        ("Holidays", ""),
+        ("ScheduleMode", "report"),
+        ("AlertCommand", ""),
+    ] {
+        push_comment(&mut content, &setting_comment(key, false));
+        content.push_str(&format!("{}={}\n\n", key, value));
+    }
     
     // MT4/MT5セクション
-    content.push_str(";------------------------------------------------------------\n");
-    content.push_str("; ▼ MT4/MT5 監視対象リスト\n");
-    content.push_str(";------------------------------------------------------------\n");
+    push_section(&mut content, &t!("config.instances_section"));
     content.push_str("[MT4_MT5]\n\n");
     
-    content.push_str("; ● 記入形式\n");
-    content.push_str(";   MT_番号=監視|表示名|実行ファイルのフルパス\n");
-    content.push_str(";\n");
-    content.push_str("; ● 各項目の説明\n");
-    content.push_str(";   監視    : ON=監視する、OFF=監視しない\n");
-    content.push_str(";   表示名  : MetaGuardで表示される名前（自由に設定可）\n");
-    content.push_str(";   パス    : terminal.exe または terminal64.exe のフルパス\n");
-    content.push_str(";\n");
-    content.push_str("; ● 記入例\n");
-    content.push_str(";   MT_1=ON|XM本番口座|C:\\\\Program Files\\\\XM MT4\\\\terminal.exe\n");
-    content.push_str(";   MT_2=ON|楽天証券MT4|C:\\\\Program Files\\\\RakutenMT4\\\\terminal64.exe\n");
-    content.push_str(";   MT_3=OFF|デモ口座（停止中）|D:\\\\MT4_Demo\\\\terminal.exe\n");
-    content.push_str(";\n\n");
+    push_comment(&mut content, &t!("config.instances"));
+    content.push('\n');
     
-    for (i, (name, path)) in instances.iter().enumerate() {
-        content.push_str(&format!("MT_{}=ON|{}|{}\n", i + 1, name, path));
+    for (i, found) in instances.iter().enumerate() {
+        let key = format!("MT_{}", i + 1);
+        content.push_str(&format!("{}=ON|{}|{}\n", key, found.name, found.path));
+        if let Some(prefix) = &found.wine_prefix {
+            content.push_str(&format!("{}=wine\n", option_key(&key, "Launcher")));
+            content.push_str(&format!("{}={}\n", option_key(&key, "WinePrefix"), prefix.display()));
+        }
     }
     
     if instances.is_empty() {
-        content.push_str("; 【注意】自動検索でMT4/MT5が見つかりませんでした\n");
-        content.push_str(";  以下の例を参考に手動で追加してください：\n");
-        content.push_str(";\n");
-        content.push_str("; MT_1=ON|表示したい名前|実行ファイルのフルパス\n");
-        content.push_str(";\n");
-        content.push_str("; 例：\n");
-        content.push_str("; MT_1=ON|MetaTrader 4|C:\\\\Program Files\\\\MetaTrader 4\\\\terminal.exe\n");
-        content.push_str("; MT_2=ON|MetaTrader 5|C:\\\\Program Files\\\\MetaTrader 5\\\\terminal64.exe\n");
+        push_comment(&mut content, &t!("config.none_found"));
     }
     
-    content.push_str("\n");
+    content.push('\n');
     content.push_str(";============================================================\n");
-    content.push_str("; 設定ファイル終了\n");
+    push_comment(&mut content, &t!("config.end"));
     content.push_str(";============================================================\n");
     
-    if let Err(e) = fs::write(get_config_path(), content) {
-        eprintln!("設定ファイルの作成に失敗: {}", e);
+    // TOML・JSON の設定ファイルなら同じ内容をその形式で書く
+    let path = get_config_path();
+    let content = match config_format(&path) {
+        ConfigFormat::Ini => content,
+        format => config::render(format, &config::parse_ini(&content))
+            .map_err(|e| t!("config.write_failed", error = e))?,
+    };
+    write_config_file(&path, &content, backup::DEFAULT_KEEP)
+}
+
+/// 設定項目の説明（`short` は保存し直すときの短い説明）
+fn setting_comment(key: &str, short: bool) -> String {
+    let name = match key {
+        "ConfigVersion" => "config_version",
+        "Include" => "include",
+        "CheckInterval" => "check_interval",
+        "AutoStart" => "auto_start",
+        "AutoStartBackend" => "auto_start_backend",
+        "StartTimeout" => "start_timeout",
+        "MaxRestartsPerHour" => "max_restarts",
+        "StartupDelay" => "startup_delay",
+        "LaunchStagger" => "launch_stagger",
+        "StopTimeout" => "stop_timeout",
+        "Language" => "language",
+        "ConfigBackups" => "config_backups",
+        "TimeZone" => "time_zone",
+        "Maintenance" => "maintenance",
+        "Blackout" => "blackout",
+        "Holidays" => "holidays",
+        "ScheduleMode" => "schedule_mode",
+        "AlertCommand" => "alert_command",
+        _ => return String::new(),
+    };
+    if short {
+        t!(&format!("config.{}_short", name))
+    } else {
+        t!(&format!("config.{}", name))
     }
 }
 
-fn load_or_create_config() -> HashMap<String, String> {
-    let mut config = HashMap::new();
+/// 設定ファイルを読む（ファイルがなければ既定の設定）
+///
+/// 古い版の設定ファイルは今の版に移行し、元のファイルをバックアップしてから書き直す。
+fn read_config() -> Result<HashMap<String, String>, String> {
+    let (config, migrated_from) = read_config_file()?;
+    if let Some(from) = migrated_from {
+        upgrade_config_file(&get_config_path(), &config, from);
+    }
+    Ok(config)
+}
 
-    if let Ok(contents) = fs::read_to_string(get_config_path()) {
-        for line in contents.lines() {
-            let line = line.trim();
-            
-            // コメントと空行をスキップ
-            if line.starts_with(';') || line.starts_with('[') || line.is_empty() {
-                continue;
-            }
-            
-            // key=value の形式をパース
-            if let Some(pos) = line.find('=') {
-                let key = line[..pos].trim().to_string();
-                let mut value = line[pos + 1..].trim().to_string();
-                
-                // ON/OFF形式と1/0形式の両方をサポート（互換性維持）
-                if key == "AutoStart" {
-                    value = match value.to_uppercase().as_str() {
-                        "ON" | "TRUE" | "1" => "1".to_string(),
-                        "OFF" | "FALSE" | "0" => "0".to_string(),
-                        _ => "1".to_string()
-                    };
-                } else if key.starts_with("MT_") {
-                    // MT_エントリの形式を変換
-                    let parts: Vec<&str> = value.split('|').collect();
-                    if parts.len() == 3 {
-                        let enabled = match parts[0].to_uppercase().as_str() {
-                            "ON" | "TRUE" | "1" => "1",
-                            "OFF" | "FALSE" | "0" => "0",
-                            _ => "1"
-                        };
-                        value = format!("{}|{}|{}", enabled, parts[1], parts[2]);
-                    }
-                }
-                
-                config.insert(key, value);
-            }
-        }
-    } else {
+/// 設定ファイルを読んで今の版にする（ファイルは書き直さない。移行したら元の版も返す）
+fn read_config_file() -> Result<(HashMap<String, String>, Option<u32>), String> {
+    let path = get_config_path();
+    let Ok(contents) = fs::read_to_string(&path) else {
         // デフォルト設定
+        let mut config = HashMap::new();
+        config.insert(migration::VERSION_KEY.to_string(), migration::CONFIG_VERSION.to_string());
         config.insert("CheckInterval".to_string(), "30".to_string());
         config.insert("AutoStart".to_string(), "1".to_string());
+        return Ok((config, None));
+    };
+    let mut config = config::parse(config_format(&path), &contents)?;
+    let migrated_from = migration::migrate(&mut config)?;
+    // Include= の共通の設定の上に、このファイルの設定を重ねる
+    let config = config::apply_includes(config, &config_dir())?;
+    Ok((config, migrated_from))
+}
+
+/// 移行した設定を書き直す（元のファイルは日時付きのバックアップとして残す）
+fn upgrade_config_file(path: &Path, config: &HashMap<String, String>, from: u32) {
+    let backup = migration::backup_path(path, from, Local::now());
+    if let Err(e) = fs::copy(path, &backup) {
+        eprintln!("{}", t!("config.backup_failed", path = backup.display(), error = e));
+        return;
+    }
+    if let Err(e) = save_config(config) {
+        eprintln!("{}", e);
+        return;
+    }
+    println!("{}", t!("config.migrated",
+        from = from,
+        to = migration::CONFIG_VERSION,
+        backup = backup.display()
+    ));
+}
+
+/// 設定ファイルを読む。読めなければ理由を表示して終了する
+fn load_or_create_config() -> HashMap<String, String> {
+    match read_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", t!("config.load_failed", path = get_config_path().display(), error = e));
+            if io::stdin().is_terminal() {
+                wait_for_enter();
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 監視中などに設定ファイルを読み直す（読めなければ前の設定のまま）
+fn reload_config(config: &mut HashMap<String, String>) -> Result<(), String> {
+    *config = read_config()?;
+    Ok(())
+}
+
+/// 設定ファイルの形式（拡張子が .toml / .json 以外なら INI）
+fn config_format(path: &Path) -> ConfigFormat {
+    ConfigFormat::for_path(path)
+}
+
+/// 設定を指定の形式の文にする
+fn render_config(format: ConfigFormat, config: &HashMap<String, String>) -> Result<String, String> {
+    match format {
+        ConfigFormat::Ini => Ok(render_ini(config)),
+        format => config::render(format, config),
+    }
+}
+
+/// 設定ファイルを書く
+///
+/// 一時ファイルに書いてから置き換え、それまでの内容はバックアップとして残す。
+/// ユーザーの設定フォルダなど、フォルダがなければ作る。
+fn write_config_file(path: &Path, content: &str, keep: usize) -> Result<(), String> {
+    let write = || -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        backup::write_with_backup(path, content, keep, Local::now()).map(|_| ())
+    };
+    write().map_err(|e| t!("config.save_failed", path = path.display(), error = e))
+}
+
+/// 設定を設定ファイルに保存する
+fn save_config(config: &HashMap<String, String>) -> Result<(), String> {
+    // 新しい MetaGuard が書いた設定ファイルは上書きしない
+    migration::check_writable(config)?;
+    // 共通の設定から来た項目は、消しても次に読んだときに戻るため保存しない
+    config::check_included_removals(config, &config_dir())?;
+    // 共通の設定ファイルと同じ値はこのファイルに書かない（共通の設定を変えたときに追従するように）
+    let included = config::included_settings(config, &config_dir())?;
+    let mut config = config::strip_included(config, &included);
+    config.insert(migration::VERSION_KEY.to_string(), migration::CONFIG_VERSION.to_string());
+    let path = get_config_path();
+    let content = render_config(config_format(&path), &config)
+        .map_err(|e| t!("config.save_failed", path = path.display(), error = e))?;
+    write_config_file(&path, &content, backup::keep_count(&config))
+}
+
+/// メニューから設定を保存する（保存できなければ理由を表示して false）
+fn save_config_from_menu(config: &HashMap<String, String>) -> bool {
+    match save_config(config) {
+        Ok(()) => true,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", t!("common.press_enter_continue"));
+            wait_for_enter();
+            false
+        }
     }
-    
-    config
 }
 
-fn save_config(config: &HashMap<String, String>) {
+/// 説明付きの INI 形式にする
+fn render_ini(config: &HashMap<String, String>) -> String {
     let mut content = String::new();
     
     // ヘッダー
     content.push_str(";============================================================\n");
-    content.push_str(";          MetaGuard 設定ファイル (MetaGuard.ini)\n");
+    push_comment(&mut content, &t!("config.title"));
     content.push_str(";============================================================\n");
     content.push_str(";\n");
-    content.push_str("; ■ 編集方法: メモ帳で開いて編集 → 保存 → MetaGuard再起動\n");
+    push_comment(&mut content, &t!("config.howto_short"));
     content.push_str(";\n\n");
     
     // 基本設定
-    content.push_str(";------------------------------------------------------------\n");
-    content.push_str("; ▼ 基本設定\n");
-    content.push_str(";------------------------------------------------------------\n");
+    push_section(&mut content, &t!("config.basic"));
     content.push_str("[Settings]\n\n");
     
-    content.push_str("; ● 監視間隔（秒）: 10～300\n");
-    content.push_str(&format!("CheckInterval={}\n\n", 
-        config.get("CheckInterval").unwrap_or(&"30".to_string())
+    push_comment(&mut content, &setting_comment(migration::VERSION_KEY, true));
+    content.push_str(&format!("{}={}\n\n", migration::VERSION_KEY,
+        config.get(migration::VERSION_KEY).cloned().unwrap_or_else(|| migration::CONFIG_VERSION.to_string())
     ));
     
-    // AutoStartの値をON/OFF形式で保存
-    content.push_str("; ● Windows自動起動: ON/OFF\n");
-    let auto_start_value = match config.get("AutoStart").map(|s| s.as_str()) {
-        Some("1") => "ON",
-        Some("0") => "OFF",
-        _ => "ON"
+    push_comment(&mut content, &setting_comment(config::INCLUDE_KEY, true));
+    content.push_str(&format!("{}={}\n\n", config::INCLUDE_KEY,
+        config.get(config::INCLUDE_KEY).map(|s| s.as_str()).unwrap_or("")
+    ));
+    
+    // 共通の設定ファイルを読み込むときは、書かれていない項目に既定値を書かない（共通の値を使う）
+    let includes = config.get(config::INCLUDE_KEY).is_some_and(|v| !v.trim().is_empty());
+    let value_or = |key: &str, default: &str| -> Option<String> {
+        config.get(key).cloned().or_else(|| (!includes).then(|| default.to_string()))
     };
-    content.push_str(&format!("AutoStart={}\n\n", auto_start_value));
+    
+    for (key, default) in [
+        ("CheckInterval", "30"),
+        ("AutoStart", "ON"),
+        ("AutoStartBackend", "auto"),
+        ("StartTimeout", "60"),
+        ("MaxRestartsPerHour", "0"),
+        ("StartupDelay", "0"),
+        ("LaunchStagger", "0"),
+        ("StopTimeout", "30"),
+        ("Language", "auto"),
+        ("ConfigBackups", "5"),
+    ] {
+        let Some(value) = value_or(key, default) else {
+            continue;
+        };
+        let value = match key {
+            // AutoStartの値をON/OFF形式で保存
+            "AutoStart" if value == "0" => "OFF".to_string(),
+            "AutoStart" => "ON".to_string(),
+            _ => value,
+        };
+        push_comment(&mut content, &setting_comment(key, true));
+        content.push_str(&format!("{}={}\n\n", key, value));
+    }
+    
+    let mut schedule = String::new();
+    for (key, default) in [
+        ("TimeZone", ""),
+        ("Maintenance", ""),
+        ("Blackout", ""),
+        ("Holidays", ""),
+        ("ScheduleMode", "report"),
+    ] {
+        if let Some(value) = value_or(key, default) {
+            schedule.push_str(&format!("{}={}\n", key, value));
+        }
+    }
+    if !schedule.is_empty() {
+        push_comment(&mut content, &t!("config.schedule_short"));
+        content.push_str(&schedule);
+        content.push('\n');
+    }
+    
+    if let Some(value) = value_or("AlertCommand", "") {
+        push_comment(&mut content, &setting_comment("AlertCommand", true));
+        content.push_str(&format!("AlertCommand={}\n\n", value));
+    }
+    
+    // 上記以外の設定も失わないように書き戻す
+    let mut other_settings: Vec<_> = config.iter()
+        .filter(|(k, _)| !k.starts_with("MT_") && !KNOWN_SETTINGS.contains(&k.as_str()))
+        .collect();
+    other_settings.sort_by_key(|(k, _)| k.as_str());
+    for (key, value) in other_settings {
+        content.push_str(&format!("{}={}\n", key, value));
+    }
     
     // MT4/MT5設定
-    content.push_str(";------------------------------------------------------------\n");
-    content.push_str("; ▼ MT4/MT5 監視対象リスト\n");
-    content.push_str(";------------------------------------------------------------\n");
+    push_section(&mut content, &t!("config.instances_section"));
     content.push_str("[MT4_MT5]\n\n");
     
-    content.push_str("; 形式: MT_番号=監視|表示名|実行ファイルパス\n");
-    content.push_str("; 監視: ON=監視する、OFF=監視しない\n\n");
+    push_comment(&mut content, &t!("config.instances_short"));
+    content.push('\n');
     
     let mut mt_entries: Vec<_> = config.iter()
         .filter(|(k, _)| k.starts_with("MT_"))
         .collect();
-    mt_entries.sort_by_key(|(k, _)| k.as_str());
+    // 番号順（MT_2 は MT_10 より前）。追加項目はそのインスタンスの後
+    mt_entries.sort_by_key(|(k, _)| (instance::key_order(k.split('.').next().unwrap_or(k)), k.as_str()));
     
     for (key, value) in mt_entries {
         // MT_エントリの形式をON/OFF形式で保存
         let parts: Vec<&str> = value.split('|').collect();
-        if parts.len() == 3 {
+        if is_instance_key(key) && parts.len() == 3 {
             let enabled = if parts[0] == "1" { "ON" } else { "OFF" };
             content.push_str(&format!("{}={}|{}|{}\n", key, enabled, parts[1], parts[2]));
         } else {
@@ -402,11 +881,11 @@ fn save_config(config: &HashMap<String, String>) {
         }
     }
     
-    let _ = fs::write(get_config_path(), content);
+    content
 }
 
 fn open_config_file() {
-    println!("\n設定ファイルを開いています...");
+    println!("{}", t!("config.opening"));
 
     #[cfg(windows)]
     {
@@ -415,48 +894,82 @@ fn open_config_file() {
             .spawn()
         {
             Ok(_) => {
-                println!("設定ファイルを開きました。");
-                println!("編集後は保存して、このプログラムに戻ってください。");
+                println!("{}", t!("config.opened"));
+                println!("{}", t!("config.return_hint"));
             }
             Err(e) => {
-                println!("エラー: {}", e);
+                println!("{}", t!("common.error", error = e));
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        // $EDITOR があればそれで開き、なければデスクトップ環境に任せる
+        let result = match std::env::var("EDITOR") {
+            Ok(editor) if !editor.trim().is_empty() => std::process::Command::new(editor)
+                .arg(get_config_path())
+                .status()
+                .map(|_| ()),
+            _ => std::process::Command::new("xdg-open")
+                .arg(get_config_path())
+                .spawn()
+                .map(|_| ()),
+        };
+        match result {
+            Ok(()) => println!("{}", t!("config.path", path = get_config_path().display())),
+            Err(e) => {
+                println!("{}", t!("common.error", error = e));
+                println!("{}", t!("config.path", path = get_config_path().display()));
             }
         }
     }
     
-    println!("\nEnterキーを押して戻る...");
+    println!("{}", t!("common.press_enter_back"));
     wait_for_enter();
 }
 
 fn clear_screen() {
-    std::process::Command::new("cmd")
-        .args(&["/C", "cls"])
-        .status()
-        .unwrap();
+    #[cfg(windows)]
+    {
+        let _ = std::process::Command::new("cmd")
+            .args(["/C", "cls"])
+            .status();
+    }
+
+    #[cfg(not(windows))]
+    {
+        // ANSIエスケープで画面消去とカーソルを左上へ
+        print!("\x1B[2J\x1B[H");
+        let _ = io::stdout().flush();
+    }
 }
 
 fn show_header() {
     println!("================================");
-    println!("   MetaGuard - MT4/MT5 監視    ");
+    println!("{}", t!("app.title"));
     println!("================================");
-    println!("現在時刻: {}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+    println!("{}", t!("header.now", time = Local::now().format("%Y-%m-%d %H:%M:%S")));
+    if let Some(profile) = config_location().profile() {
+        println!("{}", t!("header.profile", name = profile, path = get_config_path().display()));
+    }
+    if let Some(holder) = lock::current_holder(&get_config_path()) {
+        if holder.pid != std::process::id() {
+            println!("{}", t!("header.other_monitor", pid = holder.pid, started = holder.started_at));
+        }
+    }
     println!();
 }
 
 fn show_menu() {
-    println!("メインメニュー:");
-    println!("1. 監視を開始");
-    println!("2. MT4/MT5一覧を表示");
-    println!("3. MT4/MT5を追加");
-    println!("4. MT4/MT5の有効/無効を切り替え");
-    println!("5. MT4/MT5を削除");
-    println!("6. MT4/MT5を自動検索");
-    println!("7. チェック間隔を変更");
-    println!("8. Windows自動起動設定");
-    println!("9. 設定ファイルを開く");
-    println!("0. 終了");
+    println!("{}", t!("menu.title"));
+    for item in [
+        "monitor", "list", "add", "toggle", "remove", "search", "interval", "autostart", "config", "quit",
+    ] {
+        println!("{}", t!(&format!("menu.{}", item)));
+    }
     println!();
-    print!("選択してください (0-9): ");
+    print!("{} ", t!("menu.prompt"));
     io::stdout().flush().unwrap();
 }
 
@@ -473,321 +986,1115 @@ fn wait_for_enter() {
 
 fn monitoring_mode(config: &HashMap<String, String>) {
     clear_screen();
-    println!("=== 監視モード ===");
-    println!("Ctrl+C で停止します\n");
+    println!("{}", t!("monitor.title"));
+    
+    // 自動起動した監視など、別のMetaGuardが同じ設定を監視中なら開始しない
+    let _lock = match InstanceLock::acquire(&get_config_path()) {
+        Ok(lock) => lock,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", t!("monitor.not_started"));
+            println!("{}", t!("common.press_enter_back"));
+            wait_for_enter();
+            return;
+        }
+    };
     
-    ctrlc::set_handler(move || {
-        println!("\n\n監視を停止しました。");
-        std::process::exit(0);
-    }).expect("Ctrl+Cハンドラーの設定に失敗");
+    let shutdown = ShutdownToken::global();
+    let _active = shutdown.begin();
+    let mut config = config.clone();
+    let mut monitor = new_monitor(&config);
+    
+    // 端末で動かしているときは監視画面（TUI）を使う
+    if io::stdout().is_terminal() {
+        match dashboard_mode(&mut config, &mut monitor, &shutdown) {
+            Ok(()) => {
+                println!("{}", t!("monitor.stopped"));
+                finish_monitoring(&monitor);
+                println!("{}", t!("common.press_enter_back"));
+                wait_for_enter();
+                return;
+            }
+            Err(e) => println!("{}", t!("monitor.tui_failed", error = e)),
+        }
+    }
     
-    let interval = config.get("CheckInterval")
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(30);
+    println!("{}\n", t!("monitor.ctrl_c"));
+    report_config_problems(&config);
+    // 開始直後と再読み込み直後はすべてのインスタンスを確認する
+    let mut check_all = true;
     
-    loop {
-        println!("\n[{}] チェック開始...", Local::now().format("%H:%M:%S"));
-        check_and_restart_mt4(config);
+    'monitor: loop {
+        let requested = take_monitor_requests(&shutdown);
+        if let Err(e) = &requested {
+            println!("{}", e);
+        }
+        if shutdown.take_reload() {
+            if let Err(e) = reload_config(&mut config) {
+                println!("{}", t!("config.reload_failed", error = e));
+            }
+            apply_language(&config);
+            monitor.set_policy(RestartPolicy::from_config(&config));
+            println!("\n{}", t!("monitor.reloaded"));
+            report_config_problems(&config);
+            check_all = true;
+        }
+        for event in &restart_requested(&mut monitor, &config, requested.as_deref().unwrap_or_default()) {
+            print_event(event);
+        }
+        
+        println!("\n{}", t!("monitor.check_start", time = Local::now().format("%H:%M:%S")));
+        check_and_restart_mt4(&mut monitor, &config, check_all);
+        check_all = false;
         
-        println!("\n次回チェック: {}秒後", interval);
-        for i in (1..=interval).rev() {
-            print!("\r残り: {}秒  ", i);
-            io::stdout().flush().unwrap();
-            thread::sleep(Duration::from_secs(1));
+        let wait = time_until_next_check(&monitor, &config);
+        println!("\n{}", t!("monitor.next_check", secs = wait.as_secs()));
+        if !wait_until_next_check(&mut monitor, &config, &shutdown, wait, true, requested.is_ok()) {
+            break 'monitor;
         }
     }
+    
+    println!("\n\n{}", t!("monitor.stopped"));
+    finish_monitoring(&monitor);
+    
+    println!("{}", t!("common.press_enter_back"));
+    wait_for_enter();
 }
 
 fn auto_monitoring_mode(config: &HashMap<String, String>) {
     // ウィンドウを最小化して小さいサイズに設定
     minimize_and_resize_console();
 
-    let interval = config.get("CheckInterval")
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(30);
+    let shutdown = ShutdownToken::global();
+    let _active = shutdown.begin();
+    let mut config = config.clone();
+    let mut monitor = new_monitor(&config);
+    report_config_problems(&config);
+    let mut check_all = true;
+
+    if !wait_for_startup_delay(&config, &shutdown) {
+        println!("{}", t!("monitor.stopped"));
+        finish_monitoring(&monitor);
+        return;
+    }
 
     loop {
-        check_and_restart_mt4(config);
-        thread::sleep(Duration::from_secs(interval));
+        let requested = take_monitor_requests(&shutdown);
+        if let Err(e) = &requested {
+            println!("{}", e);
+        }
+        if shutdown.take_reload() {
+            if let Err(e) = reload_config(&mut config) {
+                println!("{}", t!("config.reload_failed", error = e));
+            }
+            apply_language(&config);
+            monitor.set_policy(RestartPolicy::from_config(&config));
+            println!("{}", t!("monitor.reloaded"));
+            report_config_problems(&config);
+            check_all = true;
+        }
+        for event in &restart_requested(&mut monitor, &config, requested.as_deref().unwrap_or_default()) {
+            print_event(event);
+        }
+
+        check_and_restart_mt4(&mut monitor, &config, check_all);
+        check_all = false;
+        let wait = time_until_next_check(&monitor, &config);
+        if !wait_until_next_check(&mut monitor, &config, &shutdown, wait, false, requested.is_ok()) {
+            break;
+        }
     }
+
+    println!("{}", t!("monitor.stopped"));
+    finish_monitoring(&monitor);
 }
 
-fn check_and_restart_mt4(config: &HashMap<String, String>) {
-    let mut system = System::new_all();
-    system.refresh_processes();
-    
-    let mt_entries: Vec<_> = config.iter()
-        .filter(|(k, _)| k.starts_with("MT_"))
-        .collect();
+/// OS起動直後なら StartupDelay が経つまで待つ。停止が要求されたら false を返す
+fn wait_for_startup_delay(config: &HashMap<String, String>, shutdown: &ShutdownToken) -> bool {
+    let delay = config.get("StartupDelay")
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let uptime = System::new().uptime();
+    if delay <= uptime {
+        return true;
+    }
+    let remaining = delay - uptime;
+    println!("{}", t!("monitor.startup_delay", secs = remaining));
+    shutdown.sleep(Duration::from_secs(remaining))
+}
+
+/// 読み込めなかった設定を表示する
+fn report_config_problems(config: &HashMap<String, String>) {
+    for problem in config_problems(config) {
+        println!("{}", t!("monitor.config_ignored", problem = problem));
+    }
+}
+
+/// 読み込めなかった設定の一覧
+fn config_problems(config: &HashMap<String, String>) -> std::collections::BTreeSet<String> {
+    instance::parse_instances(config)
+        .into_iter()
+        .flat_map(|i| {
+            let unknown = i
+                .unknown_dependencies
+                .iter()
+                .map(|id| t!("config.unknown_dependency", key = i.key, id = id))
+                .collect::<Vec<_>>();
+            i.schedule.errors.into_iter().chain(unknown)
+        })
+        .collect()
+}
+
+/// 監視画面（TUI）で監視する。画面を使えない場合はエラーを返す
+fn dashboard_mode(
+    config: &mut HashMap<String, String>,
+    monitor: &mut SystemMonitor,
+    shutdown: &ShutdownToken,
+) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run_dashboard(&mut terminal, config, monitor, shutdown);
+    ratatui::restore();
+    result
+}
+
+fn run_dashboard(
+    terminal: &mut ratatui::DefaultTerminal,
+    config: &mut HashMap<String, String>,
+    monitor: &mut SystemMonitor,
+    shutdown: &ShutdownToken,
+) -> io::Result<()> {
+    let mut dashboard = Dashboard::new();
+    for problem in config_problems(config) {
+        dashboard.push_log(t!("monitor.config_ignored", problem = problem));
+    }
+    let mut check_all = true;
+    // 同じ読み込みの失敗を何度もログに出さない
+    let mut request_error = None;
     
-    for (_, value) in mt_entries {
-        let parts: Vec<&str> = value.split('|').collect();
-        if parts.len() != 3 {
-            continue;
+    loop {
+        if shutdown.is_shutdown() {
+            return Ok(());
         }
+        let requested = take_monitor_requests(shutdown);
+        match &requested {
+            Err(e) if request_error.as_ref() != Some(e) => dashboard.push_log(e.clone()),
+            _ => {}
+        }
+        request_error = requested.as_ref().err().cloned();
+        if shutdown.take_reload() {
+            if let Err(e) = reload_config(config) {
+                dashboard.push_log(t!("config.reload_failed", error = e));
+            }
+            apply_language(config);
+            monitor.set_policy(RestartPolicy::from_config(config));
+            dashboard.push_log(t!("monitor.reloaded"));
+            check_all = true;
+        }
+        let events = restart_requested(monitor, config, requested.as_deref().unwrap_or_default());
+        show_events(&mut dashboard, &events);
         
-        let enabled = parts[0] == "1";
-        let name = parts[1];
-        let path = parts[2];
-        
-        if !enabled {
-            continue;
+        let instances = instance::parse_instances(config);
+        let due = monitor.next_due(&instances).is_some_and(|at| at <= Utc::now());
+        if check_all || due {
+            let events = run_check(monitor, config, check_all);
+            show_events(&mut dashboard, &events);
+            check_all = false;
+        }
+        if let Some(exit) = monitor.wait_for_exit(Duration::ZERO) {
+            let events = monitor.handle_exit(&exit, &instances);
+            record_events(config, &events);
+            save_state(monitor, "exit");
+            show_events(&mut dashboard, &events);
         }
         
-        let is_running = system.processes().values().any(|process| {
-            let exe_path = process.exe();
-            exe_path.to_string_lossy().to_lowercase() == path.to_lowercase()
-        });
+        dashboard.status = t!("monitor.next_check", secs = time_until_next_check(monitor, config).as_secs());
+        dashboard.set_rows(dashboard::rows(&instances, monitor, Utc::now()));
+        terminal.draw(|frame| dashboard.render(frame))?;
         
-        if is_running {
-            println!("✓ {} - 実行中", name);
-        } else {
-            println!("✗ {} - 停止中", name);
-            
-            match std::process::Command::new(path).spawn() {
-                Ok(_) => {
-                    println!("  → 起動しました！");
-                }
-                Err(e) => {
-                    println!("  → 起動失敗: {}", e);
+        if !term_event::poll(EXIT_POLL_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = term_event::read()? else {
+            continue;
+        };
+        match dashboard.handle_key(key) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Check) => check_all = true,
+            Some(Action::Restart(key)) => {
+                let events = monitor.restart(&key, &instances);
+                record_events(config, &events);
+                save_state(monitor, "restart");
+                show_events(&mut dashboard, &events);
+            }
+            Some(Action::Toggle(key)) => {
+                if let Some((name, enabled)) = toggle_instance(config, &key) {
+                    match save_config(config) {
+                        Ok(()) => {
+                            dashboard.push_log(toggled_message(&name, enabled));
+                            check_all = true;
+                        }
+                        Err(e) => {
+                            // 保存できなければ元に戻す
+                            toggle_instance(config, &key);
+                            dashboard.push_log(e);
+                        }
+                    }
                 }
             }
+            None => {}
         }
     }
 }
 
-fn list_mt4_instances(config: &HashMap<String, String>) {
-    clear_screen();
-    println!("=== MT4/MT5 一覧 ===\n");
+/// 履歴に残す出来事を監視画面のログに出す
+fn show_events(dashboard: &mut Dashboard, events: &[MonitorEvent]) {
+    for event in events.iter().filter(|e| history::describe(e).is_some()) {
+        dashboard.push_event(&event.key, event.at, &event.message());
+    }
+}
+
+fn new_monitor(config: &HashMap<String, String>) -> SystemMonitor {
+    let mut monitor = Monitor::new(SysinfoProcessTable::new(), CommandLauncher::new(), SystemClock);
+    monitor.set_policy(RestartPolicy::from_config(config));
+    // 前回の再起動回数や待機を引き継ぐ
+    match state::load_snapshot(&state::state_path_for(&get_config_path())) {
+        Ok(statuses) => monitor.restore(&statuses),
+        Err(e) => eprintln!("{}", t!("monitor.state_load_failed", error = e)),
+    }
+    monitor
+}
+
+/// 次のチェックまで待つ。停止が要求されたら false を返す
+///
+/// 待機中にMetaGuardが起動したMT4/MT5が終了した場合は、間隔を待たずに再起動する。
+/// `watch_requests` が true なら、依頼が置かれたときも待たずに戻る（依頼を取り出せなかった直後は
+/// 置かれたままなので false にし、次のチェックまで待つ）。
+fn wait_until_next_check(
+    monitor: &mut SystemMonitor,
+    config: &HashMap<String, String>,
+    shutdown: &ShutdownToken,
+    wait: Duration,
+    countdown: bool,
+    watch_requests: bool,
+) -> bool {
+    let deadline = Instant::now() + wait;
+    let mut shown = None;
+    let requests = stop::request_dir_for(&get_config_path());
     
-    let mut mt_entries: Vec<_> = config.iter()
-        .filter(|(k, _)| k.starts_with("MT_"))
-        .collect();
-    
-    if mt_entries.is_empty() {
-        println!("登録されているMT4/MT5はありません。");
-    } else {
-        mt_entries.sort_by_key(|(k, _)| k.as_str());
+    loop {
+        if shutdown.is_shutdown() {
+            return false;
+        }
+        let now = Instant::now();
+        // 依頼があれば待たずに応える（依頼フォルダを見るので Windows でも届く）
+        if now >= deadline || shutdown.reload_pending() || (watch_requests && stop::has_requests(&requests)) {
+            return true;
+        }
+        let remaining = deadline - now;
         
-        for (i, (_, value)) in mt_entries.iter().enumerate() {
-            let parts: Vec<&str> = value.split('|').collect();
-            if parts.len() == 3 {
-                let enabled = parts[0] == "1";
-                let name = parts[1];
-                let path = parts[2];
-                
-                println!("{}. [{}] {}", 
-                    i + 1,
-                    if enabled { "有効" } else { "無効" },
-                    name
-                );
-                println!("   パス: {}", path);
+        if countdown {
+            let secs = remaining.as_secs() + 1;
+            if shown != Some(secs) {
+                print!("\r{}  ", t!("monitor.remaining", secs = secs));
+                io::stdout().flush().unwrap();
+                shown = Some(secs);
+            }
+        }
+        
+        if let Some(exit) = monitor.wait_for_exit(remaining.min(EXIT_POLL_INTERVAL)) {
+            if countdown {
                 println!();
             }
+            println!("{}", t!("monitor.exit_detected", time = Local::now().format("%H:%M:%S")));
+            let instances = instance::parse_instances(config);
+            let events = monitor.handle_exit(&exit, &instances);
+            for event in &events {
+                print_event(event);
+            }
+            record_events(config, &events);
+            save_state(monitor, "exit");
+            shown = None;
+        }
+    }
+}
+
+/// いずれかのインスタンスの確認時刻が来るまでの時間
+fn time_until_next_check(monitor: &SystemMonitor, config: &HashMap<String, String>) -> Duration {
+    let instances = instance::parse_instances(config);
+    let fallback = monitor.policy().check_interval;
+    let next = monitor
+        .next_due(&instances)
+        .unwrap_or_else(|| Utc::now() + fallback);
+    // 表示と待機は秒単位にそろえる（最短1秒）
+    let secs = (next - Utc::now()).num_milliseconds().max(0) as u64;
+    Duration::from_secs(secs.div_ceil(1000).max(1))
+}
+
+/// 監視終了時の後始末（出力のフラッシュと最終状態の書き出し）
+fn finish_monitoring(monitor: &SystemMonitor) {
+    save_state(monitor, "shutdown");
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+}
+
+/// 状態ファイルを書き出す（MetaGuardを再起動しても引き継げるように）
+fn save_state(monitor: &SystemMonitor, reason: &str) {
+    let state_path = state::state_path_for(&get_config_path());
+    if let Err(e) = state::write_snapshot(&state_path, &monitor.statuses(), reason) {
+        eprintln!("{}", t!("monitor.state_write_failed", error = e));
+    }
+}
+
+fn check_and_restart_mt4(monitor: &mut SystemMonitor, config: &HashMap<String, String>, check_all: bool) {
+    for event in &run_check(monitor, config, check_all) {
+        print_event(event);
+    }
+}
+
+/// チェックして、出来事の記録と状態ファイルの書き出しまで行う
+fn run_check(monitor: &mut SystemMonitor, config: &HashMap<String, String>, check_all: bool) -> Vec<MonitorEvent> {
+    let instances = instance::parse_instances(config);
+    let events = if check_all {
+        monitor.check(&instances)
+    } else {
+        monitor.check_due(&instances)
+    };
+    record_events(config, &events);
+    save_state(monitor, "check");
+    events
+}
+
+/// 再起動などの出来事を履歴ファイルに追記し、警告を通知する
+fn record_events(config: &HashMap<String, String>, events: &[MonitorEvent]) {
+    let path = history::history_path_for(&get_config_path());
+    if let Err(e) = history::append(&path, events) {
+        eprintln!("{}", t!("monitor.history_write_failed", error = e));
+    }
+    if let Some(notifier) = CommandNotifier::from_config(config) {
+        for event in events {
+            if let Err(e) = notifier.notify(event) {
+                eprintln!("{}", t!("monitor.notify_failed", error = e));
+            }
+        }
+    }
+}
+
+/// 監視イベントを画面に表示する
+fn print_event(event: &MonitorEvent) {
+    println!("{}", event_line(event));
+}
+
+/// 監視イベントの表示（どのインスタンスかわかるようにIDを付ける）
+fn event_line(event: &MonitorEvent) -> String {
+    format!("[{}] {}", event.key, event.message().trim_start())
+}
+
+fn list_mt4_instances(config: &HashMap<String, String>) {
+    clear_screen();
+    println!("{}", t!("list.title"));
+    
+    let instances = instance::parse_instances(config);
+    
+    // グループやタグを付けていれば絞り込める
+    let mut selector = Selector::all();
+    if instances.iter().any(|i| i.group.is_some() || !i.tags.is_empty()) {
+        print!("{} ", t!("list.filter_prompt"));
+        io::stdout().flush().unwrap();
+        let input = get_user_input();
+        if !input.trim().is_empty() {
+            match Selector::parse(&input) {
+                Ok(parsed) => selector = parsed,
+                Err(e) => {
+                    println!("{}", e);
+                    wait_for_enter();
+                    return;
+                }
+            }
         }
+        println!();
     }
     
-    println!("\nEnterキーを押して戻る...");
+    print!("{}", describe_instances(&selector.select(&instances)));
+    
+    println!("{}", t!("common.press_enter_back"));
     wait_for_enter();
 }
 
+/// インスタンスの一覧の文（メニューの一覧と `list` コマンドで使う）
+fn describe_instances(instances: &[&MtInstance]) -> String {
+    if instances.is_empty() {
+        return format!("{}\n", t!("common.no_instances"));
+    }
+    let mut text = String::new();
+    for instance in instances {
+        let mut lines = vec![
+            instance_line(instance),
+            t!("list.path", path = instance.path),
+        ];
+        if !instance.args.is_empty() {
+            lines.push(t!("list.args", args = instance.args.join(" ")));
+        }
+        if instance.launch_mode == LaunchMode::Wine {
+            lines.push(t!("list.wine",
+                binary = instance.wine_binary,
+                prefix = instance.wine_prefix.clone().unwrap_or_else(|| t!("common.default")),
+            ));
+        }
+        if let Some(group) = &instance.group {
+            lines.push(t!("list.group", group = group));
+        }
+        if !instance.tags.is_empty() {
+            lines.push(t!("list.tags", tags = instance.tags.join(", ")));
+        }
+        for line in lines {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text
+}
+
 fn add_mt4_instance(config: &mut HashMap<String, String>) {
     clear_screen();
-    println!("=== MT4/MT5を追加 ===\n");
+    println!("{}", t!("add.title"));
     
-    print!("名前を入力してください: ");
+    print!("{} ", t!("add.name_prompt"));
     io::stdout().flush().unwrap();
     let name = get_user_input().trim().to_string();
     
     if name.is_empty() {
-        println!("名前が入力されていません。");
+        println!("{}", t!("add.name_missing"));
         wait_for_enter();
         return;
     }
     
-    print!("実行ファイルのパスを入力してください: ");
+    print!("{} ", t!("add.path_prompt"));
     io::stdout().flush().unwrap();
     let path = get_user_input().trim().to_string();
     
     if path.is_empty() {
-        println!("パスが入力されていません。");
+        println!("{}", t!("add.path_missing"));
         wait_for_enter();
         return;
     }
     
-    if !std::path::Path::new(&path).exists() {
-        println!("指定されたファイルが見つかりません: {}", path);
-        wait_for_enter();
-        return;
+    // Linuxでは wine 経由で起動するため WINEPREFIX も確認する
+    let mut wine_prefix = String::new();
+    if LaunchMode::platform_default() == LaunchMode::Wine {
+        print!("{} ", t!("add.prefix_prompt"));
+        io::stdout().flush().unwrap();
+        wine_prefix = get_user_input().trim().to_string();
     }
     
-    // 次の番号を決定
-    let mut next_num = 1;
-    loop {
-        let key = format!("MT_{}", next_num);
-        if !config.contains_key(&key) {
-            break;
-        }
-        next_num += 1;
+    let host_path = match LaunchMode::platform_default() {
+        LaunchMode::Native => PathBuf::from(&path),
+        LaunchMode::Wine => wine::to_unix_path(
+            Some(std::path::Path::new(&wine_prefix)).filter(|_| !wine_prefix.is_empty()),
+            &path,
+        ),
+    };
+    if !host_path.exists() {
+        println!("{}", t!("add.not_found", path = host_path.display()));
+        wait_for_enter();
+        return;
     }
     
-    let key = format!("MT_{}", next_num);
+    // 番号は使われている一番大きい番号の次（途中の空いた番号は使わない）
+    let key = instance::next_key(config);
     let value = format!("1|{}|{}", name, path);
-    config.insert(key, value);
+    if !wine_prefix.is_empty() {
+        config.insert(option_key(&key, "WinePrefix"), wine_prefix);
+    }
+    config.insert(key.clone(), value);
     
-    save_config(config);
-    println!("\n✓ MT4/MT5を追加しました！");
+    if !save_config_from_menu(config) {
+        return;
+    }
+    println!("{}", t!("add.done", key = key));
     thread::sleep(Duration::from_secs(2));
 }
 
 fn toggle_mt4_instance(config: &mut HashMap<String, String>) {
     clear_screen();
-    println!("=== 有効/無効の切り替え ===\n");
-    
-    let mut mt_entries: Vec<_> = config.iter()
-        .filter(|(k, _)| k.starts_with("MT_"))
-        .map(|(k, v)| (k.clone(), v.clone()))
-        .collect();
+    println!("{}", t!("toggle.title"));
     
-    if mt_entries.is_empty() {
-        println!("登録されているMT4/MT5はありません。");
+    let instances = instance::parse_instances(config);
+    if instances.is_empty() {
+        println!("{}", t!("common.no_instances"));
         wait_for_enter();
         return;
     }
     
-    mt_entries.sort_by_key(|(k, _)| k.clone());
-    
-    for (i, (_, value)) in mt_entries.iter().enumerate() {
-        let parts: Vec<&str> = value.split('|').collect();
-        if parts.len() == 3 {
-            let enabled = parts[0] == "1";
-            let name = parts[1];
-            
-            println!("{}. [{}] {}", 
-                i + 1,
-                if enabled { "有効" } else { "無効" },
-                name
-            );
-        }
+    for instance in &instances {
+        println!("{}", instance_line(instance));
     }
     
-    print!("\n切り替える番号を入力してください (0で戻る): ");
+    print!("{} ", t!("toggle.prompt"));
     io::stdout().flush().unwrap();
     let input = get_user_input();
+    let input = input.trim();
+    if input.is_empty() || input == "0" {
+        return;
+    }
     
-    if let Ok(num) = input.trim().parse::<usize>() {
-        if num == 0 {
+    let Some(key) = instance::resolve_instance_id(input, instances.iter().map(|i| i.key.as_str())) else {
+        // 番号の代わりにグループ・タグを入力したらまとめて操作する（打ち間違えたIDは選び方として扱わない）
+        if is_selector_input(input, &instances) {
+            bulk_action(config, input);
+        } else {
+            println!("{}", t!("common.invalid_number"));
+            wait_for_enter();
+        }
+        return;
+    };
+    let key = key.to_string();
+    if let Some((name, enabled)) = toggle_instance(config, &key) {
+        if !save_config_from_menu(config) {
             return;
         }
-        if num > 0 && num <= mt_entries.len() {
-            let (key, value) = &mt_entries[num - 1];
-            let parts: Vec<&str> = value.split('|').collect();
-            
-            if parts.len() == 3 {
-                let enabled = parts[0] != "1";
-                let new_value = format!("{}|{}|{}", 
-                    if enabled { "1" } else { "0" },
-                    parts[1],
-                    parts[2]
-                );
-                
-                config.insert(key.clone(), new_value);
-                save_config(config);
-                
-                let status = if enabled { "有効" } else { "無効" };
-                println!("\n✓ {} を{}にしました", parts[1], status);
-                thread::sleep(Duration::from_secs(2));
+        
+        println!("\n✓ {}", toggled_message(&name, enabled));
+        thread::sleep(Duration::from_secs(2));
+    }
+}
+
+/// メニューの入力がグループ・タグの選び方か（選び方の記号を含むか、あるグループ・タグの名前）
+fn is_selector_input(input: &str, instances: &[MtInstance]) -> bool {
+    input.contains([':', '+', ',', '!', '*'])
+        || input.eq_ignore_ascii_case("all")
+        || instances.iter().any(|i| i.in_group(input) || i.has_tag(input))
+}
+
+/// 一覧の1行（`MT_3 [有効] 表示名`）
+fn instance_line(instance: &MtInstance) -> String {
+    t!("list.item", key = instance.key, status = enabled_label(instance.enabled), name = instance.name)
+}
+
+/// 有効/無効の表示名
+fn enabled_label(enabled: bool) -> String {
+    if enabled { t!("common.enabled") } else { t!("common.disabled") }
+}
+
+/// 有効/無効を切り替えたときの文
+fn toggled_message(name: &str, enabled: bool) -> String {
+    if enabled {
+        t!("toggle.enabled", name = name)
+    } else {
+        t!("toggle.disabled", name = name)
+    }
+}
+
+/// インスタンスの有効/無効を切り替える。切り替えたら表示名と切り替え後の状態を返す
+fn toggle_instance(config: &mut HashMap<String, String>, key: &str) -> Option<(String, bool)> {
+    let enabled = !config.get(key)?.starts_with("1|");
+    set_instance_enabled(config, key, enabled).map(|name| (name, enabled))
+}
+
+/// インスタンスを有効/無効にする。設定したら表示名を返す
+fn set_instance_enabled(config: &mut HashMap<String, String>, key: &str, enabled: bool) -> Option<String> {
+    let value = config.get(key)?;
+    let parts: Vec<&str> = value.split('|').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let name = parts[1].to_string();
+    let new_value = format!("{}|{}|{}",
+        if enabled { "1" } else { "0" },
+        parts[1],
+        parts[2]
+    );
+    config.insert(key.to_string(), new_value);
+    Some(name)
+}
+
+/// グループ・タグで選んだインスタンスをまとめて操作する（有効/無効の切り替えのメニューから）
+fn bulk_action(config: &mut HashMap<String, String>, selector: &str) {
+    let selected = match select_instances(config, &[selector.to_string()]) {
+        Ok(selected) => selected,
+        Err(e) => {
+            println!("{}", e);
+            wait_for_enter();
+            return;
+        }
+    };
+    
+    println!();
+    for instance in &selected {
+        println!("  {}", instance_line(instance));
+    }
+    print!("{} ", t!("select.action_prompt", count = selected.len()));
+    io::stdout().flush().unwrap();
+    
+    let choice = get_user_input();
+    let result = match choice.trim() {
+        "1" => set_enabled_for(config, &selected, true),
+        "2" => set_enabled_for(config, &selected, false),
+        // 再起動と終了は、上の一覧の対象でよいか確かめてから
+        choice @ ("3" | "4") => {
+            let restart = choice == "3";
+            if !confirm(&stop_question(selected.len(), restart)) {
+                println!("{}", t!("common.cancelled"));
+                thread::sleep(Duration::from_secs(1));
+                return;
+            }
+            stop_instances(config, &selected, restart)
+        }
+        _ => return,
+    };
+    println!();
+    match result {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
             }
+        }
+        Err(e) => println!("{}", e),
+    }
+    println!("{}", t!("common.press_enter_back"));
+    wait_for_enter();
+}
+
+/// コマンドの引数（`live`、`group:tanaka` など）でインスタンスを選ぶ。複数書けばどれかに当てはまるもの
+fn select_instances(config: &HashMap<String, String>, args: &[String]) -> Result<Vec<MtInstance>, String> {
+    if args.is_empty() {
+        return Err(t!("select.usage"));
+    }
+    let text = args.join(",");
+    let selector = Selector::parse(&text)?;
+    let instances = instance::parse_instances(config);
+    let selected: Vec<MtInstance> = selector.select(&instances).into_iter().cloned().collect();
+    if selected.is_empty() {
+        return Err(t!("select.none", selector = text));
+    }
+    Ok(selected)
+}
+
+/// `list [選び方]`: インスタンスの一覧（グループ・タグで絞り込める）
+fn list_command(args: &[String]) -> Result<String, String> {
+    let config = read_config()?;
+    let selector = match args.is_empty() {
+        true => Selector::all(),
+        false => Selector::parse(&args.join(","))?,
+    };
+    let instances = instance::parse_instances(&config);
+    Ok(describe_instances(&selector.select(&instances)).trim_end().to_string())
+}
+
+/// `enable`・`disable <選び方>`: まとめて有効/無効にする
+fn set_enabled_command(args: &[String], enabled: bool) -> Result<String, String> {
+    let mut config = read_config()?;
+    let selected = select_instances(&config, args)?;
+    Ok(set_enabled_for(&mut config, &selected, enabled)?.join("\n"))
+}
+
+/// `restart`・`stop <選び方> [--yes]`: まとめて再起動・終了する
+///
+/// 対象を表示して確かめてから行う。端末から実行していないときは `--yes` が必要。
+fn stop_command(args: &[String], restart: bool) -> Result<String, String> {
+    let yes = args.iter().any(|a| a == "--yes" || a == "-y");
+    let args: Vec<String> = args.iter().filter(|a| *a != "--yes" && *a != "-y").cloned().collect();
+    let mut config = read_config()?;
+    let selected = select_instances(&config, &args)?;
+    
+    if !yes {
+        for instance in &selected {
+            println!("  {}", instance_line(instance));
+        }
+        if !io::stdin().is_terminal() {
+            return Err(t!("select.confirm_required"));
+        }
+        if !confirm(&stop_question(selected.len(), restart)) {
+            return Ok(t!("common.cancelled"));
+        }
+    }
+    Ok(stop_instances(&mut config, &selected, restart)?.join("\n"))
+}
+
+/// 一覧のファイルの形式（拡張子が .csv / .json でなければエラー）
+fn list_format(path: &Path) -> Result<ListFormat, String> {
+    ListFormat::from_path(path).ok_or_else(|| t!("transfer.unknown_format", path = path.display()))
+}
+
+/// `export <ファイル> [選び方]`: インスタンスの一覧を CSV・JSON に書き出す
+fn export_command(args: &[String]) -> Result<String, String> {
+    let Some((file, selectors)) = args.split_first() else {
+        return Err(t!("transfer.usage"));
+    };
+    let path = Path::new(file);
+    let format = list_format(path)?;
+    let config = read_config()?;
+    let mut instances = transfer::instance_configs(&config)?;
+    if !selectors.is_empty() {
+        let selected = select_instances(&config, selectors)?;
+        instances.retain(|i| selected.iter().any(|s| s.key == i.key));
+    }
+    let content = transfer::export(&instances, format)?;
+    backup::write_atomic(path, &content)
+        .map_err(|e| t!("transfer.write_failed", path = path.display(), error = e))?;
+    Ok(t!("transfer.exported", count = instances.len(), path = path.display()))
+}
+
+/// `import <ファイル> [--merge|--replace] [--dry-run] [--map 元=先] [--yes]`: 一覧を読み込む
+///
+/// 書き込む前にファイル全体を確かめ、追加・上書き・削除するものを表示する。
+/// `--dry-run` なら表示だけで終える。replace で削除するものがあれば確かめる。
+fn import_command(args: &[String]) -> Result<String, String> {
+    let mut file = None;
+    let mut mode = ImportMode::Merge;
+    let (mut dry_run, mut yes) = (false, false);
+    let mut rules = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--merge" => mode = ImportMode::Merge,
+            "--replace" => mode = ImportMode::Replace,
+            "--dry-run" | "-n" => dry_run = true,
+            "--yes" | "-y" => yes = true,
+            "--map" => {
+                let rule = args.next().ok_or_else(|| t!("transfer.usage"))?;
+                rules.push(PathRule::parse(rule)?);
+            }
+            _ if arg.starts_with("--map=") => rules.push(PathRule::parse(&arg["--map=".len()..])?),
+            _ if arg.starts_with('-') || file.is_some() => return Err(t!("transfer.usage")),
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+    let path = file.ok_or_else(|| t!("transfer.usage"))?;
+    let format = list_format(&path)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| t!("config.load_failed", path = path.display(), error = e))?;
+    let imported = transfer::parse(format, &content)
+        .map_err(|e| t!("config.load_failed", path = path.display(), error = e))?;
+
+    let mut config = read_config()?;
+    let plan = transfer::plan_import(&config, imported, mode, &rules)?;
+    for (key, name) in &plan.removed {
+        println!("  {}", t!("transfer.removed", key = key, name = name));
+    }
+    for (key, name) in &plan.added {
+        println!("  {}", t!("transfer.added", key = key, name = name));
+    }
+    for (key, name) in &plan.updated {
+        println!("  {}", t!("transfer.updated", key = key, name = name));
+    }
+    for (from, to) in &plan.renumbered {
+        println!("  {}", t!("transfer.renumbered", from = from, to = to));
+    }
+    for warning in &plan.warnings {
+        println!("  {}", warning);
+    }
+    // このPCにない実行ファイル（パスの置き換えが必要かもしれない）
+    for instance in instance::parse_instances(&plan.config) {
+        let imported = plan.added.iter().chain(&plan.updated).any(|(key, _)| *key == instance.key);
+        if imported && !instance.host_path().exists() {
+            println!("  {}", t!("transfer.missing_path", key = instance.key, path = instance.path));
+        }
+    }
+    println!("{}", t!("transfer.summary",
+        added = plan.added.len(),
+        updated = plan.updated.len(),
+        removed = plan.removed.len()
+    ));
+    if dry_run {
+        return Ok(t!("transfer.dry_run"));
+    }
+
+    if !plan.removed.is_empty() && !yes {
+        if !io::stdin().is_terminal() {
+            return Err(t!("select.confirm_required"));
+        }
+        if !confirm(&t!("transfer.confirm_replace", count = plan.removed.len())) {
+            return Ok(t!("common.cancelled"));
+        }
+    }
+    config = plan.config;
+    save_config(&config)?;
+    let mut lines = vec![t!("transfer.imported", path = path.display())];
+    lines.extend(request_monitor_reload());
+    Ok(lines.join("\n"))
+}
+
+/// 再起動・終了の確認の質問
+fn stop_question(count: usize, restart: bool) -> String {
+    if restart {
+        t!("select.confirm_restart", count = count)
+    } else {
+        t!("select.confirm_stop", count = count)
+    }
+}
+
+/// 選んだインスタンスをまとめて有効/無効にして保存する
+fn set_enabled_for(
+    config: &mut HashMap<String, String>,
+    selected: &[MtInstance],
+    enabled: bool,
+) -> Result<Vec<String>, String> {
+    let mut lines = mark_enabled(config, selected, enabled);
+    save_config(config)?;
+    lines.extend(request_monitor_reload());
+    Ok(lines)
+}
+
+/// 選んだインスタンスを有効/無効にする（保存はしない。結果の文を返す）
+fn mark_enabled(config: &mut HashMap<String, String>, selected: &[MtInstance], enabled: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    for instance in selected {
+        if set_instance_enabled(config, &instance.key, enabled).is_some() {
+            lines.push(toggled_message(&instance.name, enabled));
+        }
+    }
+    lines
+}
+
+/// 選んだインスタンスをまとめて終了させる（`restart` なら起動し直す）
+///
+/// 終了だけのときは、監視中のMetaGuardに起動し直されないよう先に無効にし、
+/// 監視中のMetaGuardがそれを読み込んでから終了させる。
+/// 再起動は、監視中のMetaGuardがいればそちらが起動し直すのに任せ、いなければここで起動する。
+fn stop_instances(
+    config: &mut HashMap<String, String>,
+    selected: &[MtInstance],
+    restart: bool,
+) -> Result<Vec<String>, String> {
+    if restart {
+        if let Some(holder) = monitor_holder() {
+            return request_monitored_restart(selected, holder.pid);
+        }
+    }
+    let mut lines = Vec::new();
+    if !restart {
+        lines = mark_enabled(config, selected, false);
+        save_config(config)?;
+        if let Some(holder) = monitor_holder() {
+            let request = send_monitor_requests(&[MonitorRequest::Reload])?;
+            lines.push(t!("select.reload_sent", pid = holder.pid));
+            // 読み込む前に終了させると、監視している側が異常終了とみなして起動し直す
+            if !stop::wait_until_taken(&request, MONITOR_REQUEST_TIMEOUT) {
+                lines.push(t!("select.reload_timeout", pid = holder.pid));
+                return Ok(lines);
+            }
+        }
+    }
+    let policy = RestartPolicy::from_config(config);
+    let running = SysinfoProcessTable::new().refresh();
+    let mut launcher = CommandLauncher::new();
+    let mut events = Vec::new();
+    
+    for instance in selected {
+        if restart && !instance.enabled {
+            lines.push(t!("select.skipped_disabled", name = instance.name));
+            continue;
+        }
+        let mut emit = |kind: EventKind| {
+            let event = MonitorEvent {
+                key: instance.key.clone(),
+                name: instance.name.clone(),
+                at: Utc::now(),
+                kind,
+            };
+            lines.push(event_line(&event));
+            events.push(event);
+        };
+        let found = instance.find_process(&running).map(|p| p.pid);
+        if restart {
+            emit(EventKind::ManualRestart { pid: found });
+        }
+        let mut stopped = true;
+        match found {
+            Some(pid) => {
+                let timeout = instance
+                    .stop_timeout
+                    .map(|secs| chrono::Duration::seconds(secs as i64))
+                    .unwrap_or(policy.stop_timeout);
+                let outcome = stop::stop_and_wait(pid, timeout);
+                stopped = outcome.result != stop::StopResult::StillRunning;
+                emit(EventKind::StopFinished { outcome });
+            }
+            None if !restart => emit(EventKind::Stopped),
+            None => {}
+        }
+        if restart && stopped {
+            match launcher.launch(instance) {
+                Ok(pid) => emit(EventKind::Launched { pid }),
+                Err(e) => emit(EventKind::LaunchFailed { error: e.to_string() }),
+            }
+        }
+    }
+    record_events(config, &events);
+    Ok(lines)
+}
+
+/// 監視中のMetaGuardに再起動を依頼する
+///
+/// ここで終了させると監視している側には異常終了に見え、再起動回数に数えられてしまうため、
+/// 監視している側に定期再起動と同じ手順（再起動回数に数えない）で行ってもらう。履歴もそちらに残る。
+fn request_monitored_restart(selected: &[MtInstance], pid: u32) -> Result<Vec<String>, String> {
+    let mut lines = Vec::new();
+    let mut keys = Vec::new();
+    for instance in selected {
+        if instance.enabled {
+            keys.push(instance.key.clone());
         } else {
-            println!("無効な番号です。");
-            wait_for_enter();
+            lines.push(t!("select.skipped_disabled", name = instance.name));
         }
     }
+    if keys.is_empty() {
+        return Ok(lines);
+    }
+    let requests: Vec<MonitorRequest> = keys.iter().cloned().map(MonitorRequest::Restart).collect();
+    send_monitor_requests(&requests)?;
+    lines.push(t!("select.restart_requested", pid = pid, count = keys.len()));
+    Ok(lines)
+}
+
+/// 監視中のMetaGuardとして依頼を取り出す（再読み込みは `shutdown` に伝え、再起動するキーを返す）
+///
+/// 再起動は読み込み直した設定で行うため、呼び出し側は再読み込みを済ませてから [`restart_requested`] に渡す。
+fn take_monitor_requests(shutdown: &ShutdownToken) -> Result<Vec<String>, String> {
+    let requests = stop::take_requests(&stop::request_dir_for(&get_config_path()))
+        .map_err(|e| t!("monitor.request_failed", error = e))?;
+    let mut keys = Vec::new();
+    for request in requests {
+        match request {
+            MonitorRequest::Reload => shutdown.request_reload(),
+            MonitorRequest::Restart(key) => keys.push(key),
+        }
+    }
+    Ok(keys)
+}
+
+/// 依頼された手動の再起動を行う（`restart` コマンドから。出来事は記録済み）
+fn restart_requested(monitor: &mut SystemMonitor, config: &HashMap<String, String>, keys: &[String]) -> Vec<MonitorEvent> {
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    let instances = instance::parse_instances(config);
+    let events: Vec<MonitorEvent> = keys.iter().flat_map(|key| monitor.restart(key, &instances)).collect();
+    record_events(config, &events);
+    save_state(monitor, "restart");
+    events
+}
+
+/// 監視中の別のMetaGuard（このプロセスでないもの）
+fn monitor_holder() -> Option<lock::LockHolder> {
+    lock::current_holder(&get_config_path()).filter(|holder| holder.pid != std::process::id())
+}
+
+/// 監視中の別のMetaGuardがいれば設定の再読み込みを依頼する（結果の文を返す）
+fn request_monitor_reload() -> Option<String> {
+    let holder = monitor_holder()?;
+    Some(match send_monitor_requests(&[MonitorRequest::Reload]) {
+        Ok(_) => t!("select.reload_sent", pid = holder.pid),
+        Err(e) => e,
+    })
+}
+
+/// 監視中のMetaGuardへの依頼を依頼フォルダに置く（置いたファイルのパスを返す）
+fn send_monitor_requests(requests: &[MonitorRequest]) -> Result<PathBuf, String> {
+    let dir = stop::request_dir_for(&get_config_path());
+    stop::send_requests(&dir, requests).map_err(|e| t!("select.request_failed", path = dir.display(), error = e))
 }
 
 fn remove_mt4_instance(config: &mut HashMap<String, String>) {
     clear_screen();
-    println!("=== MT4/MT5を削除 ===\n");
+    println!("{}", t!("remove.title"));
     
+    // 形式が正しくない行も消せるように、設定の行から一覧を作る
     let mut mt_entries: Vec<_> = config.iter()
-        .filter(|(k, _)| k.starts_with("MT_"))
+        .filter(|(k, _)| is_instance_key(k))
         .map(|(k, v)| (k.clone(), v.clone()))
         .collect();
     
     if mt_entries.is_empty() {
-        println!("登録されているMT4/MT5はありません。");
+        println!("{}", t!("common.no_instances"));
         wait_for_enter();
         return;
     }
     
-    mt_entries.sort_by_key(|(k, _)| k.clone());
+    mt_entries.sort_by(|(a, _), (b, _)| instance::key_order(a).cmp(&instance::key_order(b)));
     
-    for (i, (_, value)) in mt_entries.iter().enumerate() {
-        let parts: Vec<&str> = value.split('|').collect();
-        if parts.len() >= 2 {
-            println!("{}. {}", i + 1, parts[1]);
-        }
+    let entry_name = |value: &str| value.split('|').nth(1).map(str::to_string).unwrap_or_else(|| t!("common.unknown"));
+    for (key, value) in &mt_entries {
+        println!("{} {}", key, entry_name(value));
     }
     
-    print!("\n削除する番号を入力してください (0で戻る): ");
+    print!("{} ", t!("remove.prompt"));
     io::stdout().flush().unwrap();
     let input = get_user_input();
+    let input = input.trim();
+    if input.is_empty() || input == "0" {
+        return;
+    }
     
-    if let Ok(num) = input.trim().parse::<usize>() {
-        if num == 0 {
-            return;
-        }
-        if num > 0 && num <= mt_entries.len() {
-            let (key, value) = &mt_entries[num - 1];
-            let parts: Vec<&str> = value.split('|').collect();
-            let name = if parts.len() >= 2 { parts[1] } else { "不明" };
-            
-            config.remove(key);
-            save_config(config);
-            
-            println!("\n✓ {} を削除しました", name);
-            thread::sleep(Duration::from_secs(2));
-        } else {
-            println!("無効な番号です。");
-            wait_for_enter();
-        }
+    let Some((key, value)) = instance::resolve_instance_id(input, mt_entries.iter().map(|(k, _)| k.as_str()))
+        .and_then(|key| mt_entries.iter().find(|(k, _)| k == key))
+    else {
+        println!("{}", t!("common.invalid_number"));
+        wait_for_enter();
+        return;
+    };
+    let name = entry_name(value);
+    
+    // 共通の設定ファイルから来たものは、ここで消しても次に読んだときに戻るため消さない
+    let mut removed = config.clone();
+    removed.remove(key);
+    removed.retain(|k, _| !is_option_of(k, key));
+    if let Err(e) = config::check_included_removals(&removed, &config_dir()) {
+        println!("{}", e);
+        wait_for_enter();
+        return;
     }
+    
+    // 消す前に対象を見せて確かめる
+    let path = value.split('|').nth(2).unwrap_or("");
+    if !confirm(&t!("remove.confirm", key = key, name = name, path = path)) {
+        println!("{}", t!("common.cancelled"));
+        thread::sleep(Duration::from_secs(1));
+        return;
+    }
+    
+    config.remove(key);
+    config.retain(|k, _| !is_option_of(k, key));
+    if !save_config_from_menu(config) {
+        return;
+    }
+    
+    println!("{}", t!("remove.done", key = key, name = name));
+    thread::sleep(Duration::from_secs(2));
+}
+
+/// 確認の質問をして、y なら true
+fn confirm(question: &str) -> bool {
+    print!("{} ", question);
+    io::stdout().flush().unwrap();
+    matches!(get_user_input().trim().to_lowercase().as_str(), "y" | "yes")
 }
 
 fn search_and_add_mt4(config: &mut HashMap<String, String>) {
-    println!("\nMT4/MT5を検索中...");
+    println!("{}", t!("search.searching"));
     
     let instances = auto_search_mt4();
     let mut added_count = 0;
     
-    for (name, path) in instances {
+    for found in instances {
         // 既に登録されているかチェック
-        let already_exists = config.values().any(|v| {
-            let parts: Vec<&str> = v.split('|').collect();
-            parts.len() == 3 && parts[2] == path
+        let already_exists = instance::parse_instances(config).iter().any(|registered| {
+            paths::same_path(&registered.host_path().to_string_lossy(), &found.path)
+                || paths::same_path(&registered.path, &found.path)
         });
         
         if !already_exists {
-            let mut next_num = 1;
-            loop {
-                let key = format!("MT_{}", next_num);
-                if !config.contains_key(&key) {
-                    break;
-                }
-                next_num += 1;
-            }
-            
-            let key = format!("MT_{}", next_num);
-            let value = format!("1|{}|{}", name, path);
-            config.insert(key, value);
+            let key = instance::next_key(config);
+            insert_found_terminal(config, &key, &found);
             added_count += 1;
         }
     }
     
     if added_count > 0 {
-        println!("\n{}個の新しいMT4/MT5を追加しました", added_count);
+        println!("{}", t!("search.added", count = added_count));
     } else {
-        println!("\n新しいMT4/MT5は見つかりませんでした");
+        println!("{}", t!("search.none"));
     }
     
     thread::sleep(Duration::from_secs(3));
@@ -795,71 +2102,85 @@ fn search_and_add_mt4(config: &mut HashMap<String, String>) {
 
 fn change_check_interval(config: &mut HashMap<String, String>) {
     clear_screen();
-    println!("=== チェック間隔の変更 ===\n");
+    println!("{}", t!("interval.title"));
     
     let current = config.get("CheckInterval")
         .and_then(|s| s.parse::<u64>().ok())
         .unwrap_or(30);
     
-    println!("現在の間隔: {}秒", current);
+    println!("{}", t!("interval.current", secs = current));
     
-    print!("\n新しい間隔を入力してください (10-300秒): ");
+    print!("{} ", t!("interval.prompt"));
     io::stdout().flush().unwrap();
     let input = get_user_input();
     
     if let Ok(interval) = input.trim().parse::<u64>() {
-        if interval >= 10 && interval <= 300 {
+        if (10..=300).contains(&interval) {
             config.insert("CheckInterval".to_string(), interval.to_string());
-            save_config(config);
-            println!("\n✓ チェック間隔を{}秒に変更しました", interval);
+            if !save_config_from_menu(config) {
+                return;
+            }
+            println!("{}", t!("interval.done", secs = interval));
             thread::sleep(Duration::from_secs(2));
         } else {
-            println!("10〜300の間で入力してください。");
+            println!("{}", t!("interval.out_of_range"));
             wait_for_enter();
         }
     } else {
-        println!("無効な入力です。");
+        println!("{}", t!("interval.invalid"));
         wait_for_enter();
     }
 }
 
 fn toggle_auto_start() {
     clear_screen();
-    println!("=== Windows自動起動設定 ===\n");
+    println!("{}", t!("autostart.title"));
     
     let mut config = load_or_create_config();
     let is_enabled = config.get("AutoStart").map(|v| v == "1").unwrap_or(true);
-    
-    println!("現在の状態: {}", if is_enabled { "有効" } else { "無効" });
+    let backend = auto_start_backend(&config);
+    
+    println!("{}", t!("autostart.backend", description = backend.description(), name = backend.name()));
+    println!("{}", t!("autostart.state", status = enabled_label(is_enabled)));
+    let profile = config_location().profile();
+    let registration = if backend.is_enabled(profile.as_deref()) { t!("autostart.registered") } else { t!("autostart.not_registered") };
+    println!("{}", t!("autostart.registration", status = registration));
+    if !backend.is_available() {
+        println!("{}", t!("autostart.unavailable_hint", description = backend.description()));
+    }
     
     if is_enabled {
-        print!("\n自動起動を無効にしますか？ (y/n): ");
+        print!("{} ", t!("autostart.disable_prompt"));
         io::stdout().flush().unwrap();
         let input = get_user_input();
         
         if input.trim().to_lowercase() == "y" {
             config.insert("AutoStart".to_string(), "0".to_string());
-            save_config(&config);
+            if !save_config_from_menu(&config) {
+                return;
+            }
             
-            if let Err(e) = remove_auto_start() {
-                println!("エラー: {}", e);
+            if let Err(e) = backend.disable(profile.as_deref()) {
+                println!("{}", t!("common.error", error = e));
             } else {
-                println!("\n✓ 自動起動を無効にしました");
+                println!("{}", t!("autostart.disabled"));
             }
         }
     } else {
-        print!("\n自動起動を有効にしますか？ (y/n): ");
+        print!("{} ", t!("autostart.enable_prompt"));
         io::stdout().flush().unwrap();
         let input = get_user_input();
         
         if input.trim().to_lowercase() == "y" {
             config.insert("AutoStart".to_string(), "1".to_string());
-            save_config(&config);
+            if !save_config_from_menu(&config) {
+                return;
+            }
             
-            if let Err(e) = setup_auto_start() {
-                println!("エラー: {}", e);
+            if let Err(e) = enable_auto_start(backend.as_ref()) {
+                println!("{}", t!("common.error", error = e));
             } else {
-                println!("\n✓ 自動起動を有効にしました");
+                println!("{}", t!("autostart.enabled", description = backend.description()));
             }
         }
     }
@@ -867,76 +2188,42 @@ fn toggle_auto_start() {
     thread::sleep(Duration::from_secs(2));
 }
 
-fn setup_auto_start() -> Result<(), Box<dyn std::error::Error>> {
-    #[cfg(windows)]
-    {
-        use winreg::enums::*;
-        use winreg::RegKey;
-        
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let path = r"Software\Microsoft\Windows\CurrentVersion\Run";
-        let (key, _) = hkcu.create_subkey(path)?;
-        
-        let exe_path = std::env::current_exe()?;
-        let auto_start_command = format!("{} --auto", exe_path.to_string_lossy());
-        key.set_value("MetaGuard", &auto_start_command)?;
-    }
-    
-    Ok(())
+/// 設定ファイルで選択された自動起動の登録方法
+fn auto_start_backend(config: &HashMap<String, String>) -> Box<dyn AutoStartBackend> {
+    let name = config.get("AutoStartBackend").map(|s| s.as_str()).unwrap_or("auto");
+    autostart::backend_for(name).unwrap_or_else(|| {
+        eprintln!("{}", t!("autostart.invalid_backend", name = name));
+        autostart::detect()
+    })
 }
 
-fn remove_auto_start() -> Result<(), Box<dyn std::error::Error>> {
-    #[cfg(windows)]
-    {
-        use winreg::enums::*;
-        use winreg::RegKey;
-        
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let path = r"Software\Microsoft\Windows\CurrentVersion\Run";
-        let key = hkcu.open_subkey_with_flags(path, KEY_SET_VALUE)?;
-        
-        key.delete_value("MetaGuard")?;
-    }
-    
-    Ok(())
-}
-
-fn check_auto_start_enabled() -> bool {
-    #[cfg(windows)]
-    {
-        use winreg::enums::*;
-        use winreg::RegKey;
-        
-        if let Ok(hkcu) = RegKey::predef(HKEY_CURRENT_USER).open_subkey(r"Software\Microsoft\Windows\CurrentVersion\Run") {
-            if let Ok(_) = hkcu.get_value::<String, _>("MetaGuard") {
-                return true;
-            }
-        }
-    }
-    
-    false
+fn enable_auto_start(backend: &dyn AutoStartBackend) -> Result<(), Box<dyn std::error::Error>> {
+    let command = AutoStartCommand::current(config_location())?;
+    backend.enable(&command)
 }
 
 fn sync_auto_start_setting(config: &HashMap<String, String>) {
     let config_auto_start = config.get("AutoStart").map(|v| v == "1").unwrap_or(true);
-    let registry_auto_start = check_auto_start_enabled();
+    let backend = auto_start_backend(config);
+    let profile = config_location().profile();
+    let registered = backend.is_enabled(profile.as_deref());
     
-    // 設定ファイルとレジストリが一致しない場合、設定ファイルの値に合わせる
-    if config_auto_start != registry_auto_start {
+    // 設定ファイルと実際の登録が一致しない場合、設定ファイルの値に合わせる
+    if config_auto_start != registered {
         if config_auto_start {
-            println!("設定ファイルに従って自動起動を有効化します...");
-            if let Err(e) = setup_auto_start() {
-                eprintln!("自動起動設定エラー: {}", e);
+            println!("{}", t!("autostart.sync_enable", description = backend.description()));
+            if let Err(e) = enable_auto_start(backend.as_ref()) {
+                eprintln!("{}", t!("autostart.sync_enable_failed", error = e));
             } else {
-                println!("✓ 自動起動を有効にしました\n");
+                println!("{}", t!("autostart.sync_enabled"));
             }
         } else {
-            println!("設定ファイルに従って自動起動を無効化します...");
-            if let Err(e) = remove_auto_start() {
-                eprintln!("自動起動解除エラー: {}", e);
+            println!("{}", t!("autostart.sync_disable", description = backend.description()));
+            if let Err(e) = backend.disable(profile.as_deref()) {
+                eprintln!("{}", t!("autostart.sync_disable_failed", error = e));
             } else {
-                println!("✓ 自動起動を無効にしました\n");
+                println!("{}", t!("autostart.sync_disabled"));
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,146 @@
+//! 設定ファイルの版（`ConfigVersion`）と移行
+//!
+//! 設定ファイルには書き出したときの版を `ConfigVersion` として書く。古い版の設定は
+//! 読み込むときに [`MIGRATIONS`] の手順を順に当てて今の版にし、元のファイルは
+//! 日時付きのバックアップとして残す（`MetaGuard.ini.v1.20261018-093000.bak`）。
+//!
+//! 新しい MetaGuard が書いた設定ファイル（版が [`CONFIG_VERSION`] より大きい）は
+//! 読み込むだけで、上書きはしない（知らない項目を消してしまわないように）。
+//!
+//! | 版 | 内容 |
+//! |----|------|
+//! | 1 | `ConfigVersion` がない設定ファイル（v0.3.0 まで） |
+//! | 2 | `ConfigVersion` を書く。ON/OFF は ON・OFF・TRUE・FALSE・1・0 のどれか |
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::instance::is_instance_key;
+use crate::t;
+
+/// 設定ファイルの版を書くキー
+pub const VERSION_KEY: &str = "ConfigVersion";
+
+/// このMetaGuardが書く設定ファイルの版
+pub const CONFIG_VERSION: u32 = 2;
+
+/// 版を1つ上げる手順（`MIGRATIONS[i]` は版 `i + 1` から `i + 2` への移行）
+type Migration = fn(&mut HashMap<String, String>);
+
+const MIGRATIONS: &[Migration] = &[migrate_1_to_2];
+
+/// 設定の版（`ConfigVersion` がなければ 1）
+pub fn version_of(config: &HashMap<String, String>) -> Result<u32, String> {
+    match config.get(VERSION_KEY).map(|s| s.trim()) {
+        None | Some("") => Ok(1),
+        Some(value) => value
+            .parse::<u32>()
+            .ok()
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| t!("config.invalid_value", key = VERSION_KEY, value = value)),
+    }
+}
+
+/// 古い版の設定を今の版にする（移行したら元の版を返す）
+///
+/// 新しい版の設定はそのままにする（[`check_writable`] で上書きを止める）。
+pub fn migrate(config: &mut HashMap<String, String>) -> Result<Option<u32>, String> {
+    let from = version_of(config)?;
+    if from >= CONFIG_VERSION {
+        return Ok(None);
+    }
+    for step in &MIGRATIONS[from as usize - 1..] {
+        step(config);
+    }
+    config.insert(VERSION_KEY.to_string(), CONFIG_VERSION.to_string());
+    Ok(Some(from))
+}
+
+/// この版で書き出してよいか（新しい MetaGuard が書いた設定は上書きしない）
+pub fn check_writable(config: &HashMap<String, String>) -> Result<(), String> {
+    let version = version_of(config)?;
+    if version > CONFIG_VERSION {
+        return Err(t!("config.newer_version", version = version, supported = CONFIG_VERSION));
+    }
+    Ok(())
+}
+
+/// 移行前のファイルのバックアップ先（例: `MetaGuard.ini.v1.20261018-093000.bak`）
+///
+/// 保存のたびに作るバックアップ（[`crate::backup`]）と違い、古くなっても消さない。
+pub fn backup_path(path: &Path, from: u32, now: DateTime<Local>) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.{}.bak", from, now.format("%Y%m%d-%H%M%S")));
+    path.with_file_name(name)
+}
+
+/// 1 → 2: ON/OFF の値をそろえる
+///
+/// 版1では 1/0 で書いた設定ファイル（v0.2.0）も読めるようにしていて、
+/// 読めない値は ON として扱っていた。
+fn migrate_1_to_2(config: &mut HashMap<String, String>) {
+    fn flag(value: &str) -> &'static str {
+        match value.trim().to_uppercase().as_str() {
+            "OFF" | "FALSE" | "0" => "0",
+            _ => "1",
+        }
+    }
+
+    if let Some(value) = config.get_mut("AutoStart") {
+        *value = flag(value).to_string();
+    }
+    for (_, value) in config.iter_mut().filter(|(k, _)| is_instance_key(k)) {
+        let parts: Vec<&str> = value.split('|').collect();
+        if parts.len() == 3 {
+            *value = format!("{}|{}|{}", flag(parts[0]), parts[1], parts[2]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn old_files_are_upgraded() {
+        let mut config = map(&[
+            ("AutoStart", "yes"),
+            ("MT_1", "0|Demo|C:\\MT4\\terminal.exe"),
+            ("MT_2", "whatever|Live|C:\\MT5\\terminal64.exe"),
+        ]);
+        assert_eq!(migrate(&mut config).unwrap(), Some(1));
+        assert_eq!(config["ConfigVersion"], CONFIG_VERSION.to_string());
+        assert_eq!(config["AutoStart"], "1");
+        assert_eq!(config["MT_1"], "0|Demo|C:\\MT4\\terminal.exe");
+        assert_eq!(config["MT_2"], "1|Live|C:\\MT5\\terminal64.exe");
+
+        // 今の版はそのまま
+        assert_eq!(migrate(&mut config).unwrap(), None);
+    }
+
+    #[test]
+    fn newer_files_are_read_but_not_written() {
+        let mut config = map(&[("ConfigVersion", "99"), ("AutoStart", "maybe")]);
+        assert_eq!(migrate(&mut config).unwrap(), None);
+        assert_eq!(config["AutoStart"], "maybe");
+        assert!(check_writable(&config).is_err());
+        assert!(check_writable(&map(&[("ConfigVersion", "2")])).is_ok());
+        assert!(version_of(&map(&[("ConfigVersion", "two")])).is_err());
+    }
+
+    #[test]
+    fn backup_name_has_a_timestamp() {
+        let now = Local.with_ymd_and_hms(2026, 10, 18, 9, 30, 0).unwrap();
+        assert_eq!(
+            backup_path(Path::new("/srv/mg/MetaGuard.toml"), 1, now),
+            PathBuf::from("/srv/mg/MetaGuard.toml.v1.20261018-093000.bak")
+        );
+    }
+}
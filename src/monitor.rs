@@ -0,0 +1,1002 @@
+//! 監視エンジン
+//!
+//! `Monitor` はインスタンスごとの状態（実行中・起動待ち・待機中・クラッシュループ）を
+//! 持ち、`check` を呼ぶたびにプロセス一覧を確認して必要なら再起動する。
+//! プロセス一覧・起動・時刻はトレイト経由で受け取るため、テストでは
+//! すべて偽物に差し替えて決定的に動かせる。
+//!
+//! 状態の遷移:
+//!
+//! - 停止を検出 → 起動して「起動待ち」に入る
+//! - 起動待ちの間にプロセスが見つかれば「実行中」
+//! - 起動待ちのまま `StartTimeout` を過ぎた、または起動に失敗した場合は
+//!   連続失敗回数に応じて待機（10秒から倍々で最大300秒）してから再試行
+//! - 1時間あたりの再起動回数が `MaxRestartsPerHour` に達したら
+//!   「クラッシュループ」として、回数が減るまで再起動しない
+//!
+//! インスタンスごとに `MT_n.Interval` で確認の間隔を変えられる。`check_due` は
+//! 確認の時刻が来たインスタンスだけを確認し、複数のインスタンスを再起動する場合は
+//! `MT_n.Priority` の大きいものから順に起動する。
+//!
+//! 起動が集中しないよう、`LaunchStagger` で起動と起動の間隔を空けられる。
+//! `MT_n.DependsOn` に指定したインスタンスが実行中になるまでは起動しない
+//! （どちらの場合も「順番待ち」として次の確認で再試行する）。
+//!
+//! メンテナンス時間など（`schedule` モジュール）の間は再起動せず「休止中」とし、
+//! `ScheduleMode=enforce` なら動いているMT4/MT5を終了させる。
+//!
+//! `MT_n.RestartSchedule` の時刻になったら、動いているMT4/MT5を終了させてから
+//! 起動し直す（定期再起動）。定期再起動は1時間あたりの再起動回数に数えない。
+//!
+//! `MT_n.MaxMemoryMB` などリソースの上限があるインスタンスは、実行中を確認する
+//! たびに使用量を測る。上限を超えた状態が続いたら警告し、`ThresholdAction=restart`
+//! なら定期再起動と同じ手順で再起動する（こちらは再起動回数に数える）。
+//!
+//! MetaGuardが起動したMT4/MT5は終了通知（`wait_for_exit`）でも検出し、
+//! チェック間隔を待たずに `handle_exit` で再起動する。
+//! MetaGuard以外から起動されたものは従来どおり `check` の定期確認で検出する。
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Local, Utc};
+
+use crate::clock::Clock;
+use crate::instance::MtInstance;
+use crate::launcher::{Launcher, ProcessExit};
+use crate::process::{ProcessTable, RunningProcess};
+use crate::resources::{ResourceUsage, ThresholdAction};
+use crate::schedule::ScheduleMode;
+use crate::state::InstanceStatus;
+use crate::stop::{StopOutcome, StopRequest, StopResult};
+use crate::t;
+
+/// 起動失敗後の最初の待機時間（秒）
+const BACKOFF_BASE_SECS: i64 = 10;
+/// 起動失敗後の待機時間の上限（秒）
+const BACKOFF_MAX_SECS: i64 = 300;
+/// 終了を待つ間の確認間隔（秒）
+const STOP_POLL_SECS: i64 = 2;
+
+/// 監視と再起動の方針
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// 個別の間隔が指定されていないインスタンスのチェック間隔
+    pub check_interval: Duration,
+    /// 起動してからプロセスが見つかるまで待つ時間
+    pub start_timeout: Duration,
+    /// 1時間あたりの再起動回数の上限（0 = 無制限）
+    pub max_restarts_per_hour: u32,
+    /// 起動してから次のインスタンスを起動するまで空ける時間
+    pub launch_stagger: Duration,
+    /// 終了を依頼してから強制終了するまで待つ時間
+    pub stop_timeout: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            check_interval: Duration::seconds(30),
+            start_timeout: Duration::seconds(60),
+            max_restarts_per_hour: 0,
+            launch_stagger: Duration::zero(),
+            stop_timeout: Duration::seconds(30),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// 設定の `CheckInterval`・`StartTimeout`・`MaxRestartsPerHour`・`LaunchStagger`・`StopTimeout` から作る
+    pub fn from_config(config: &HashMap<String, String>) -> Self {
+        let default = RestartPolicy::default();
+        RestartPolicy {
+            check_interval: config
+                .get("CheckInterval")
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .filter(|s| *s > 0)
+                .map(Duration::seconds)
+                .unwrap_or(default.check_interval),
+            start_timeout: config
+                .get("StartTimeout")
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .filter(|s| *s > 0)
+                .map(Duration::seconds)
+                .unwrap_or(default.start_timeout),
+            max_restarts_per_hour: config
+                .get("MaxRestartsPerHour")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(default.max_restarts_per_hour),
+            launch_stagger: config
+                .get("LaunchStagger")
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .filter(|s| *s >= 0)
+                .map(Duration::seconds)
+                .unwrap_or(default.launch_stagger),
+            stop_timeout: config
+                .get("StopTimeout")
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .filter(|s| *s >= 0)
+                .map(Duration::seconds)
+                .unwrap_or(default.stop_timeout),
+        }
+    }
+}
+
+/// インスタンスの状態
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstanceState {
+    /// まだ確認していない
+    Unknown,
+    Running { pid: u32, since: DateTime<Utc> },
+    /// 起動したがまだプロセスが見つからない
+    Starting { pid: u32, since: DateTime<Utc> },
+    /// 停止していて、起動に失敗した直後の待機中
+    Backoff { until: DateTime<Utc> },
+    /// 再起動回数の上限に達した
+    CrashLoop,
+    /// 停止していて、起動の順番を待っている（時刻が決まっていればその時刻まで）
+    Pending { until: Option<DateTime<Utc>> },
+    /// メンテナンス時間などで再起動を止めている
+    Suspended,
+    /// 定期再起動やメンテナンス時間のため終了を待っている
+    Stopping { stop: StopRequest, then: AfterStop },
+}
+
+/// 終了できた後にすること
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AfterStop {
+    /// 起動し直す（`planned` は定期再起動・手動の再起動かどうか）
+    Restart { planned: bool },
+    /// メンテナンス時間などが終わるまで止めておく
+    Suspend { reason: String },
+}
+
+/// インスタンスごとの監視情報
+#[derive(Debug, Clone)]
+pub struct InstanceRuntime {
+    pub name: String,
+    pub state: InstanceState,
+    /// 直近1時間の再起動時刻
+    pub restarts: Vec<DateTime<Utc>>,
+    /// 連続した起動失敗の回数
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// 次に確認する時刻（未確認なら `None`）
+    pub next_check: Option<DateTime<Utc>>,
+    /// 最後に処理した定期再起動の時刻（監視開始より前の予定は実行しない）
+    pub last_planned_restart: Option<DateTime<Utc>>,
+    /// 定期再起動で起動し、まだ実行中を確認していない
+    pub planned_restart_pending: bool,
+    /// 最後に測ったリソース使用量
+    pub last_usage: Option<ResourceUsage>,
+    /// リソースの上限を超え始めた時刻
+    pub breach_since: Option<DateTime<Utc>>,
+    /// 今回の上限超過をすでに警告したかどうか
+    pub breach_reported: bool,
+}
+
+impl InstanceRuntime {
+    fn new(name: &str) -> Self {
+        InstanceRuntime {
+            name: name.to_string(),
+            state: InstanceState::Unknown,
+            restarts: Vec::new(),
+            consecutive_failures: 0,
+            last_error: None,
+            next_check: None,
+            last_planned_restart: None,
+            planned_restart_pending: false,
+            last_usage: None,
+            breach_since: None,
+            breach_reported: false,
+        }
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_check.is_none_or(|at| at <= now)
+    }
+
+    /// 前回のチェックで分かっているPID
+    pub fn tracked_pid(&self) -> Option<u32> {
+        match self.state {
+            InstanceState::Running { pid, .. }
+            | InstanceState::Starting { pid, .. } => Some(pid),
+            InstanceState::Stopping { ref stop, .. } => Some(stop.pid),
+            _ => None,
+        }
+    }
+}
+
+/// 1回のチェックで起きたこと
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    Running { pid: u32 },
+    Stopped,
+    Launched { pid: u32 },
+    LaunchFailed { error: String },
+    /// 起動したがまだプロセスが見つからない
+    WaitingForStart { pid: u32 },
+    /// 起動待ちのまま時間切れになった
+    StartTimedOut { pid: u32 },
+    /// 起動したプロセスの終了を検出した
+    Exited { pid: u32, code: Option<i32> },
+    BackingOff { until: DateTime<Utc> },
+    CrashLoop { restarts: usize },
+    /// 直前に別のインスタンスを起動したため、起動を遅らせた
+    LaunchDelayed { until: DateTime<Utc> },
+    /// 依存先のインスタンスが実行中になるまで起動しない
+    WaitingForDependency { key: String },
+    /// メンテナンス時間などのため再起動しない
+    Suspended { reason: String },
+    /// メンテナンス時間などのため終了させた
+    StoppedForSchedule { pid: u32, reason: String },
+    /// 終了処理の結果が出た
+    StopFinished { outcome: StopOutcome },
+    /// 定期再起動を始めた（`at` は予定の時刻）
+    PlannedRestart { pid: u32, at: DateTime<Utc> },
+    /// 手動で再起動した（停止中なら `pid` は `None`）
+    ManualRestart { pid: Option<u32> },
+    /// リソースの上限を超えた状態が続いた（`restart` は再起動するかどうか）
+    ThresholdExceeded {
+        pid: u32,
+        usage: ResourceUsage,
+        breaches: Vec<String>,
+        restart: bool,
+    },
+    /// 定期再起動などで終了を待っている
+    WaitingForStop { pid: u32 },
+    /// 定期再起動・手動の再起動で起動したプロセスを確認した
+    PlannedRestartCompleted { pid: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorEvent {
+    pub key: String,
+    pub name: String,
+    pub at: DateTime<Utc>,
+    pub kind: EventKind,
+}
+
+impl MonitorEvent {
+    /// 画面やログに出す文（複数行になることがある）
+    pub fn message(&self) -> String {
+        let name = &self.name;
+        let local_time = |at: &DateTime<Utc>| at.with_timezone(&Local).format("%H:%M:%S").to_string();
+        match &self.kind {
+            EventKind::Running { pid } => t!("event.running", name = name, pid = pid),
+            EventKind::Stopped => t!("event.stopped", name = name),
+            EventKind::Launched { .. } => t!("event.launched"),
+            EventKind::LaunchFailed { error } => t!("event.launch_failed", error = error),
+            EventKind::WaitingForStart { pid } => t!("event.waiting_for_start", name = name, pid = pid),
+            EventKind::StartTimedOut { .. } => t!("event.start_timed_out", name = name),
+            EventKind::Exited { code, .. } => t!(
+                "event.exited",
+                name = name,
+                code = code.map(|c| c.to_string()).unwrap_or_else(|| t!("common.unknown")),
+            ),
+            EventKind::BackingOff { until } => t!("event.backing_off", name = name, until = local_time(until)),
+            EventKind::CrashLoop { restarts } => t!("event.crash_loop", name = name, restarts = restarts),
+            EventKind::LaunchDelayed { until } => t!("event.launch_delayed", until = local_time(until)),
+            EventKind::WaitingForDependency { key } => t!("event.waiting_for_dependency", key = key),
+            EventKind::Suspended { reason } => t!("event.suspended", name = name, reason = reason),
+            EventKind::StoppedForSchedule { pid, reason } => {
+                t!("event.stopped_for_schedule", name = name, reason = reason, pid = pid)
+            }
+            EventKind::StopFinished { outcome } => {
+                let (pid, secs) = (outcome.pid, outcome.waited.num_seconds());
+                match (&outcome.result, &outcome.error) {
+                    (StopResult::Closed, _) => t!("event.stop_closed", name = name, pid = pid, secs = secs),
+                    (StopResult::Killed, _) => t!("event.stop_killed", name = name, pid = pid, secs = secs),
+                    (StopResult::StillRunning, Some(error)) => {
+                        t!("event.stop_failed_error", name = name, pid = pid, error = error)
+                    }
+                    (StopResult::StillRunning, None) => t!("event.stop_failed", name = name, pid = pid),
+                }
+            }
+            EventKind::PlannedRestart { pid, .. } => t!("event.planned_restart", name = name, pid = pid),
+            EventKind::WaitingForStop { pid } => t!("event.waiting_for_stop", name = name, pid = pid),
+            EventKind::ThresholdExceeded { breaches, restart, .. } => {
+                let mut message = t!("event.threshold_exceeded", name = name, breaches = breaches.join(", "));
+                if *restart {
+                    message.push('\n');
+                    message.push_str(&t!("event.threshold_restart"));
+                }
+                message
+            }
+            EventKind::ManualRestart { pid: Some(pid) } => t!("event.manual_restart", name = name, pid = pid),
+            EventKind::ManualRestart { pid: None } => t!("event.manual_start", name = name),
+            EventKind::PlannedRestartCompleted { pid } => t!("event.restart_completed", name = name, pid = pid),
+        }
+    }
+}
+
+/// 監視エンジン
+pub struct Monitor<P, L, C> {
+    processes: P,
+    launcher: L,
+    clock: C,
+    policy: RestartPolicy,
+    instances: BTreeMap<String, InstanceRuntime>,
+    /// 最後にMT4/MT5を起動した時刻
+    last_launch: Option<DateTime<Utc>>,
+}
+
+impl<P: ProcessTable, L: Launcher, C: Clock> Monitor<P, L, C> {
+    pub fn new(processes: P, launcher: L, clock: C) -> Self {
+        Monitor {
+            processes,
+            launcher,
+            clock,
+            policy: RestartPolicy::default(),
+            instances: BTreeMap::new(),
+            last_launch: None,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: RestartPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &RestartPolicy {
+        &self.policy
+    }
+
+    /// インスタンスの監視情報
+    pub fn runtime(&self, key: &str) -> Option<&InstanceRuntime> {
+        self.instances.get(key)
+    }
+
+    /// 有効なインスタンスをすべて確認し、停止しているものを起動する
+    ///
+    /// 前回見つけたPIDがあればまずそのPIDだけを確認し、消えていた場合や
+    /// まだPIDが分からない場合に限って全プロセスを走査する（1回のチェックで最大1回）。
+    pub fn check(&mut self, instances: &[MtInstance]) -> Vec<MonitorEvent> {
+        self.check_where(instances, false)
+    }
+
+    /// 確認の時刻が来たインスタンスだけを確認する
+    pub fn check_due(&mut self, instances: &[MtInstance]) -> Vec<MonitorEvent> {
+        self.check_where(instances, true)
+    }
+
+    /// 次に確認が必要になる時刻（有効なインスタンスがなければ `None`）
+    pub fn next_due(&self, instances: &[MtInstance]) -> Option<DateTime<Utc>> {
+        let now = self.clock.now();
+        instances
+            .iter()
+            .filter(|i| i.enabled)
+            .map(|i| {
+                self.instances
+                    .get(&i.key)
+                    .and_then(|r| r.next_check)
+                    .unwrap_or(now)
+            })
+            .min()
+    }
+
+    fn check_where(&mut self, instances: &[MtInstance], due_only: bool) -> Vec<MonitorEvent> {
+        let mut full_scan: Option<Vec<RunningProcess>> = None;
+        let now = self.clock.now();
+        let mut events = Vec::new();
+
+        // 設定から消えた・無効になったインスタンスの情報は捨てる
+        self.instances
+            .retain(|key, _| instances.iter().any(|i| i.enabled && i.key == *key));
+        // 依存関係を判定できるよう、有効なインスタンスはすべて登録しておく
+        for instance in instances.iter().filter(|i| i.enabled) {
+            self.instances
+                .entry(instance.key.clone())
+                .or_insert_with(|| InstanceRuntime::new(&instance.name));
+        }
+
+        let targets: Vec<&MtInstance> = instances
+            .iter()
+            .filter(|i| i.enabled)
+            .filter(|i| !due_only || self.instances.get(&i.key).is_none_or(|r| r.is_due(now)))
+            .collect();
+
+        for instance in launch_order(targets) {
+            let mut runtime = self
+                .instances
+                .remove(&instance.key)
+                .unwrap_or_else(|| InstanceRuntime::new(&instance.name));
+            self.check_instance(instance, &mut runtime, &mut full_scan, now, &mut events);
+            self.instances.insert(instance.key.clone(), runtime);
+        }
+
+        events
+    }
+
+    /// 起動したMT4/MT5の終了通知を待つ（最大 `timeout`）
+    ///
+    /// 起動処理が終了通知に対応していない場合は何も返さない。
+    pub fn wait_for_exit(&mut self, timeout: std::time::Duration) -> Option<ProcessExit> {
+        self.launcher.wait_exit(timeout)
+    }
+
+    /// 終了通知を受けたインスタンスをすぐに確認し、必要なら再起動する
+    ///
+    /// すでに別のPIDで動いている（再起動済みなど）場合の古い通知は無視する。
+    pub fn handle_exit(&mut self, exit: &ProcessExit, instances: &[MtInstance]) -> Vec<MonitorEvent> {
+        let mut events = Vec::new();
+        let Some(instance) = instances.iter().find(|i| i.enabled && i.key == exit.key) else {
+            return events;
+        };
+        let Some(mut runtime) = self.instances.remove(&exit.key) else {
+            return events;
+        };
+        if runtime.tracked_pid() != Some(exit.pid) {
+            self.instances.insert(exit.key.clone(), runtime);
+            return events;
+        }
+
+        let now = self.clock.now();
+        events.push(MonitorEvent {
+            key: instance.key.clone(),
+            name: instance.name.clone(),
+            at: now,
+            kind: EventKind::Exited {
+                pid: exit.pid,
+                code: exit.code,
+            },
+        });
+
+        runtime.state = if let InstanceState::Starting { .. } = runtime.state {
+            // 起動直後に終了した場合は起動失敗として数える
+            runtime.consecutive_failures += 1;
+            runtime.last_error = Some(t!("runtime.exited_immediately"));
+            if runtime.consecutive_failures > 1 {
+                InstanceState::Backoff {
+                    until: now + backoff_delay(runtime.consecutive_failures),
+                }
+            } else {
+                InstanceState::Unknown
+            }
+        } else if let InstanceState::Stopping { .. } = runtime.state {
+            // 終了させたものは、終了後の処理をそのまま続ける
+            runtime.state.clone()
+        } else {
+            InstanceState::Unknown
+        };
+
+        let mut full_scan = None;
+        self.check_instance(instance, &mut runtime, &mut full_scan, now, &mut events);
+        self.instances.insert(instance.key.clone(), runtime);
+        events
+    }
+
+    /// 手動で再起動する（動いていれば終了させてから、止まっていればすぐ起動する）
+    ///
+    /// 再起動回数には数えず、起動失敗の待機やクラッシュループも解除する。
+    pub fn restart(&mut self, key: &str, instances: &[MtInstance]) -> Vec<MonitorEvent> {
+        let mut events = Vec::new();
+        let Some(instance) = instances.iter().find(|i| i.enabled && i.key == key) else {
+            return events;
+        };
+        let mut runtime = self
+            .instances
+            .remove(key)
+            .unwrap_or_else(|| InstanceRuntime::new(&instance.name));
+        if let InstanceState::Stopping { .. } = runtime.state {
+            // すでに終了させている途中
+            self.instances.insert(key.to_string(), runtime);
+            return events;
+        }
+
+        let now = self.clock.now();
+        let tracked = runtime
+            .tracked_pid()
+            .and_then(|pid| self.processes.probe(pid))
+            .filter(|p| instance.find_process(std::slice::from_ref(p)).is_some())
+            .map(|p| p.pid);
+        let found = tracked.or_else(|| instance.find_process(&self.processes.refresh()).map(|p| p.pid));
+
+        let mut emit = |kind: EventKind| {
+            events.push(MonitorEvent {
+                key: instance.key.clone(),
+                name: instance.name.clone(),
+                at: now,
+                kind,
+            })
+        };
+        emit(EventKind::ManualRestart { pid: found });
+        runtime.consecutive_failures = 0;
+        runtime.last_error = None;
+        match found {
+            Some(pid) => {
+                self.begin_stop(instance, &mut runtime, pid, AfterStop::Restart { planned: true }, now, &mut emit)
+            }
+            None => self.relaunch(instance, &mut runtime, now, &mut emit),
+        }
+        runtime.next_check = Some(self.next_check_after(instance, &runtime, now));
+        self.instances.insert(key.to_string(), runtime);
+        events
+    }
+
+    fn check_instance(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        full_scan: &mut Option<Vec<RunningProcess>>,
+        now: DateTime<Utc>,
+        events: &mut Vec<MonitorEvent>,
+    ) {
+        let tracked = runtime
+            .tracked_pid()
+            .and_then(|pid| self.processes.probe(pid))
+            .filter(|p| instance.find_process(std::slice::from_ref(p)).is_some())
+            .map(|p| p.pid);
+        let found = tracked.or_else(|| {
+            let processes = full_scan.get_or_insert_with(|| self.processes.refresh());
+            instance.find_process(processes).map(|p| p.pid)
+        });
+        runtime.name = instance.name.clone();
+
+        let mut emit = |kind: EventKind| {
+            events.push(MonitorEvent {
+                key: instance.key.clone(),
+                name: instance.name.clone(),
+                at: now,
+                kind,
+            })
+        };
+
+        // 停止時間中も、動いているものは enforce のときだけ止める
+        let window = instance.schedule.closed_reason(now);
+        let in_window = window.is_some();
+        let closed = window.filter(|_| found.is_none() || instance.schedule.mode == ScheduleMode::Enforce);
+        // 停止時間中の定期再起動は行わない
+        let planned = take_planned_restart(instance, runtime, now).filter(|_| !in_window);
+
+        if let InstanceState::Stopping { .. } = runtime.state {
+            self.continue_stop(instance, runtime, found.is_some(), now, &mut emit);
+        } else if let Some(reason) = closed {
+            self.handle_closed(instance, runtime, found, reason, now, &mut emit);
+        } else if let (Some(pid), Some(at)) = (found, planned) {
+            emit(EventKind::PlannedRestart { pid, at });
+            self.begin_stop(instance, runtime, pid, AfterStop::Restart { planned: true }, now, &mut emit);
+        } else if let Some(pid) = found {
+            runtime.state = match runtime.state {
+                InstanceState::Running { pid: old, since } if old == pid => {
+                    InstanceState::Running { pid, since }
+                }
+                _ => InstanceState::Running { pid, since: now },
+            };
+            runtime.consecutive_failures = 0;
+            runtime.last_error = None;
+            if runtime.planned_restart_pending {
+                runtime.planned_restart_pending = false;
+                emit(EventKind::PlannedRestartCompleted { pid });
+            } else {
+                emit(EventKind::Running { pid });
+            }
+            self.watch_resources(instance, runtime, pid, now, &mut emit);
+        } else {
+            self.handle_stopped(instance, runtime, now, &mut emit);
+        }
+        runtime.next_check = Some(self.next_check_after(instance, runtime, now));
+    }
+
+    /// リソース使用量を測り、上限を超えた状態が続いていれば警告・再起動する
+    fn watch_resources(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        pid: u32,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        let Some(usage) = self.processes.usage(pid) else {
+            return;
+        };
+        runtime.last_usage = Some(usage);
+        if instance.limits.is_empty() {
+            return;
+        }
+
+        let breaches = instance.limits.breaches(&usage);
+        if breaches.is_empty() {
+            runtime.breach_since = None;
+            runtime.breach_reported = false;
+            return;
+        }
+        let since = *runtime.breach_since.get_or_insert(now);
+        if now - since < instance.limits.sustain || runtime.breach_reported {
+            return;
+        }
+
+        let restart = instance.limits.action == ThresholdAction::Restart;
+        emit(EventKind::ThresholdExceeded {
+            pid,
+            usage,
+            breaches,
+            restart,
+        });
+        if restart {
+            runtime.breach_since = None;
+            self.begin_stop(instance, runtime, pid, AfterStop::Restart { planned: false }, now, emit);
+        } else {
+            // 使用量が上限を下回るまで同じ警告は繰り返さない
+            runtime.breach_reported = true;
+        }
+    }
+
+    /// 終了を依頼して終了処理を始める（すぐ終了していれば続きも行う）
+    fn begin_stop(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        pid: u32,
+        then: AfterStop,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        let timeout = instance
+            .stop_timeout
+            .map(|secs| Duration::seconds(secs as i64))
+            .unwrap_or(self.policy.stop_timeout);
+        let stop = StopRequest::begin(&mut self.launcher, pid, timeout, now);
+        runtime.state = InstanceState::Stopping { stop, then };
+
+        let alive = self
+            .processes
+            .probe(pid)
+            .is_some_and(|p| instance.find_process(std::slice::from_ref(&p)).is_some());
+        self.continue_stop(instance, runtime, alive, now, emit);
+    }
+
+    /// 終了処理の続き（時間切れなら強制終了し、終了を確認できたら次の処理に進む）
+    fn continue_stop(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        alive: bool,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        let InstanceState::Stopping { mut stop, then } = runtime.state.clone() else {
+            return;
+        };
+        let Some(outcome) = stop.poll(&mut self.launcher, alive, now) else {
+            emit(EventKind::WaitingForStop { pid: stop.pid });
+            runtime.state = InstanceState::Stopping { stop, then };
+            return;
+        };
+        let pid = outcome.pid;
+        let result = outcome.result;
+        emit(EventKind::StopFinished { outcome });
+
+        if result == StopResult::StillRunning {
+            // 終了できなかったら、そのまま動かしておく
+            runtime.state = InstanceState::Running { pid, since: stop.started };
+            runtime.last_error = Some(t!("stop.failed", pid = pid));
+            return;
+        }
+        match then {
+            AfterStop::Suspend { reason } => {
+                runtime.state = InstanceState::Suspended;
+                // 止めている間の停止は失敗として数えない
+                runtime.consecutive_failures = 0;
+                emit(EventKind::StoppedForSchedule { pid, reason });
+            }
+            AfterStop::Restart { planned: true } => self.relaunch(instance, runtime, now, emit),
+            // 上限超過などでの再起動は、停止したときと同じく再起動回数の上限・起動の間隔・依存先を確かめる
+            AfterStop::Restart { planned: false } => self.launch_if_allowed(instance, runtime, now, emit),
+        }
+    }
+
+    /// 定期再起動・手動の再起動で終了させた後に起動し直す（再起動回数には数えない）
+    fn relaunch(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        match self.launcher.launch(instance) {
+            Ok(pid) => {
+                self.last_launch = Some(now);
+                runtime.planned_restart_pending = true;
+                runtime.state = InstanceState::Starting { pid, since: now };
+                emit(EventKind::Launched { pid });
+            }
+            Err(e) => {
+                runtime.last_error = Some(e.to_string());
+                emit(EventKind::LaunchFailed {
+                    error: e.to_string(),
+                });
+                self.enter_backoff(runtime, now, emit);
+            }
+        }
+    }
+
+    /// メンテナンス時間などの間の処理
+    fn handle_closed(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        found: Option<u32>,
+        reason: String,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        let Some(pid) = found else {
+            runtime.state = InstanceState::Suspended;
+            // 止めている間の停止は失敗として数えない
+            runtime.consecutive_failures = 0;
+            emit(EventKind::Suspended { reason });
+            return;
+        };
+        self.begin_stop(instance, runtime, pid, AfterStop::Suspend { reason }, now, emit);
+    }
+
+    /// 次に確認する時刻（待機や起動待ちが先に終わる場合はその時刻）
+    fn next_check_after(
+        &self,
+        instance: &MtInstance,
+        runtime: &InstanceRuntime,
+        now: DateTime<Utc>,
+    ) -> DateTime<Utc> {
+        let interval = instance
+            .interval
+            .map(|secs| Duration::seconds(secs as i64))
+            .unwrap_or(self.policy.check_interval);
+        let next = now + interval;
+        match &runtime.state {
+            InstanceState::Backoff { until } => next.min(*until),
+            InstanceState::Starting { since, .. } => next.min(*since + self.policy.start_timeout),
+            InstanceState::Pending { until: Some(until) } => next.min(*until),
+            InstanceState::Stopping { stop, .. } => next
+                .min(stop.deadline())
+                .min(now + Duration::seconds(STOP_POLL_SECS)),
+            _ => next,
+        }
+    }
+
+    fn handle_stopped(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        match runtime.state {
+            InstanceState::Starting { pid, since } => {
+                if now - since < self.policy.start_timeout {
+                    emit(EventKind::WaitingForStart { pid });
+                    return;
+                }
+                emit(EventKind::StartTimedOut { pid });
+                runtime.planned_restart_pending = false;
+                runtime.last_error = Some(t!("runtime.start_timed_out"));
+                if self.enter_backoff(runtime, now, emit) {
+                    return;
+                }
+            }
+            InstanceState::Backoff { until } if now < until => {
+                emit(EventKind::BackingOff { until });
+                return;
+            }
+            _ => emit(EventKind::Stopped),
+        }
+        self.launch_if_allowed(instance, runtime, now, emit);
+    }
+
+    /// 再起動回数の上限・依存先・起動の間隔を確かめて、起動できるなら起動する
+    fn launch_if_allowed(
+        &mut self,
+        instance: &MtInstance,
+        runtime: &mut InstanceRuntime,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) {
+        // 直近1時間の再起動回数を確認
+        let window_start = now - Duration::hours(1);
+        runtime.restarts.retain(|t| *t > window_start);
+        let limit = self.policy.max_restarts_per_hour as usize;
+        if limit > 0 && runtime.restarts.len() >= limit {
+            runtime.state = InstanceState::CrashLoop;
+            emit(EventKind::CrashLoop {
+                restarts: runtime.restarts.len(),
+            });
+            return;
+        }
+
+        // 依存先が実行中になってから起動する
+        let waiting_for = instance
+            .depends_on
+            .iter()
+            .filter(|dep| **dep != instance.key)
+            .find(|dep| {
+                self.instances
+                    .get(dep.as_str())
+                    .is_some_and(|r| !matches!(r.state, InstanceState::Running { .. }))
+            });
+        if let Some(dep) = waiting_for {
+            runtime.state = InstanceState::Pending { until: None };
+            emit(EventKind::WaitingForDependency { key: dep.clone() });
+            return;
+        }
+
+        // 直前の起動から LaunchStagger が経つまで待つ
+        if let Some(last) = self.last_launch {
+            let until = last + self.policy.launch_stagger;
+            if now < until {
+                runtime.state = InstanceState::Pending { until: Some(until) };
+                emit(EventKind::LaunchDelayed { until });
+                return;
+            }
+        }
+
+        match self.launcher.launch(instance) {
+            Ok(pid) => {
+                self.last_launch = Some(now);
+                runtime.restarts.push(now);
+                runtime.state = InstanceState::Starting { pid, since: now };
+                emit(EventKind::Launched { pid });
+            }
+            Err(e) => {
+                runtime.last_error = Some(e.to_string());
+                emit(EventKind::LaunchFailed {
+                    error: e.to_string(),
+                });
+                self.enter_backoff(runtime, now, emit);
+            }
+        }
+    }
+
+    /// 失敗回数を数えて待機に入る。待機時間が残っていれば true
+    fn enter_backoff(
+        &self,
+        runtime: &mut InstanceRuntime,
+        now: DateTime<Utc>,
+        emit: &mut impl FnMut(EventKind),
+    ) -> bool {
+        runtime.consecutive_failures += 1;
+        let until = now + backoff_delay(runtime.consecutive_failures);
+        runtime.state = InstanceState::Backoff { until };
+        if runtime.consecutive_failures > 1 {
+            emit(EventKind::BackingOff { until });
+            true
+        } else {
+            // 最初の失敗はすぐ再試行する（従来の動作）
+            false
+        }
+    }
+
+    /// 状態ファイルに書き出す形式の一覧
+    pub fn statuses(&self) -> Vec<InstanceStatus> {
+        self.instances
+            .iter()
+            .map(|(key, runtime)| {
+                let (running, pid, launched, since) = match runtime.state {
+                    InstanceState::Running { pid, since } => (true, Some(pid), false, Some(since)),
+                    InstanceState::Stopping { ref stop, .. } => (true, Some(stop.pid), false, None),
+                    InstanceState::Starting { pid, since } => (false, Some(pid), true, Some(since)),
+                    _ => (false, None, false, None),
+                };
+                InstanceStatus {
+                    key: key.clone(),
+                    name: runtime.name.clone(),
+                    running,
+                    pid,
+                    launched,
+                    error: runtime.last_error.clone(),
+                    since,
+                    restarts: runtime.restarts.clone(),
+                    consecutive_failures: runtime.consecutive_failures,
+                    backoff_until: match runtime.state {
+                        InstanceState::Backoff { until } => Some(until),
+                        _ => None,
+                    },
+                    crash_loop: runtime.state == InstanceState::CrashLoop,
+                    last_planned_restart: runtime.last_planned_restart,
+                }
+            })
+            .collect()
+    }
+
+    /// 状態ファイルから読んだ監視情報を引き継ぐ（MetaGuard自体の再起動時）
+    ///
+    /// 引き継いだPIDは次のチェックで実行ファイルのパスと照合するため、
+    /// 別のプロセスがPIDを再利用していても取り違えない。
+    pub fn restore(&mut self, statuses: &[InstanceStatus]) {
+        let now = self.clock.now();
+        for status in statuses {
+            let mut runtime = InstanceRuntime::new(&status.name);
+            runtime.state = match (status.pid, status.since) {
+                (Some(pid), since) if status.running => InstanceState::Running {
+                    pid,
+                    since: since.unwrap_or(now),
+                },
+                (Some(pid), since) if status.launched => InstanceState::Starting {
+                    pid,
+                    since: since.unwrap_or(now),
+                },
+                _ if status.crash_loop => InstanceState::CrashLoop,
+                _ => match status.backoff_until {
+                    Some(until) => InstanceState::Backoff { until },
+                    None => InstanceState::Unknown,
+                },
+            };
+            runtime.restarts = status.restarts.clone();
+            runtime.consecutive_failures = status.consecutive_failures;
+            runtime.last_error = status.error.clone();
+            runtime.last_planned_restart = status.last_planned_restart;
+            self.instances.insert(status.key.clone(), runtime);
+        }
+    }
+}
+
+/// 前回から定期再起動の時刻を過ぎていれば、その時刻を返す
+///
+/// 監視を始めて最初の確認では、それより前の予定は実行しない
+/// （状態ファイルから引き継いだ場合は、引き継いだ時刻より後の予定を実行する）。
+fn take_planned_restart(
+    instance: &MtInstance,
+    runtime: &mut InstanceRuntime,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let done = *runtime.last_planned_restart.get_or_insert(now);
+    let latest = instance.schedule.last_restart_time(now)?;
+    if latest <= done {
+        return None;
+    }
+    runtime.last_planned_restart = Some(latest);
+    Some(latest)
+}
+
+/// 確認する順番を決める
+///
+/// 優先度の大きいものから（同じ優先度なら設定の順）並べ、依存先があれば
+/// 依存先を先にする。循環している場合は優先度の順に戻す。
+fn launch_order(mut rest: Vec<&MtInstance>) -> Vec<&MtInstance> {
+    rest.sort_by_key(|i| std::cmp::Reverse(i.priority));
+    let mut ordered = Vec::with_capacity(rest.len());
+    while !rest.is_empty() {
+        let next = rest
+            .iter()
+            .position(|i| {
+                i.depends_on
+                    .iter()
+                    .all(|dep| *dep == i.key || !rest.iter().any(|r| r.key == *dep))
+            })
+            .unwrap_or(0);
+        ordered.push(rest.remove(next));
+    }
+    ordered
+}
+
+/// 連続失敗回数に応じた待機時間
+pub fn backoff_delay(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    let secs = BACKOFF_BASE_SECS.saturating_mul(1 << exponent);
+    Duration::seconds(secs.min(BACKOFF_MAX_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_delay(1), Duration::seconds(10));
+        assert_eq!(backoff_delay(2), Duration::seconds(20));
+        assert_eq!(backoff_delay(3), Duration::seconds(40));
+        assert_eq!(backoff_delay(10), Duration::seconds(300));
+        assert_eq!(backoff_delay(100), Duration::seconds(300));
+    }
+
+    #[test]
+    fn policy_is_read_from_config() {
+        let mut config = HashMap::new();
+        config.insert("CheckInterval".to_string(), "10".to_string());
+        config.insert("StartTimeout".to_string(), "90".to_string());
+        config.insert("MaxRestartsPerHour".to_string(), "5".to_string());
+        let policy = RestartPolicy::from_config(&config);
+        assert_eq!(policy.check_interval, Duration::seconds(10));
+        assert_eq!(policy.start_timeout, Duration::seconds(90));
+        assert_eq!(policy.max_restarts_per_hour, 5);
+
+        assert_eq!(RestartPolicy::from_config(&HashMap::new()), RestartPolicy::default());
+    }
+}
@@ -0,0 +1,162 @@
+//! 警告の通知
+//!
+//! リソースの上限超過やクラッシュループなど、放っておけない出来事が起きたときに
+//! `AlertCommand` に指定したコマンドを実行する（メール送信やチャットへの投稿など）。
+//! コマンドには次の環境変数で内容を渡す。
+//!
+//! - `MG_KEY` … インスタンスのキー（例: `MT_1`）
+//! - `MG_NAME` … 表示名
+//! - `MG_EVENT` … 履歴と同じ種類（例: `threshold`）
+//! - `MG_MESSAGE` … 詳細
+//! - `MG_TEXT` … 画面と同じ文（`Language` の言語）
+//! - `MG_GROUP` … インスタンスのグループ（`MT_番号.Group`。なければ空）
+//!
+//! `AlertCommand.グループ名` を書くと、そのグループのインスタンスの警告はそちらで通知する
+//! （顧客ごとに通知先を分けるなど）。
+//!
+//! ```ini
+//! AlertCommand=/home/fx/bin/notify-telegram.sh
+//! AlertCommand.tanaka=/home/fx/bin/notify-mail.sh tanaka@example.com
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::history;
+use crate::instance::{is_instance_key, option_key};
+use crate::monitor::{EventKind, MonitorEvent};
+use crate::stop::StopResult;
+
+/// 警告として通知するイベントかどうか
+pub fn is_alert(event: &MonitorEvent) -> bool {
+    match &event.kind {
+        EventKind::ThresholdExceeded { .. } | EventKind::CrashLoop { .. } => true,
+        EventKind::StopFinished { outcome } => outcome.result == StopResult::StillRunning,
+        _ => false,
+    }
+}
+
+/// 通知コマンドを書く設定のキー
+const ALERT_COMMAND_KEY: &str = "AlertCommand";
+
+/// `AlertCommand` を実行して通知する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandNotifier {
+    command: Option<String>,
+    /// グループごとの通知コマンド（キーは小文字のグループ名）
+    routes: HashMap<String, String>,
+    /// インスタンスのキーとグループ
+    groups: HashMap<String, String>,
+}
+
+impl CommandNotifier {
+    /// `AlertCommand` か `AlertCommand.グループ名` が設定されていれば作る
+    pub fn from_config(config: &HashMap<String, String>) -> Option<Self> {
+        let non_empty = |value: &String| Some(value.trim()).filter(|s| !s.is_empty()).map(str::to_string);
+        let command = config.get(ALERT_COMMAND_KEY).and_then(non_empty);
+        let routes: HashMap<String, String> = config
+            .iter()
+            .filter_map(|(key, value)| {
+                let group = key.strip_prefix(ALERT_COMMAND_KEY)?.strip_prefix('.')?;
+                Some((group.trim().to_lowercase(), non_empty(value)?))
+            })
+            .collect();
+        if command.is_none() && routes.is_empty() {
+            return None;
+        }
+        let groups = config
+            .keys()
+            .filter(|key| is_instance_key(key))
+            .filter_map(|key| {
+                let group = config.get(&option_key(key, "Group")).and_then(non_empty)?;
+                Some((key.clone(), group))
+            })
+            .collect();
+        Some(CommandNotifier { command, routes, groups })
+    }
+
+    /// インスタンスの警告に使うコマンド（グループの通知先がなければ `AlertCommand`）
+    pub fn command_for(&self, key: &str) -> Option<&str> {
+        self.groups
+            .get(key)
+            .and_then(|group| self.routes.get(&group.to_lowercase()))
+            .or(self.command.as_ref())
+            .map(|s| s.as_str())
+    }
+
+    /// 警告にあたるイベントを通知する（コマンドの終了は待たない）
+    pub fn notify(&self, event: &MonitorEvent) -> io::Result<()> {
+        if !is_alert(event) {
+            return Ok(());
+        }
+        let Some(line) = self.command_for(&event.key) else {
+            return Ok(());
+        };
+        let (kind, detail) = history::describe(event).unwrap_or(("alert", String::new()));
+
+        let mut command = shell_command(line);
+        command
+            .env("MG_KEY", &event.key)
+            .env("MG_NAME", &event.name)
+            .env("MG_EVENT", kind)
+            .env("MG_MESSAGE", detail)
+            .env("MG_TEXT", event.message())
+            .env("MG_GROUP", self.groups.get(&event.key).map(|s| s.as_str()).unwrap_or(""))
+            .stdin(Stdio::null());
+        let mut child = command.spawn()?;
+        // 終了したプロセスを回収する
+        thread::spawn(move || {
+            let _ = child.wait();
+        });
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(line);
+    command
+}
+
+#[cfg(not(windows))]
+fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alerts_are_routed_by_group() {
+        let config: HashMap<String, String> = [
+            ("AlertCommand", "notify-all"),
+            ("AlertCommand.Tanaka", "notify-tanaka"),
+            ("AlertCommand.sato", ""),
+            ("MT_1.Group", "tanaka"),
+            ("MT_2.Group", "sato"),
+            ("MT_1", "1|A|a.exe"),
+            ("MT_2", "1|B|b.exe"),
+            ("MT_3", "1|C|c.exe"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let notifier = CommandNotifier::from_config(&config).unwrap();
+        assert_eq!(notifier.command_for("MT_1"), Some("notify-tanaka"));
+        assert_eq!(notifier.command_for("MT_2"), Some("notify-all"));
+        assert_eq!(notifier.command_for("MT_3"), Some("notify-all"));
+
+        // グループの通知先だけでも作れる
+        let mut config = config;
+        config.remove("AlertCommand");
+        let notifier = CommandNotifier::from_config(&config).unwrap();
+        assert_eq!(notifier.command_for("MT_1"), Some("notify-tanaka"));
+        assert_eq!(notifier.command_for("MT_3"), None);
+    }
+}
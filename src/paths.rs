@@ -0,0 +1,258 @@
+//! MT4/MT5の実行ファイルパスの正規化
+//!
+//! 設定ファイルに書かれたパスと実行中プロセスのパスを比較するときは、
+//! 必ずこのモジュールの `path_key` / `same_path` を経由する。
+//! スラッシュの向き、重複したバックスラッシュ、前後の空白や引用符、
+//! 大文字小文字（Windows形式のパスのみ）、8.3形式の短い名前、シンボリックリンクの違いを吸収する。
+
+use std::path::PathBuf;
+
+/// パスを比較用のキーに変換する
+///
+/// 字句的に整えたうえで、実在するパスであればファイルシステム上の
+/// 実体（シンボリックリンク解決済み・長い名前）に置き換える。
+/// 大文字小文字を区別しないパス（Windows、Wine の `C:\` などのドライブ形式）は小文字化する。
+pub fn path_key(raw: &str) -> String {
+    let lexical = clean(raw);
+    if lexical.is_empty() {
+        return lexical;
+    }
+
+    let resolved = resolve_on_disk(&lexical)
+        .map(|p| clean(&p.to_string_lossy()))
+        .unwrap_or(lexical);
+
+    if cfg!(windows) || is_windows_form(&resolved) {
+        resolved.to_lowercase()
+    } else {
+        resolved
+    }
+}
+
+/// ドライブ名（`C:\`）や UNC（`\\server`）で始まる Windows 形式のパスか
+fn is_windows_form(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':') || path.starts_with(r"\\")
+}
+
+/// 2つのパスが同じ実行ファイルを指しているかどうか
+pub fn same_path(a: &str, b: &str) -> bool {
+    let a = path_key(a);
+    !a.is_empty() && a == path_key(b)
+}
+
+/// ファイルシステムにアクセスせずにパスを整える
+///
+/// 区切り文字は `\` に統一し、連続した区切り、`.` / `..`、
+/// 各要素末尾の空白とピリオド、`\\?\` 形式の接頭辞を取り除く。
+pub fn clean(raw: &str) -> String {
+    let mut s = raw.trim();
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        s = s[1..s.len() - 1].trim();
+    }
+
+    let s = s.replace('/', "\\");
+    let mut rest = s.as_str();
+
+    // \\?\UNC\server\share と \\?\C:\ は通常の形式に戻す
+    let prefix = if let Some(r) = strip_prefix_ignore_case(rest, r"\\?\UNC\") {
+        rest = r;
+        r"\\"
+    } else if let Some(r) = rest.strip_prefix(r"\\?\") {
+        rest = r;
+        ""
+    } else if rest.starts_with(r"\\") {
+        r"\\"
+    } else if rest.starts_with('\\') {
+        "\\"
+    } else {
+        ""
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                // ドライブやルートより上には戻らない
+                if parts.last().map(|p| !p.ends_with(':')).unwrap_or(false) {
+                    parts.pop();
+                }
+            }
+            _ => {
+                let trimmed = part.trim_end_matches([' ', '.']);
+                if !trimmed.is_empty() {
+                    parts.push(trimmed);
+                }
+            }
+        }
+    }
+
+    let mut out = String::from(prefix);
+    out.push_str(&parts.join("\\"));
+    if parts.len() == 1 && parts[0].ends_with(':') {
+        out.push('\\');
+    }
+    out
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// 実在するパスであれば実体のパスを返す
+fn resolve_on_disk(lexical: &str) -> Option<PathBuf> {
+    let native = if cfg!(windows) {
+        lexical.to_string()
+    } else {
+        lexical.replace('\\', "/")
+    };
+    std::fs::canonicalize(native).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slashes_are_unified() {
+        assert_eq!(
+            clean("C:/Program Files/XM MT4/terminal.exe"),
+            r"C:\Program Files\XM MT4\terminal.exe"
+        );
+        assert!(same_path(
+            "C:/Program Files/XM MT4/terminal.exe",
+            r"C:\Program Files\XM MT4\terminal.exe"
+        ));
+    }
+
+    #[test]
+    fn doubled_backslashes_are_collapsed() {
+        assert_eq!(
+            clean(r"C:\\Program Files\\XM MT4\\terminal.exe"),
+            r"C:\Program Files\XM MT4\terminal.exe"
+        );
+    }
+
+    #[test]
+    fn surrounding_spaces_and_quotes_are_ignored() {
+        assert_eq!(
+            clean("  \"C:\\MT4\\terminal.exe\"  "),
+            r"C:\MT4\terminal.exe"
+        );
+        assert_eq!(clean("C:\\MT4 \\terminal.exe   "), r"C:\MT4\terminal.exe");
+        assert_eq!(clean("C:\\MT4\\terminal.exe."), r"C:\MT4\terminal.exe");
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert!(same_path(
+            r"c:\program files\mt4\TERMINAL.EXE",
+            r"C:\Program Files\MT4\terminal.exe"
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unix_paths_are_case_sensitive() {
+        assert!(!same_path("/srv/mt/XM/terminal.exe", "/srv/mt/xm/terminal.exe"));
+        // Wine のドライブ形式は Windows と同じく区別しない
+        assert!(same_path(r"Z:\srv\mt\XM\terminal.exe", r"z:\srv\mt\xm\TERMINAL.EXE"));
+    }
+
+    #[test]
+    fn dot_segments_are_resolved() {
+        assert_eq!(
+            clean(r"C:\MT4\.\config\..\terminal.exe"),
+            r"C:\MT4\terminal.exe"
+        );
+        assert_eq!(clean(r"C:\..\terminal.exe"), r"C:\terminal.exe");
+        assert_eq!(clean("C:/"), r"C:\");
+    }
+
+    #[test]
+    fn verbatim_prefix_is_removed() {
+        assert_eq!(clean(r"\\?\C:\MT4\terminal.exe"), r"C:\MT4\terminal.exe");
+        assert_eq!(
+            clean(r"\\?\UNC\server\share\terminal.exe"),
+            r"\\server\share\terminal.exe"
+        );
+        assert!(same_path(r"\\?\C:\MT4\terminal.exe", r"C:\MT4\terminal.exe"));
+    }
+
+    #[test]
+    fn unc_paths_keep_their_prefix() {
+        assert_eq!(
+            clean(r"\\\\server\\share\\MT4\\terminal.exe"),
+            r"\\server\share\MT4\terminal.exe"
+        );
+    }
+
+    #[test]
+    fn empty_paths_never_match() {
+        assert!(!same_path("", ""));
+        assert!(!same_path("   ", r"C:\MT4\terminal.exe"));
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "metaguard-paths-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_folders_match_their_target() {
+        let dir = scratch_dir("symlink");
+        let real = dir.join("real");
+        std::fs::create_dir_all(&real).unwrap();
+        std::fs::write(real.join("terminal.exe"), b"").unwrap();
+        std::os::unix::fs::symlink(&real, dir.join("link")).unwrap();
+
+        let via_link = dir.join("link").join("terminal.exe");
+        let direct = real.join("terminal.exe");
+        assert!(same_path(
+            &via_link.to_string_lossy(),
+            &direct.to_string_lossy()
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn short_names_match_long_names() {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+        use winapi::um::fileapi::GetShortPathNameW;
+
+        let dir = scratch_dir("short").join("MetaGuard Long Directory Name");
+        std::fs::create_dir_all(&dir).unwrap();
+        let long = dir.join("terminal.exe");
+        std::fs::write(&long, b"").unwrap();
+
+        let wide: Vec<u16> = long.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut buf = vec![0u16; 1024];
+        let len = unsafe { GetShortPathNameW(wide.as_ptr(), buf.as_mut_ptr(), buf.len() as u32) };
+        assert!(len > 0);
+        let short = std::ffi::OsString::from_wide(&buf[..len as usize]);
+
+        assert!(same_path(
+            &short.to_string_lossy(),
+            &long.to_string_lossy()
+        ));
+
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+}
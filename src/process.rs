@@ -0,0 +1,264 @@
+//! 実行中プロセスの一覧
+//!
+//! 監視エンジンは `ProcessTable` トレイト経由で一覧を取得するため、
+//! テストでは `FakeProcessTable` に差し替えられる。
+//!
+//! 実際の一覧 `SysinfoProcessTable` は監視中ずっと同じ `System` を使い、
+//! プロセス情報だけを更新する。前回見つけたPIDは `probe` で個別に確認し、
+//! 全プロセスの走査はそのPIDが消えたときなどに限って行う。
+//!
+//! リソースの上限が設定されたインスタンスは `usage` でメモリやCPUの使用量も測る。
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use sysinfo::{Pid, PidExt, Process, ProcessExt, ProcessRefreshKind, System, SystemExt};
+
+use crate::resources::{self, ResourceUsage};
+use crate::{paths, wine};
+
+/// 判定に必要な情報だけを正規化して持つプロセス情報
+#[derive(Debug, Clone, Default)]
+pub struct RunningProcess {
+    pub pid: u32,
+    /// 実行ファイルのパス（`paths::path_key` 済み）
+    pub exe_key: String,
+    /// argv[0] が .exe の場合のパス（Wine上のプロセス用、`paths::path_key` 済み）
+    pub cmd_key: Option<String>,
+    /// 環境変数が読めた場合のWINEPREFIX（未設定なら既定の ~/.wine）
+    pub wine_prefix: Option<String>,
+}
+
+/// プロセス一覧の取得元
+pub trait ProcessTable {
+    /// 全プロセスを走査して最新の一覧を返す
+    fn refresh(&mut self) -> Vec<RunningProcess>;
+
+    /// 指定したPIDのプロセスだけを確認する（終了していれば `None`）
+    fn probe(&mut self, pid: u32) -> Option<RunningProcess>;
+
+    /// 指定したPIDのリソース使用量を測る（終了していれば `None`）
+    fn usage(&mut self, pid: u32) -> Option<ResourceUsage>;
+}
+
+/// sysinfo から取得する実際のプロセス一覧
+pub struct SysinfoProcessTable {
+    system: System,
+    /// 正規化済みの情報（PIDと開始時刻が同じ間は再計算しない）
+    cache: HashMap<u32, (u64, RunningProcess)>,
+}
+
+impl SysinfoProcessTable {
+    pub fn new() -> Self {
+        SysinfoProcessTable {
+            // ディスクやネットワークなどは読み込まない
+            system: System::new(),
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl Default for SysinfoProcessTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// キャッシュを使ってプロセス情報を正規化する
+fn describe_cached(cache: &mut HashMap<u32, (u64, RunningProcess)>, process: &Process) -> RunningProcess {
+    let pid = process.pid().as_u32();
+    let start = process.start_time();
+    if let Some((cached_start, cached)) = cache.get(&pid) {
+        if *cached_start == start {
+            return cached.clone();
+        }
+    }
+    let info = describe(process);
+    cache.insert(pid, (start, info.clone()));
+    info
+}
+
+impl ProcessTable for SysinfoProcessTable {
+    fn refresh(&mut self) -> Vec<RunningProcess> {
+        self.system.refresh_processes_specifics(ProcessRefreshKind::new());
+
+        let cache = &mut self.cache;
+        let list: Vec<RunningProcess> = self
+            .system
+            .processes()
+            .values()
+            .map(|process| describe_cached(cache, process))
+            .collect();
+
+        // 終了したプロセスの情報は捨てる
+        let alive: std::collections::HashSet<u32> = list.iter().map(|p| p.pid).collect();
+        self.cache.retain(|pid, _| alive.contains(pid));
+
+        list
+    }
+
+    fn probe(&mut self, pid: u32) -> Option<RunningProcess> {
+        let sys_pid = Pid::from_u32(pid);
+        if !self
+            .system
+            .refresh_process_specifics(sys_pid, ProcessRefreshKind::new())
+        {
+            self.cache.remove(&pid);
+            return None;
+        }
+
+        let cache = &mut self.cache;
+        self.system
+            .process(sys_pid)
+            .map(|process| describe_cached(cache, process))
+    }
+
+    fn usage(&mut self, pid: u32) -> Option<ResourceUsage> {
+        let sys_pid = Pid::from_u32(pid);
+        if !self
+            .system
+            .refresh_process_specifics(sys_pid, ProcessRefreshKind::new().with_cpu())
+        {
+            return None;
+        }
+        let process = self.system.process(sys_pid)?;
+        // sysinfo のCPU使用率は1コアあたりの値なので、全コアに対する割合に直す
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as f32;
+        let (threads, handles) = resources::thread_and_handle_counts(pid);
+        Some(ResourceUsage {
+            memory_mb: process.memory() / 1024 / 1024,
+            cpu_percent: process.cpu_usage() / cores,
+            threads,
+            handles,
+        })
+    }
+}
+
+/// テスト用のプロセス一覧（複製しても同じ一覧を共有する）
+#[derive(Debug, Clone, Default)]
+pub struct FakeProcessTable {
+    processes: Arc<Mutex<Vec<RunningProcess>>>,
+    next_pid: Arc<Mutex<u32>>,
+    scans: Arc<Mutex<usize>>,
+    usage: Arc<Mutex<HashMap<u32, ResourceUsage>>>,
+}
+
+impl FakeProcessTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 指定したパスの実行ファイルが動いている状態にする
+    ///
+    /// Wine経由の起動とみなせるよう、コマンドラインにも同じパスを設定する。
+    pub fn start(&self, pid: u32, path: &str) {
+        let key = paths::path_key(path);
+        self.processes.lock().unwrap().push(RunningProcess {
+            pid,
+            exe_key: key.clone(),
+            cmd_key: Some(key),
+            wine_prefix: None,
+        });
+    }
+
+    /// 指定したPIDのプロセスを終了させる
+    pub fn kill(&self, pid: u32) {
+        self.processes.lock().unwrap().retain(|p| p.pid != pid);
+    }
+
+    /// 指定したパスのプロセスをすべて終了させる
+    pub fn kill_path(&self, path: &str) {
+        let key = paths::path_key(path);
+        self.processes.lock().unwrap().retain(|p| p.exe_key != key);
+    }
+
+    pub fn pids(&self) -> Vec<u32> {
+        self.processes.lock().unwrap().iter().map(|p| p.pid).collect()
+    }
+
+    /// 指定したPIDのリソース使用量を設定する
+    pub fn set_usage(&self, pid: u32, usage: ResourceUsage) {
+        self.usage.lock().unwrap().insert(pid, usage);
+    }
+
+    /// これまでに全プロセスを走査した回数
+    pub fn scans(&self) -> usize {
+        *self.scans.lock().unwrap()
+    }
+
+    /// まだ使われていないPIDを払い出す
+    pub fn next_pid(&self) -> u32 {
+        let mut next = self.next_pid.lock().unwrap();
+        *next += 1;
+        1000 + *next
+    }
+}
+
+impl ProcessTable for FakeProcessTable {
+    fn refresh(&mut self) -> Vec<RunningProcess> {
+        *self.scans.lock().unwrap() += 1;
+        self.processes.lock().unwrap().clone()
+    }
+
+    fn probe(&mut self, pid: u32) -> Option<RunningProcess> {
+        self.processes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.pid == pid)
+            .cloned()
+    }
+
+    fn usage(&mut self, pid: u32) -> Option<ResourceUsage> {
+        self.probe(pid)?;
+        Some(self.usage.lock().unwrap().get(&pid).copied().unwrap_or_default())
+    }
+}
+
+/// sysinfo のプロセス情報を判定用に正規化する
+fn describe(process: &Process) -> RunningProcess {
+    let cmd_key = process
+        .cmd()
+        .first()
+        .filter(|arg0| arg0.to_lowercase().ends_with(".exe"))
+        .map(|arg0| paths::path_key(arg0));
+
+    let environ = process.environ();
+    let wine_prefix = if environ.is_empty() {
+        None
+    } else {
+        wine::prefix_from_environ(environ)
+            .or_else(|| wine::default_prefix().map(|p| p.to_string_lossy().to_string()))
+    };
+
+    RunningProcess {
+        pid: process.pid().as_u32(),
+        exe_key: exe_key(process.exe()),
+        cmd_key,
+        wine_prefix,
+    }
+}
+
+fn exe_key(exe: &Path) -> String {
+    paths::path_key(&exe.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn probe_follows_a_real_process() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        let mut table = SysinfoProcessTable::new();
+
+        assert!(table.refresh().iter().any(|p| p.pid == pid));
+        assert_eq!(table.probe(pid).map(|p| p.pid), Some(pid));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(table.probe(pid).is_none());
+    }
+}
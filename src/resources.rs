@@ -0,0 +1,256 @@
+//! リソース使用量の監視
+//!
+//! 長時間動かしたMT4/MT5はメモリが増え続けたり、CPUを使い続けたりすることがある。
+//! インスタンスごとに上限を指定すると、チェックのたびに使用量を測り、
+//! 上限を超えた状態が `ThresholdMinutes` 分続いたら警告する
+//! （`ThresholdAction=restart` なら再起動する）。
+//!
+//! ```ini
+//! MT_1.MaxMemoryMB=1500
+//! MT_1.MaxCpuPercent=80
+//! MT_1.MaxThreads=200
+//! MT_1.MaxHandles=5000
+//! MT_1.ThresholdMinutes=5
+//! MT_1.ThresholdAction=restart
+//! ```
+//!
+//! CPU使用率はPC全体（全コア）に対する割合。スレッド数とハンドル数は
+//! Windowsでは Win32 API、Linux（Wine）では `/proc` から読む
+//! （Linuxではハンドル数の代わりに開いているファイル数を使う）。
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::Duration;
+
+use crate::instance::option_key;
+
+/// 上限を超えたまま続いたら対処するまでの時間の既定値（分）
+const DEFAULT_SUSTAIN_MINUTES: i64 = 5;
+
+/// 1回の測定結果
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ResourceUsage {
+    pub memory_mb: u64,
+    /// PC全体に対するCPU使用率（%）
+    pub cpu_percent: f32,
+    pub threads: Option<u32>,
+    pub handles: Option<u32>,
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: Option<u32>| v.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+        write!(
+            f,
+            "memory={}MB cpu={:.1}% threads={} handles={}",
+            self.memory_mb,
+            self.cpu_percent,
+            show(self.threads),
+            show(self.handles)
+        )
+    }
+}
+
+/// 上限を超えたときの対処
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThresholdAction {
+    /// 警告だけ行う
+    #[default]
+    Alert,
+    /// 警告して再起動する
+    Restart,
+}
+
+impl ThresholdAction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "alert" => Some(ThresholdAction::Alert),
+            "restart" => Some(ThresholdAction::Restart),
+            _ => None,
+        }
+    }
+}
+
+/// インスタンスごとのリソースの上限
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimits {
+    pub max_memory_mb: Option<u64>,
+    pub max_cpu_percent: Option<f32>,
+    pub max_threads: Option<u32>,
+    pub max_handles: Option<u32>,
+    /// 上限を超えた状態がこの時間続いたら対処する
+    pub sustain: Duration,
+    pub action: ThresholdAction,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_memory_mb: None,
+            max_cpu_percent: None,
+            max_threads: None,
+            max_handles: None,
+            sustain: Duration::minutes(DEFAULT_SUSTAIN_MINUTES),
+            action: ThresholdAction::Alert,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// `MT_n.MaxMemoryMB` などから読む
+    pub fn for_instance(config: &HashMap<String, String>, key: &str) -> Self {
+        let opt = |name: &str| config.get(&option_key(key, name)).map(|s| s.trim());
+        let default = ResourceLimits::default();
+        ResourceLimits {
+            max_memory_mb: opt("MaxMemoryMB").and_then(|s| s.parse().ok()).filter(|v| *v > 0),
+            max_cpu_percent: opt("MaxCpuPercent").and_then(|s| s.parse().ok()).filter(|v| *v > 0.0),
+            max_threads: opt("MaxThreads").and_then(|s| s.parse().ok()).filter(|v| *v > 0),
+            max_handles: opt("MaxHandles").and_then(|s| s.parse().ok()).filter(|v| *v > 0),
+            sustain: opt("ThresholdMinutes")
+                .and_then(|s| s.parse::<i64>().ok())
+                .filter(|v| *v >= 0)
+                .map(Duration::minutes)
+                .unwrap_or(default.sustain),
+            action: opt("ThresholdAction")
+                .and_then(ThresholdAction::parse)
+                .unwrap_or(default.action),
+        }
+    }
+
+    /// 上限が1つも指定されていないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.max_memory_mb.is_none()
+            && self.max_cpu_percent.is_none()
+            && self.max_threads.is_none()
+            && self.max_handles.is_none()
+    }
+
+    /// 上限を超えている項目（例: `memory 1834MB > 1500MB`）
+    pub fn breaches(&self, usage: &ResourceUsage) -> Vec<String> {
+        let mut breaches = Vec::new();
+        if let Some(limit) = self.max_memory_mb.filter(|l| usage.memory_mb > *l) {
+            breaches.push(format!("memory {}MB > {}MB", usage.memory_mb, limit));
+        }
+        if let Some(limit) = self.max_cpu_percent.filter(|l| usage.cpu_percent > *l) {
+            breaches.push(format!("cpu {:.1}% > {}%", usage.cpu_percent, limit));
+        }
+        if let (Some(limit), Some(threads)) = (self.max_threads, usage.threads) {
+            if threads > limit {
+                breaches.push(format!("threads {} > {}", threads, limit));
+            }
+        }
+        if let (Some(limit), Some(handles)) = (self.max_handles, usage.handles) {
+            if handles > limit {
+                breaches.push(format!("handles {} > {}", handles, limit));
+            }
+        }
+        breaches
+    }
+}
+
+/// プロセスのスレッド数とハンドル数（読めない場合は `None`）
+#[cfg(target_os = "linux")]
+pub fn thread_and_handle_counts(pid: u32) -> (Option<u32>, Option<u32>) {
+    let threads = std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Threads:"))
+                .and_then(|v| v.trim().parse().ok())
+        });
+    let handles = std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|entries| entries.count() as u32);
+    (threads, handles)
+}
+
+/// プロセスのスレッド数とハンドル数（読めない場合は `None`）
+#[cfg(windows)]
+pub fn thread_and_handle_counts(pid: u32) -> (Option<u32>, Option<u32>) {
+    use winapi::shared::minwindef::FALSE;
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::processthreadsapi::{GetProcessHandleCount, OpenProcess};
+    use winapi::um::tlhelp32::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    unsafe {
+        let mut handles = None;
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if !process.is_null() {
+            let mut count = 0;
+            if GetProcessHandleCount(process, &mut count) != 0 {
+                handles = Some(count);
+            }
+            CloseHandle(process);
+        }
+
+        let mut threads = None;
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot != INVALID_HANDLE_VALUE {
+            let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+            let mut ok = Process32FirstW(snapshot, &mut entry) != 0;
+            while ok {
+                if entry.th32ProcessID == pid {
+                    threads = Some(entry.cntThreads);
+                    break;
+                }
+                ok = Process32NextW(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+        }
+
+        (threads, handles)
+    }
+}
+
+/// プロセスのスレッド数とハンドル数（この環境では読めない）
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn thread_and_handle_counts(_pid: u32) -> (Option<u32>, Option<u32>) {
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_are_read_per_instance() {
+        let config: HashMap<String, String> = [
+            ("MT_1.MaxMemoryMB", "1500"),
+            ("MT_1.MaxCpuPercent", "80"),
+            ("MT_1.ThresholdMinutes", "10"),
+            ("MT_1.ThresholdAction", "restart"),
+            ("MT_2.MaxThreads", "0"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let first = ResourceLimits::for_instance(&config, "MT_1");
+        assert_eq!(first.max_memory_mb, Some(1500));
+        assert_eq!(first.sustain, Duration::minutes(10));
+        assert_eq!(first.action, ThresholdAction::Restart);
+
+        let usage = ResourceUsage {
+            memory_mb: 1834,
+            cpu_percent: 12.0,
+            threads: Some(40),
+            handles: None,
+        };
+        assert_eq!(first.breaches(&usage), vec!["memory 1834MB > 1500MB"]);
+
+        // 0 は上限なし
+        assert!(ResourceLimits::for_instance(&config, "MT_2").is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn own_thread_count_is_readable() {
+        let (threads, handles) = thread_and_handle_counts(std::process::id());
+        assert!(threads.unwrap() >= 1);
+        assert!(handles.unwrap() >= 1);
+    }
+}
@@ -0,0 +1,484 @@
+//! 取引時間・メンテナンス時間の判定
+//!
+//! ブローカーの週末メンテナンスやVPSの更新作業など、MT4/MT5を止めておく時間帯を
+//! 設定で指定する。該当する時間帯は再起動せず状態の表示だけを行い、
+//! `ScheduleMode=enforce` の場合は動いているMT4/MT5を終了させる。
+//!
+//! ```ini
+//! TimeZone=Europe/Athens
+//! Maintenance=Sat 00:00-Mon 00:30, Wed 23:55-Thu 00:05
+//! Blackout=2026-11-01 02:00~2026-11-01 06:00
+//! Holidays=2026-12-25, 2027-01-01
+//! ScheduleMode=report
+//!
+//! MT_1.Maintenance=Sun 22:00-23:00
+//! MT_1.ScheduleMode=enforce
+//! ```
+//!
+//! 全体の設定とインスタンスごとの設定はどちらも有効で、どちらかに該当すれば
+//! 停止時間とみなす。`TimeZone` を省略した場合はPCのローカル時刻で判定する。
+//!
+//! 定期的な再起動（メモリリーク対策など）はインスタンスごとに指定する。
+//! 曜日を省略すると毎日、`@タイムゾーン` を付けるとその時刻で判定する。
+//!
+//! ```ini
+//! MT_1.RestartSchedule=Sat 03:00@Europe/Athens, Wed 04:30
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{
+    DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+use crate::instance::option_key;
+use crate::t;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTES_PER_WEEK: u32 = 7 * MINUTES_PER_DAY;
+
+/// 停止時間中の動作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScheduleMode {
+    /// 再起動せず、状態の表示だけを行う
+    #[default]
+    Report,
+    /// 動いているMT4/MT5を終了させる
+    Enforce,
+}
+
+impl ScheduleMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "report" => Some(ScheduleMode::Report),
+            "enforce" => Some(ScheduleMode::Enforce),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleMode::Report => "report",
+            ScheduleMode::Enforce => "enforce",
+        }
+    }
+}
+
+/// 毎週繰り返す時間帯（週をまたいでもよい）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeeklyWindow {
+    /// 月曜 00:00 からの分
+    start: u32,
+    end: u32,
+}
+
+impl WeeklyWindow {
+    /// `Sat 00:00-Mon 00:30` または `Sun 22:00-23:00` の形式
+    ///
+    /// 終わりの曜日を省いて始まりより前の時刻を書いた場合は翌日の時刻とする（`Fri 22:00-01:00` は土曜 01:00 まで）。
+    pub fn parse(value: &str) -> Option<Self> {
+        let (start, end) = value.split_once('-')?;
+        let (start_day, start_time) = parse_day_time(start, None)?;
+        let (end_day, end_time) = parse_day_time(end, Some(start_day))?;
+        let end_day = if end.split_whitespace().count() == 1 && end_time < start_time {
+            end_day.succ()
+        } else {
+            end_day
+        };
+        let start = week_minute(start_day, start_time);
+        let end = week_minute(end_day, end_time);
+        (start != end).then_some(WeeklyWindow { start, end })
+    }
+
+    fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl fmt::Display for WeeklyWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |m: u32, f: &mut fmt::Formatter<'_>| {
+            let day = Weekday::try_from((m / MINUTES_PER_DAY % 7) as u8).unwrap_or(Weekday::Mon);
+            let minute = m % MINUTES_PER_DAY;
+            write!(f, "{} {:02}:{:02}", day, minute / 60, minute % 60)
+        };
+        show(self.start, f)?;
+        write!(f, "-")?;
+        show(self.end, f)
+    }
+}
+
+/// 曜日と時刻（`24:00` は翌日の 00:00 として扱う）
+fn parse_day_time(value: &str, default_day: Option<Weekday>) -> Option<(Weekday, u32)> {
+    let mut parts = value.split_whitespace();
+    let (day, time) = match (parts.next()?, parts.next()) {
+        (day, Some(time)) => (parse_weekday(day)?, time),
+        (time, None) => (default_day?, time),
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    let minute = if time == "24:00" {
+        MINUTES_PER_DAY
+    } else {
+        let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+        time.hour() * 60 + time.minute()
+    };
+    Some((day, minute))
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    let day = match value {
+        "月" => Weekday::Mon,
+        "火" => Weekday::Tue,
+        "水" => Weekday::Wed,
+        "木" => Weekday::Thu,
+        "金" => Weekday::Fri,
+        "土" => Weekday::Sat,
+        "日" => Weekday::Sun,
+        other => other.parse().ok()?,
+    };
+    Some(day)
+}
+
+fn week_minute(day: Weekday, minute: u32) -> u32 {
+    (day.num_days_from_monday() * MINUTES_PER_DAY + minute) % MINUTES_PER_WEEK
+}
+
+/// 1か所（全体または1インスタンス）の設定から読んだ停止時間
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Rules {
+    /// 判定に使うタイムゾーン（`None` はPCのローカル時刻）
+    pub time_zone: Option<Tz>,
+    pub weekly: Vec<WeeklyWindow>,
+    /// 一度だけの停止期間（開始を含み、終了を含まない）
+    pub blackouts: Vec<(NaiveDateTime, NaiveDateTime)>,
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl Rules {
+    fn is_empty(&self) -> bool {
+        self.weekly.is_empty() && self.blackouts.is_empty() && self.holidays.is_empty()
+    }
+
+    /// 指定した時刻が停止時間ならその理由を返す
+    fn closed_reason(&self, at: DateTime<Utc>) -> Option<String> {
+        let local = match self.time_zone {
+            Some(tz) => at.with_timezone(&tz).naive_local(),
+            None => at.with_timezone(&Local).naive_local(),
+        };
+
+        if self.holidays.contains(&local.date()) {
+            return Some(t!("schedule.holiday", date = local.date()));
+        }
+        if let Some((start, end)) = self.blackouts.iter().find(|(s, e)| *s <= local && local < *e) {
+            return Some(t!(
+                "schedule.blackout",
+                start = start.format("%Y-%m-%d %H:%M"),
+                end = end.format("%Y-%m-%d %H:%M"),
+            ));
+        }
+        let minute = week_minute(local.weekday(), local.hour() * 60 + local.minute());
+        self.weekly
+            .iter()
+            .find(|w| w.contains(minute))
+            .map(|w| t!("schedule.maintenance", window = w))
+    }
+}
+
+/// 定期再起動の時刻（`Sat 03:00`、`03:00@Europe/Athens` など）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartTime {
+    /// 曜日（`None` は毎日）
+    day: Option<Weekday>,
+    time: NaiveTime,
+    time_zone: Option<Tz>,
+}
+
+impl RestartTime {
+    /// `@タイムゾーン` がない場合は `default_zone` を使う
+    pub fn parse(value: &str, default_zone: Option<Tz>) -> Option<Self> {
+        let (spec, time_zone) = match value.split_once('@') {
+            Some((spec, zone)) => (spec, Some(zone.trim().parse::<Tz>().ok()?)),
+            None => (value, default_zone),
+        };
+        let mut parts = spec.split_whitespace();
+        let (day, time) = match (parts.next()?, parts.next()) {
+            (day, Some(time)) => (Some(parse_weekday(day)?), time),
+            (time, None) => (None, time),
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+        Some(RestartTime { day, time, time_zone })
+    }
+
+    /// `now` 以前で最も新しい再起動の時刻
+    pub fn last_occurrence(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.time_zone {
+            Some(tz) => self.last_occurrence_in(&tz, now),
+            None => self.last_occurrence_in(&Local, now),
+        }
+    }
+
+    fn last_occurrence_in<Z: TimeZone>(&self, zone: &Z, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = now.with_timezone(zone).naive_local();
+        let mut date = local.date();
+        let step = match self.day {
+            Some(day) => {
+                let back = (7 + date.weekday().num_days_from_monday() - day.num_days_from_monday()) % 7;
+                date = date.checked_sub_days(Days::new(back as u64))?;
+                Days::new(7)
+            }
+            None => Days::new(1),
+        };
+        let mut candidate = date.and_time(self.time);
+        if candidate > local {
+            candidate = candidate.checked_sub_days(step)?;
+        }
+        // 夏時間の切り替えで存在しない時刻は、その直後の時刻として扱う
+        zone.from_local_datetime(&candidate)
+            .earliest()
+            .or_else(|| zone.from_local_datetime(&(candidate + chrono::Duration::hours(1))).earliest())
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+impl fmt::Display for RestartTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(day) = self.day {
+            write!(f, "{} ", day)?;
+        }
+        write!(f, "{}", self.time.format("%H:%M"))?;
+        if let Some(tz) = self.time_zone {
+            write!(f, "@{}", tz)?;
+        }
+        Ok(())
+    }
+}
+
+/// インスタンスに適用される停止時間の設定
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schedule {
+    rules: Vec<Rules>,
+    /// 定期再起動の時刻
+    pub restarts: Vec<RestartTime>,
+    pub mode: ScheduleMode,
+    /// 読み込めなかった設定（表示用）
+    pub errors: Vec<String>,
+}
+
+impl Schedule {
+    /// 全体の設定と `MT_n.` の設定を合わせて読む
+    pub fn for_instance(config: &HashMap<String, String>, key: &str) -> Self {
+        let mut schedule = Schedule::default();
+        let global_zone = schedule.read_rules(config, |name| name.to_string(), None);
+        let zone = schedule.read_rules(config, |name| option_key(key, name), global_zone);
+
+        let restart_key = option_key(key, "RestartSchedule");
+        if let Some(value) = config.get(&restart_key) {
+            for item in list_items(value) {
+                match RestartTime::parse(item, zone) {
+                    Some(time) => schedule.restarts.push(time),
+                    None => schedule
+                        .errors
+                        .push(t!("schedule.unreadable", key = restart_key, value = item)),
+                }
+            }
+        }
+
+        schedule.mode = config
+            .get(&option_key(key, "ScheduleMode"))
+            .or_else(|| config.get("ScheduleMode"))
+            .and_then(|s| ScheduleMode::parse(s))
+            .unwrap_or_default();
+        schedule
+    }
+
+    /// 停止時間の設定を1か所分読み、その場所のタイムゾーンを返す
+    fn read_rules(
+        &mut self,
+        config: &HashMap<String, String>,
+        key_of: impl Fn(&str) -> String,
+        inherited_zone: Option<Tz>,
+    ) -> Option<Tz> {
+        let mut rules = Rules {
+            time_zone: inherited_zone,
+            ..Rules::default()
+        };
+        let get = |name: &str| {
+            let key = key_of(name);
+            config.get(&key).map(|value| (key, value.as_str()))
+        };
+
+        if let Some((key, value)) = get("TimeZone").filter(|(_, v)| !v.trim().is_empty()) {
+            match value.trim().parse::<Tz>() {
+                Ok(tz) => rules.time_zone = Some(tz),
+                Err(_) => self.errors.push(t!("schedule.unknown_timezone", key = key, value = value.trim())),
+            }
+        }
+        if let Some((key, value)) = get("Maintenance") {
+            for item in list_items(value) {
+                match WeeklyWindow::parse(item) {
+                    Some(window) => rules.weekly.push(window),
+                    None => self.errors.push(t!("schedule.unreadable", key = key, value = item)),
+                }
+            }
+        }
+        if let Some((key, value)) = get("Blackout") {
+            for item in list_items(value) {
+                match parse_blackout(item) {
+                    Some(period) => rules.blackouts.push(period),
+                    None => self.errors.push(t!("schedule.unreadable", key = key, value = item)),
+                }
+            }
+        }
+        if let Some((key, value)) = get("Holidays") {
+            for item in list_items(value) {
+                match NaiveDate::parse_from_str(item, "%Y-%m-%d") {
+                    Ok(date) => rules.holidays.push(date),
+                    Err(_) => self.errors.push(t!("schedule.unreadable", key = key, value = item)),
+                }
+            }
+        }
+
+        let zone = rules.time_zone;
+        if !rules.is_empty() {
+            self.rules.push(rules);
+        }
+        zone
+    }
+
+    /// 停止時間が1つも設定されていないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 指定した時刻が停止時間ならその理由を返す
+    pub fn closed_reason(&self, at: DateTime<Utc>) -> Option<String> {
+        self.rules.iter().find_map(|rules| rules.closed_reason(at))
+    }
+
+    /// `now` 以前で最も新しい定期再起動の時刻
+    pub fn last_restart_time(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.restarts.iter().filter_map(|r| r.last_occurrence(now)).max()
+    }
+}
+
+fn list_items(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// `2026-11-01 02:00~2026-11-01 06:00` の形式
+fn parse_blackout(value: &str) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let (start, end) = value.split_once('~')?;
+    let parse = |s: &str| NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M").ok();
+    let (start, end) = (parse(start)?, parse(end)?);
+    (start < end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn weekly_window_can_wrap_the_week() {
+        let window = WeeklyWindow::parse("Sat 22:00-Mon 01:00").unwrap();
+        assert!(window.contains(week_minute(Weekday::Sun, 12 * 60)));
+        assert!(window.contains(week_minute(Weekday::Mon, 0)));
+        assert!(!window.contains(week_minute(Weekday::Mon, 60)));
+        assert!(!window.contains(week_minute(Weekday::Sat, 21 * 60)));
+
+        let same_day = WeeklyWindow::parse("日 22:00-24:00").unwrap();
+        assert!(same_day.contains(week_minute(Weekday::Sun, 23 * 60 + 59)));
+        assert!(!same_day.contains(week_minute(Weekday::Mon, 0)));
+
+        assert!(WeeklyWindow::parse("Someday 10:00-11:00").is_none());
+    }
+
+    #[test]
+    fn day_less_end_before_the_start_is_the_next_day() {
+        let window = WeeklyWindow::parse("Fri 22:00-01:00").unwrap();
+        assert_eq!(window.to_string(), "Fri 22:00-Sat 01:00");
+        assert!(window.contains(week_minute(Weekday::Sat, 0)));
+        assert!(!window.contains(week_minute(Weekday::Sat, 60)));
+        assert!(!window.contains(week_minute(Weekday::Wed, 12 * 60)));
+
+        let midnight = WeeklyWindow::parse("Wed 23:55-00:05").unwrap();
+        assert!(midnight.contains(week_minute(Weekday::Thu, 0)));
+        assert!(!midnight.contains(week_minute(Weekday::Fri, 0)));
+        // 日曜の翌日は月曜（週をまたぐ）
+        let sunday = WeeklyWindow::parse("Sun 23:00-02:00").unwrap();
+        assert!(sunday.contains(week_minute(Weekday::Mon, 60)));
+        assert!(!sunday.contains(week_minute(Weekday::Wed, 0)));
+
+        assert!(WeeklyWindow::parse("Fri 10:00-10:00").is_none());
+    }
+
+    #[test]
+    fn windows_follow_the_broker_time_zone_across_dst() {
+        let config = config(&[("TimeZone", "Europe/Athens"), ("Maintenance", "Sat 00:00-Sat 02:00")]);
+        let schedule = Schedule::for_instance(&config, "MT_1");
+
+        // 夏時間（GMT+3）: 土曜 00:00 はUTCの金曜 21:00
+        assert!(schedule.closed_reason(utc(2026, 7, 10, 21, 30)).is_some());
+        assert!(schedule.closed_reason(utc(2026, 7, 10, 20, 30)).is_none());
+        // 冬時間（GMT+2）: 土曜 00:00 はUTCの金曜 22:00
+        assert!(schedule.closed_reason(utc(2026, 12, 4, 21, 30)).is_none());
+        assert!(schedule.closed_reason(utc(2026, 12, 4, 22, 30)).is_some());
+    }
+
+    #[test]
+    fn restart_time_finds_the_latest_occurrence() {
+        let weekly = RestartTime::parse("Sat 03:00@Europe/Athens", None).unwrap();
+        // 2026-10-17（土）03:00 はアテネの夏時間（GMT+3）で 00:00 UTC
+        assert_eq!(weekly.last_occurrence(utc(2026, 10, 17, 0, 30)), Some(utc(2026, 10, 17, 0, 0)));
+        assert_eq!(weekly.last_occurrence(utc(2026, 10, 16, 23, 59)), Some(utc(2026, 10, 10, 0, 0)));
+
+        let daily = RestartTime::parse("04:30", Some(chrono_tz::UTC)).unwrap();
+        assert_eq!(daily.last_occurrence(utc(2026, 10, 17, 4, 0)), Some(utc(2026, 10, 16, 4, 30)));
+
+        assert!(RestartTime::parse("Sat 03:00@Nowhere/City", None).is_none());
+    }
+
+    #[test]
+    fn global_and_instance_rules_are_combined() {
+        let config = config(&[
+            ("TimeZone", "UTC"),
+            ("Holidays", "2026-12-25"),
+            ("MT_1.Blackout", "2026-11-01 02:00~2026-11-01 06:00"),
+            ("MT_1.ScheduleMode", "enforce"),
+            ("MT_2.Maintenance", "whenever"),
+        ]);
+
+        let first = Schedule::for_instance(&config, "MT_1");
+        assert_eq!(first.mode, ScheduleMode::Enforce);
+        assert!(first.closed_reason(utc(2026, 12, 25, 12, 0)).is_some());
+        assert!(first.closed_reason(utc(2026, 11, 1, 3, 0)).is_some());
+        assert!(first.closed_reason(utc(2026, 11, 1, 6, 0)).is_none());
+
+        let second = Schedule::for_instance(&config, "MT_2");
+        assert_eq!(second.mode, ScheduleMode::Report);
+        assert!(second.closed_reason(utc(2026, 11, 1, 3, 0)).is_none());
+        assert_eq!(second.errors.len(), 1);
+    }
+}
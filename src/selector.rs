@@ -0,0 +1,166 @@
+//! インスタンスのグループ・タグでの選択
+//!
+//! インスタンスには `MT_番号.Group`（1つ）と `MT_番号.Tags`（カンマ区切りで複数）を付けられる。
+//! 有効/無効の切り替え・再起動・終了・一覧の絞り込みは、番号の代わりに次の書き方でまとめて選べる。
+//!
+//! | 書き方 | 選ばれるもの |
+//! |--------|--------------|
+//! | `all` または `*` | すべて |
+//! | `MT_3` または `3` | そのインスタンス |
+//! | `group:clientA` | グループが `clientA` |
+//! | `tag:live` | タグに `live` がある |
+//! | `live`、`broker:XM` | グループかタグが一致する |
+//! | `live+broker:XM` | 両方に当てはまる（`+` でつなぐ） |
+//! | `live,demo` | どちらかに当てはまる（`,` か空白で区切る） |
+//! | `!demo` | 当てはまらない |
+//!
+//! 大文字・小文字は区別しない。
+//!
+//! ```ini
+//! MT_1.Group=tanaka
+//! MT_1.Tags=live,broker:XM
+//! ```
+
+use crate::instance::{parse_instance_id, resolve_instance_id, MtInstance};
+use crate::t;
+
+/// 1つの条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    All,
+    Key(String),
+    Group(String),
+    Tag(String),
+    /// グループかタグが一致する（`MT_XM` のように番号でないキーとも比べる）
+    Label(String),
+    Not(Box<Term>),
+}
+
+impl Term {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(rest) = text.strip_prefix('!') {
+            return Term::parse(rest).map(|term| Term::Not(Box::new(term)));
+        }
+        if text.is_empty() {
+            return None;
+        }
+        if let Some(key) = parse_instance_id(text) {
+            return Some(Term::Key(key));
+        }
+        let lower = text.to_lowercase();
+        Some(match lower.as_str() {
+            "all" | "*" => Term::All,
+            _ => match lower.split_once(':') {
+                Some(("group", group)) if !group.is_empty() => Term::Group(group.to_string()),
+                Some(("tag", tag)) if !tag.is_empty() => Term::Tag(tag.to_string()),
+                _ => Term::Label(lower),
+            },
+        })
+    }
+
+    fn matches(&self, instance: &MtInstance) -> bool {
+        match self {
+            Term::All => true,
+            Term::Key(key) => resolve_instance_id(key, [instance.key.as_str()]).is_some(),
+            Term::Group(group) => instance.in_group(group),
+            Term::Tag(tag) => instance.has_tag(tag),
+            Term::Label(label) => {
+                instance.in_group(label) || instance.has_tag(label) || instance.key.eq_ignore_ascii_case(label)
+            }
+            Term::Not(term) => !term.matches(instance),
+        }
+    }
+}
+
+/// インスタンスの選び方（`live+broker:XM,group:tanaka` のような書き方）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    /// `,` で区切った候補（どれかに当てはまれば選ぶ）。各候補は `+` でつないだ条件
+    alternatives: Vec<Vec<Term>>,
+}
+
+impl Selector {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let alternatives = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|alternative| !alternative.is_empty())
+            .map(|alternative| alternative.split('+').map(Term::parse).collect::<Option<Vec<_>>>())
+            .collect::<Option<Vec<_>>>()
+            .filter(|alternatives| !alternatives.is_empty())
+            .ok_or_else(|| t!("select.invalid", selector = text.trim()))?;
+        Ok(Selector { alternatives })
+    }
+
+    /// すべてを選ぶ
+    pub fn all() -> Self {
+        Selector {
+            alternatives: vec![vec![Term::All]],
+        }
+    }
+
+    pub fn matches(&self, instance: &MtInstance) -> bool {
+        self.alternatives
+            .iter()
+            .any(|terms| terms.iter().all(|term| term.matches(instance)))
+    }
+
+    /// 当てはまるインスタンス（並びはそのまま）
+    pub fn select<'a>(&self, instances: &'a [MtInstance]) -> Vec<&'a MtInstance> {
+        instances.iter().filter(|i| self.matches(i)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(key: &str, group: Option<&str>, tags: &[&str]) -> MtInstance {
+        let mut instance = MtInstance::new(key, key, "C:\\MT4\\terminal.exe");
+        instance.group = group.map(str::to_string);
+        instance.tags = tags.iter().map(|t| t.to_string()).collect();
+        instance
+    }
+
+    fn keys(selector: &str, instances: &[MtInstance]) -> Vec<String> {
+        Selector::parse(selector)
+            .unwrap()
+            .select(instances)
+            .into_iter()
+            .map(|i| i.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn selects_by_group_tag_and_key() {
+        let instances = [
+            instance("MT_1", Some("tanaka"), &["live", "broker:XM"]),
+            instance("MT_2", Some("tanaka"), &["demo", "broker:XM"]),
+            instance("MT_3", Some("sato"), &["live", "broker:OANDA"]),
+            instance("MT_10", None, &[]),
+            instance("MT_04", None, &[]),
+            instance("MT_XM", None, &[]),
+        ];
+        assert_eq!(keys("live", &instances), ["MT_1", "MT_3"]);
+        assert_eq!(keys("Broker:xm", &instances), ["MT_1", "MT_2"]);
+        assert_eq!(keys("group:tanaka", &instances), ["MT_1", "MT_2"]);
+        assert_eq!(keys("tag:broker:OANDA", &instances), ["MT_3"]);
+        assert_eq!(keys("live+!broker:XM", &instances), ["MT_3"]);
+        assert_eq!(keys("mt_10, demo", &instances), ["MT_2", "MT_10"]);
+        assert_eq!(keys("1 3", &instances), ["MT_1", "MT_3"]);
+        assert_eq!(keys("sato tag:demo", &instances), ["MT_2", "MT_3"]);
+        assert_eq!(keys("*", &instances).len(), 6);
+        // 手で書いたキーも選べる
+        assert_eq!(keys("4,mt_xm", &instances), ["MT_04", "MT_XM"]);
+        // グループ名はタグと同じ書き方でも選べる
+        assert_eq!(keys("sato", &instances), ["MT_3"]);
+    }
+
+    #[test]
+    fn empty_terms_are_rejected() {
+        assert!(Selector::parse("").is_err());
+        assert!(Selector::parse(" , ").is_err());
+        assert!(Selector::parse("live+").is_err());
+        assert!(Selector::parse("!").is_err());
+    }
+}
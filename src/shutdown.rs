@@ -0,0 +1,176 @@
+//! 監視ループの停止と設定再読み込みの合図
+//!
+//! Ctrl+C（Linuxでは SIGTERM も）で停止を、SIGHUP で設定の再読み込みを要求する。
+//! ハンドラーはプロセス全体で1回だけ登録し、監視ループはチェックの合間に
+//! トークンを確認して自分で抜ける。監視中でないとき（メニュー表示中）に
+//! 停止を要求された場合は、従来どおりそのまま終了する。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::t;
+
+/// 待機中に停止要求を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Default)]
+struct Flags {
+    /// 監視ループが動いているかどうか
+    active: AtomicBool,
+    stop: AtomicBool,
+    reload: AtomicBool,
+}
+
+/// 監視ループに停止・再読み込みを伝えるトークン
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownToken {
+    flags: Arc<Flags>,
+}
+
+static GLOBAL: OnceLock<ShutdownToken> = OnceLock::new();
+
+impl ShutdownToken {
+    /// シグナルハンドラーに接続されたトークン（初回呼び出し時にハンドラーを登録）
+    pub fn global() -> ShutdownToken {
+        GLOBAL
+            .get_or_init(|| {
+                let token = ShutdownToken::default();
+                token.install_handlers();
+                token
+            })
+            .clone()
+    }
+
+    fn install_handlers(&self) {
+        let token = self.clone();
+        if let Err(e) = ctrlc::set_handler(move || token.on_terminate()) {
+            eprintln!("{}", t!("shutdown.ctrlc_failed", error = e));
+        }
+
+        #[cfg(unix)]
+        {
+            use signal_hook::consts::{SIGHUP, SIGTERM};
+            use signal_hook::iterator::Signals;
+
+            match Signals::new([SIGTERM, SIGHUP]) {
+                Ok(mut signals) => {
+                    let token = self.clone();
+                    thread::spawn(move || {
+                        for signal in signals.forever() {
+                            match signal {
+                                SIGHUP => token.request_reload(),
+                                _ => token.on_terminate(),
+                            }
+                        }
+                    });
+                }
+                Err(e) => eprintln!("{}", t!("shutdown.signal_failed", error = e)),
+            }
+        }
+    }
+
+    fn on_terminate(&self) {
+        if self.flags.active.load(Ordering::SeqCst) {
+            self.request_shutdown();
+        } else {
+            // 監視中でなければ従来どおり終了する
+            println!();
+            std::process::exit(0);
+        }
+    }
+
+    /// 監視ループの開始を宣言する（戻り値を破棄すると終了扱いになる）
+    pub fn begin(&self) -> ActiveGuard {
+        self.flags.stop.store(false, Ordering::SeqCst);
+        self.flags.reload.store(false, Ordering::SeqCst);
+        self.flags.active.store(true, Ordering::SeqCst);
+        ActiveGuard { token: self.clone() }
+    }
+
+    pub fn request_shutdown(&self) {
+        self.flags.stop.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.flags.stop.load(Ordering::SeqCst)
+    }
+
+    pub fn request_reload(&self) {
+        self.flags.reload.store(true, Ordering::SeqCst);
+    }
+
+    /// 再読み込みが要求されていれば true を返し、要求を取り消す
+    pub fn take_reload(&self) -> bool {
+        self.flags.reload.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn reload_pending(&self) -> bool {
+        self.flags.reload.load(Ordering::SeqCst)
+    }
+
+    /// 指定時間待つ。停止が要求されたら途中で戻り false を返す
+    ///
+    /// 再読み込みが要求された場合も、すぐ反映できるよう途中で true を返す。
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_shutdown() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline || self.reload_pending() {
+                return true;
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
+
+/// 監視ループが動いている間保持するガード
+pub struct ActiveGuard {
+    token: ShutdownToken,
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.token.flags.active.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleep_returns_early_on_shutdown() {
+        let token = ShutdownToken::default();
+        let _active = token.begin();
+
+        let waker = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            waker.request_shutdown();
+        });
+
+        let started = Instant::now();
+        assert!(!token.sleep(Duration::from_secs(10)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn begin_clears_previous_requests() {
+        let token = ShutdownToken::default();
+        token.request_shutdown();
+        token.request_reload();
+
+        let _active = token.begin();
+        assert!(!token.is_shutdown());
+        assert!(!token.take_reload());
+
+        token.request_reload();
+        assert!(token.take_reload());
+        assert!(!token.take_reload());
+    }
+}
@@ -0,0 +1,242 @@
+//! 監視状態のスナップショット
+//!
+//! チェックのたびと監視終了時に、最後のチェック結果を設定ファイルと同じフォルダの
+//! `MetaGuard.ini.state` に書き出す。形式は設定ファイルと同じ `キー=値`。
+//!
+//! 再起動回数・待機の期限・起動したPIDなども書き出し、MetaGuard自体を
+//! 再起動したときに読み込んで引き継ぐ（`MaxRestartsPerHour` などがPCの
+//! 再起動をまたいでも効くようにするため）。時刻はUTCのRFC 3339形式で書く。
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+
+use crate::{backup, t};
+
+/// 1回のチェックでのインスタンスの状態
+#[derive(Debug, Clone)]
+pub struct InstanceStatus {
+    /// 設定ファイル上のキー（例: `MT_1`）
+    pub key: String,
+    pub name: String,
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// このチェックで起動したかどうか
+    pub launched: bool,
+    /// 起動に失敗した場合のエラー
+    pub error: Option<String>,
+    /// 実行中・起動待ちになった時刻
+    pub since: Option<DateTime<Utc>>,
+    /// 直近1時間の再起動時刻
+    pub restarts: Vec<DateTime<Utc>>,
+    /// 連続した起動失敗の回数
+    pub consecutive_failures: u32,
+    /// 起動失敗後の待機の期限
+    pub backoff_until: Option<DateTime<Utc>>,
+    /// 再起動回数の上限に達しているかどうか
+    pub crash_loop: bool,
+    /// 最後に処理した定期再起動の時刻
+    pub last_planned_restart: Option<DateTime<Utc>>,
+}
+
+impl InstanceStatus {
+    /// 状態ファイルの `Status` に書く値
+    pub fn status_str(&self) -> &'static str {
+        if self.running {
+            "running"
+        } else if self.launched {
+            "starting"
+        } else if self.crash_loop {
+            "crash_loop"
+        } else if self.backoff_until.is_some() {
+            "backoff"
+        } else {
+            "stopped"
+        }
+    }
+}
+
+/// 設定ファイルに対応する状態ファイルのパス（`MetaGuard.ini.state`）
+///
+/// 拡張子を置き換えると、同じフォルダの `MetaGuard.ini` と `MetaGuard.toml` が同じファイルを使ってしまうため、
+/// ファイル名の後ろに付ける。
+pub fn state_path_for(config_path: &Path) -> PathBuf {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".state");
+    config_path.with_file_name(name)
+}
+
+/// 状態ファイルの内容を作る
+pub fn render_snapshot(statuses: &[InstanceStatus], reason: &str) -> String {
+    let mut content = String::new();
+    content.push_str(&format!("; {}\n", t!("state.header")));
+    content.push_str(&format!("WrittenAt={}\n", Local::now().format("%Y-%m-%d %H:%M:%S")));
+    content.push_str(&format!("Reason={}\n\n", reason));
+
+    for status in statuses {
+        let key = &status.key;
+        content.push_str(&format!("{}.Name={}\n", key, status.name));
+        content.push_str(&format!("{}.Status={}\n", key, status.status_str()));
+        if let Some(pid) = status.pid {
+            content.push_str(&format!("{}.PID={}\n", key, pid));
+        }
+        if let Some(since) = status.since {
+            content.push_str(&format!("{}.Since={}\n", key, format_time(since)));
+        }
+        if !status.restarts.is_empty() {
+            let restarts: Vec<String> = status.restarts.iter().map(|t| format_time(*t)).collect();
+            content.push_str(&format!("{}.Restarts={}\n", key, restarts.join(",")));
+        }
+        if status.consecutive_failures > 0 {
+            content.push_str(&format!("{}.ConsecutiveFailures={}\n", key, status.consecutive_failures));
+        }
+        if let Some(until) = status.backoff_until {
+            content.push_str(&format!("{}.BackoffUntil={}\n", key, format_time(until)));
+        }
+        if let Some(at) = status.last_planned_restart {
+            content.push_str(&format!("{}.LastPlannedRestart={}\n", key, format_time(at)));
+        }
+        if let Some(error) = &status.error {
+            content.push_str(&format!("{}.LastError={}\n", key, error));
+        }
+        content.push('\n');
+    }
+
+    content
+}
+
+/// 状態ファイルを書き出す（書き込みの途中で落ちても前の状態が残るように置き換える）
+pub fn write_snapshot(path: &Path, statuses: &[InstanceStatus], reason: &str) -> io::Result<()> {
+    backup::write_atomic(path, &render_snapshot(statuses, reason))
+}
+
+/// 状態ファイルの内容を読む（読めない行は無視する）
+pub fn parse_snapshot(content: &str) -> Vec<InstanceStatus> {
+    let mut statuses: BTreeMap<String, InstanceStatus> = BTreeMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let Some((key, field)) = name.trim().split_once('.') else {
+            continue;
+        };
+        let value = value.trim();
+        let status = statuses.entry(key.to_string()).or_insert_with(|| InstanceStatus {
+            key: key.to_string(),
+            name: String::new(),
+            running: false,
+            pid: None,
+            launched: false,
+            error: None,
+            since: None,
+            restarts: Vec::new(),
+            consecutive_failures: 0,
+            backoff_until: None,
+            crash_loop: false,
+            last_planned_restart: None,
+        });
+        match field {
+            "Name" => status.name = value.to_string(),
+            "Status" => {
+                status.running = value == "running";
+                status.launched = value == "starting";
+                status.crash_loop = value == "crash_loop";
+            }
+            "PID" => status.pid = value.parse().ok(),
+            "Since" => status.since = parse_time(value),
+            "Restarts" => status.restarts = value.split(',').filter_map(parse_time).collect(),
+            "ConsecutiveFailures" => status.consecutive_failures = value.parse().unwrap_or(0),
+            "BackoffUntil" => status.backoff_until = parse_time(value),
+            "LastPlannedRestart" => status.last_planned_restart = parse_time(value),
+            "LastError" => status.error = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    statuses.into_values().collect()
+}
+
+/// 状態ファイルを読む（ファイルがなければ空）
+pub fn load_snapshot(path: &Path) -> io::Result<Vec<InstanceStatus>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(parse_snapshot(&content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn format_time(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn profiles_in_one_folder_get_their_own_state_file() {
+        let ini = state_path_for(Path::new("/srv/metaguard/MetaGuard.ini"));
+        assert_eq!(ini, Path::new("/srv/metaguard/MetaGuard.ini.state"));
+        assert_ne!(ini, state_path_for(Path::new("/srv/metaguard/MetaGuard.toml")));
+    }
+
+    #[test]
+    fn snapshot_round_trips_the_supervisor_state() {
+        let at = |h, m| Utc.with_ymd_and_hms(2026, 10, 17, h, m, 0).unwrap();
+        let statuses = vec![
+            InstanceStatus {
+                key: "MT_1".to_string(),
+                name: "XM".to_string(),
+                running: true,
+                pid: Some(500),
+                launched: false,
+                error: None,
+                since: Some(at(9, 0)),
+                restarts: vec![at(8, 40), at(8, 55)],
+                consecutive_failures: 0,
+                backoff_until: None,
+                crash_loop: false,
+                last_planned_restart: Some(at(3, 0)),
+            },
+            InstanceStatus {
+                key: "MT_2".to_string(),
+                name: "Demo".to_string(),
+                running: false,
+                pid: None,
+                launched: false,
+                error: Some("起動直後に終了しました".to_string()),
+                since: None,
+                restarts: vec![at(8, 59)],
+                consecutive_failures: 2,
+                backoff_until: Some(at(9, 1)),
+                crash_loop: false,
+                last_planned_restart: None,
+            },
+        ];
+
+        let loaded = parse_snapshot(&render_snapshot(&statuses, "check"));
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].pid, Some(500));
+        assert!(loaded[0].running);
+        assert_eq!(loaded[0].since, Some(at(9, 0)));
+        assert_eq!(loaded[0].restarts, statuses[0].restarts);
+        assert_eq!(loaded[0].last_planned_restart, Some(at(3, 0)));
+        assert_eq!(loaded[1].status_str(), "backoff");
+        assert_eq!(loaded[1].consecutive_failures, 2);
+        assert_eq!(loaded[1].backoff_until, Some(at(9, 1)));
+        assert_eq!(loaded[1].error, statuses[1].error);
+    }
+}
@@ -0,0 +1,429 @@
+//! MT4/MT5の終了
+//!
+//! いきなり強制終了すると、MT4/MT5がチャートやEAの設定を保存できない。
+//! まず丁寧に終了を頼み（Windowsでは WM_CLOSE、Linux/Wineでは SIGTERM）、
+//! `StopTimeout` 秒待っても終了しなければ強制終了する。
+//!
+//! 監視エンジンはチェックの合間に待つため、終了処理は `StopRequest` として
+//! 状態を持ち、チェックのたびに `poll` で進める。結果は `StopOutcome` で返す。
+//!
+//! 監視中の別のMetaGuardへの設定の再読み込みと手動の再起動は、設定ファイルの隣の依頼フォルダ
+//! （`MetaGuard.ini.requests`）にファイルを置いて頼む（[`MonitorRequest`]）。Windowsにはシグナルがないため、
+//! 監視している側が待ち時間の間にこのフォルダを見て応える。
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use chrono::{DateTime, Duration, Utc};
+use sysinfo::{Pid, PidExt, ProcessExt, Signal, System, SystemExt};
+
+use crate::t;
+
+/// 強制終了してから終了を確認できるまで待つ時間（秒）
+const FORCE_KILL_GRACE_SECS: i64 = 10;
+
+/// 書き終えた依頼のファイルの拡張子（書いている途中は `.tmp`）
+const REQUEST_EXTENSION: &str = "req";
+
+/// 終了処理の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopResult {
+    /// 終了の依頼で終了した
+    Closed,
+    /// 強制終了した
+    Killed,
+    /// 強制終了しても終了しなかった
+    StillRunning,
+}
+
+impl StopResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopResult::Closed => "closed",
+            StopResult::Killed => "killed",
+            StopResult::StillRunning => "still_running",
+        }
+    }
+}
+
+/// 終了処理の結果の詳細
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopOutcome {
+    pub pid: u32,
+    pub result: StopResult,
+    /// 終了の依頼を送れたかどうか（ウィンドウが見つからない場合などは送れない）
+    pub close_requested: bool,
+    /// 終了を依頼してから結果が出るまでの時間
+    pub waited: Duration,
+    /// 途中で起きたエラー
+    pub error: Option<String>,
+}
+
+/// 終了の依頼と強制終了の実行先（テストでは偽物に差し替える）
+pub trait StopControl {
+    /// 終了を依頼する。依頼を送れたら true
+    fn request_close(&mut self, pid: u32) -> io::Result<bool>;
+
+    /// 強制終了する
+    fn force_kill(&mut self, pid: u32) -> io::Result<()>;
+}
+
+/// 進行中の終了処理
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopRequest {
+    pub pid: u32,
+    pub started: DateTime<Utc>,
+    /// 終了の依頼から強制終了までの待ち時間
+    pub timeout: Duration,
+    pub close_requested: bool,
+    /// 強制終了した時刻
+    pub forced_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+impl StopRequest {
+    /// 終了を依頼して処理を始める（依頼を送れなければすぐ強制終了する）
+    pub fn begin(control: &mut impl StopControl, pid: u32, timeout: Duration, now: DateTime<Utc>) -> Self {
+        let mut request = StopRequest {
+            pid,
+            started: now,
+            timeout,
+            close_requested: false,
+            forced_at: None,
+            error: None,
+        };
+        match control.request_close(pid) {
+            Ok(sent) => request.close_requested = sent,
+            Err(e) => request.error = Some(e.to_string()),
+        }
+        if !request.close_requested {
+            request.force(control, now);
+        }
+        request
+    }
+
+    fn force(&mut self, control: &mut impl StopControl, now: DateTime<Utc>) {
+        self.forced_at = Some(now);
+        if let Err(e) = control.force_kill(self.pid) {
+            self.error = Some(e.to_string());
+        }
+    }
+
+    /// 処理を進める。結果が出たら返す（まだ待つ場合は `None`）
+    pub fn poll(&mut self, control: &mut impl StopControl, alive: bool, now: DateTime<Utc>) -> Option<StopOutcome> {
+        let result = match (alive, self.forced_at) {
+            (false, None) => StopResult::Closed,
+            (false, Some(_)) => StopResult::Killed,
+            (true, None) if now - self.started >= self.timeout => {
+                self.force(control, now);
+                return None;
+            }
+            (true, Some(forced_at)) if now - forced_at >= Duration::seconds(FORCE_KILL_GRACE_SECS) => {
+                StopResult::StillRunning
+            }
+            (true, _) => return None,
+        };
+        Some(StopOutcome {
+            pid: self.pid,
+            result,
+            close_requested: self.close_requested,
+            waited: now - self.started,
+            error: self.error.clone(),
+        })
+    }
+
+    /// 次に `poll` したい時刻
+    pub fn deadline(&self) -> DateTime<Utc> {
+        match self.forced_at {
+            Some(forced_at) => forced_at + Duration::seconds(FORCE_KILL_GRACE_SECS),
+            None => self.started + self.timeout,
+        }
+    }
+}
+
+/// プロセスのウィンドウに WM_CLOSE を送る
+#[cfg(windows)]
+pub fn request_close(pid: u32) -> io::Result<bool> {
+    use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::{EnumWindows, GetWindowThreadProcessId, IsWindowVisible, PostMessageW, WM_CLOSE};
+
+    struct Search {
+        pid: u32,
+        sent: bool,
+    }
+
+    unsafe extern "system" fn visit(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam as *mut Search);
+        let mut owner = 0;
+        GetWindowThreadProcessId(hwnd, &mut owner);
+        if owner == search.pid && IsWindowVisible(hwnd) != 0 && PostMessageW(hwnd, WM_CLOSE, 0, 0) != 0 {
+            search.sent = true;
+        }
+        TRUE
+    }
+
+    let mut search = Search { pid, sent: false };
+    unsafe {
+        EnumWindows(Some(visit), &mut search as *mut Search as LPARAM);
+    }
+    Ok(search.sent)
+}
+
+/// プロセスに SIGTERM を送る（Wine上のMT4/MT5も含む）
+#[cfg(not(windows))]
+pub fn request_close(pid: u32) -> io::Result<bool> {
+    with_process(pid, |process| process.kill_with(Signal::Term).unwrap_or(false))
+}
+
+/// プロセスを強制終了する
+pub fn force_kill(pid: u32) -> io::Result<()> {
+    let killed = with_process(pid, |process| process.kill())?;
+    if killed {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            t!("stop.failed", pid = pid),
+        ))
+    }
+}
+
+/// 監視中の別のMetaGuardへの依頼
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorRequest {
+    /// 設定を読み込み直す
+    Reload,
+    /// 手動で再起動する（再起動回数には数えない）
+    Restart(String),
+}
+
+impl MonitorRequest {
+    fn to_line(&self) -> String {
+        match self {
+            MonitorRequest::Reload => "reload".to_string(),
+            MonitorRequest::Restart(key) => format!("restart {}", key),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        match line.split_once(' ') {
+            None if line == "reload" => Some(MonitorRequest::Reload),
+            Some(("restart", key)) if !key.trim().is_empty() => Some(MonitorRequest::Restart(key.trim().to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// 設定ファイルに対応する依頼フォルダのパス（`MetaGuard.ini.requests`。プロファイルごとに別のフォルダ）
+pub fn request_dir_for(config_path: &Path) -> PathBuf {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".requests");
+    config_path.with_file_name(name)
+}
+
+/// 監視中のMetaGuardに依頼する（依頼フォルダに1つのファイルとして置き、そのパスを返す）
+///
+/// 書き終えてから名前を付け替えるので、監視している側が書きかけの依頼を読むことはない。
+pub fn send_requests(dir: &Path, requests: &[MonitorRequest]) -> io::Result<PathBuf> {
+    static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+    fs::create_dir_all(dir)?;
+    let name = format!(
+        "{}-{}-{}",
+        Utc::now().format("%Y%m%d%H%M%S%f"),
+        std::process::id(),
+        SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    );
+    let writing = dir.join(format!("{}.tmp", name));
+    let mut content = String::new();
+    for request in requests {
+        content.push_str(&request.to_line());
+        content.push('\n');
+    }
+    fs::write(&writing, content)?;
+    let path = dir.join(format!("{}.{}", name, REQUEST_EXTENSION));
+    fs::rename(&writing, &path)?;
+    Ok(path)
+}
+
+/// 取り出していない依頼があるかどうか
+pub fn has_requests(dir: &Path) -> bool {
+    request_files(dir).is_ok_and(|files| !files.is_empty())
+}
+
+/// 依頼を置かれた順に取り出す（取り出した依頼は消す。同じ依頼は1つにまとめる）
+///
+/// 依頼はそれぞれ別のファイルなので、取り出している間に置かれた依頼も失われず、次に取り出す。
+pub fn take_requests(dir: &Path) -> io::Result<Vec<MonitorRequest>> {
+    let mut requests: Vec<MonitorRequest> = Vec::new();
+    for file in request_files(dir)? {
+        // 消せなければ次にもう一度行うことになるので、読んだだけでは行わない
+        let taken = fs::read_to_string(&file).and_then(|content| fs::remove_file(&file).map(|()| content));
+        let content = match taken {
+            Ok(content) => content,
+            // 取り出せた依頼は返し、残りは次に取り出す
+            Err(_) if !requests.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        for request in content.lines().filter_map(|line| MonitorRequest::parse(line.trim())) {
+            if !requests.contains(&request) {
+                requests.push(request);
+            }
+        }
+    }
+    Ok(requests)
+}
+
+/// 置いた依頼が取り出されるまで待つ（取り出されたら true）
+pub fn wait_until_taken(request: &Path, timeout: std::time::Duration) -> bool {
+    let deadline = std::time::Instant::now() + timeout;
+    while request.exists() {
+        if std::time::Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    true
+}
+
+/// 依頼フォルダの依頼のファイル（置かれた順。フォルダがなければ空）
+fn request_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == REQUEST_EXTENSION) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 実際のプロセスに終了を依頼・強制終了する
+pub struct SystemStopControl;
+
+impl StopControl for SystemStopControl {
+    fn request_close(&mut self, pid: u32) -> io::Result<bool> {
+        request_close(pid)
+    }
+
+    fn force_kill(&mut self, pid: u32) -> io::Result<()> {
+        force_kill(pid)
+    }
+}
+
+/// 終了を依頼し、終了するか強制終了の結果が出るまで待つ（監視の外でまとめて終了させるとき）
+pub fn stop_and_wait(pid: u32, timeout: Duration) -> StopOutcome {
+    let mut control = SystemStopControl;
+    let mut request = StopRequest::begin(&mut control, pid, timeout, Utc::now());
+    loop {
+        let alive = System::new().refresh_process(Pid::from_u32(pid));
+        if let Some(outcome) = request.poll(&mut control, alive, Utc::now()) {
+            return outcome;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+}
+
+/// プロセスがあれば `f` を実行する（すでに終了していれば true）
+fn with_process(pid: u32, f: impl FnOnce(&sysinfo::Process) -> bool) -> io::Result<bool> {
+    let mut system = System::new();
+    let sys_pid = Pid::from_u32(pid);
+    if !system.refresh_process(sys_pid) {
+        return Ok(true);
+    }
+    Ok(system.process(sys_pid).map(f).unwrap_or(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// 終了の依頼を無視するプロセス
+    #[derive(Default)]
+    struct Stubborn {
+        closes: usize,
+        kills: usize,
+    }
+
+    impl StopControl for Stubborn {
+        fn request_close(&mut self, _pid: u32) -> io::Result<bool> {
+            self.closes += 1;
+            Ok(true)
+        }
+
+        fn force_kill(&mut self, _pid: u32) -> io::Result<()> {
+            self.kills += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn force_kill_follows_the_timeout() {
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 3, 0, 0).unwrap();
+        let mut control = Stubborn::default();
+        let mut request = StopRequest::begin(&mut control, 500, Duration::seconds(30), start);
+        assert_eq!((control.closes, control.kills), (1, 0));
+
+        assert_eq!(request.poll(&mut control, true, start + Duration::seconds(10)), None);
+        assert_eq!(request.poll(&mut control, true, start + Duration::seconds(30)), None);
+        assert_eq!(control.kills, 1);
+
+        let outcome = request.poll(&mut control, false, start + Duration::seconds(32)).unwrap();
+        assert_eq!(outcome.result, StopResult::Killed);
+        assert!(outcome.close_requested);
+        assert_eq!(outcome.waited, Duration::seconds(32));
+    }
+
+    #[test]
+    fn polite_close_is_reported_as_closed() {
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 3, 0, 0).unwrap();
+        let mut control = Stubborn::default();
+        let mut request = StopRequest::begin(&mut control, 500, Duration::seconds(30), start);
+        let outcome = request.poll(&mut control, false, start + Duration::seconds(4)).unwrap();
+        assert_eq!(outcome.result, StopResult::Closed);
+        assert_eq!(control.kills, 0);
+    }
+
+    #[test]
+    fn requests_are_taken_once_in_order() {
+        let dir = std::env::temp_dir().join(format!("metaguard-stop-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let requests = request_dir_for(&dir.join("MetaGuard.ini"));
+        // 同じフォルダの別の形式の設定ファイル（別のプロファイル）とは別の依頼フォルダ
+        assert_ne!(requests, request_dir_for(&dir.join("MetaGuard.toml")));
+        assert!(!has_requests(&requests));
+        assert_eq!(take_requests(&requests).unwrap(), []);
+
+        let restart = |key: &str| MonitorRequest::Restart(key.to_string());
+        send_requests(&requests, &[restart("MT_1"), restart("MT_3")]).unwrap();
+        let reload = send_requests(&requests, &[MonitorRequest::Reload, restart("MT_1")]).unwrap();
+        assert!(has_requests(&requests));
+        assert_eq!(
+            take_requests(&requests).unwrap(),
+            [restart("MT_1"), restart("MT_3"), MonitorRequest::Reload]
+        );
+        assert!(wait_until_taken(&reload, std::time::Duration::ZERO));
+        assert!(!has_requests(&requests));
+        assert!(take_requests(&requests).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sigterm_stops_a_real_process() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        assert!(request_close(child.id()).unwrap());
+        assert!(child.wait().unwrap().code().is_none());
+    }
+}
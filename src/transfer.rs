@@ -0,0 +1,469 @@
+//! インスタンスの一覧の書き出しと読み込み（CSV・JSON）
+//!
+//! 別のVPSへ移すときに、MT4/MT5の定義（`MT_番号` の行と追加項目）だけを書き出して読み込む。
+//!
+//! - CSV は1行が1インスタンスで、列は `Key,Enabled,Name,Path` と使っている追加項目（`Args`・`Group` など）。
+//!   Excelで開けるよう BOM 付きの UTF-8 で書く。`Key` が空の行は読み込むときに新しいIDを付ける。
+//! - JSON は設定ファイルの JSON 形式の `Instances` と同じ形（JSON の設定ファイルからもそのまま読み込める）。
+//!
+//! 読み込みは、書き込む前に全体を確かめて結果を作る（[`plan_import`]）。
+//!
+//! - merge: 同じパスのインスタンスは上書きし、それ以外は追加する。ファイルのIDが空いていればそのIDを使い、
+//!   ほかのインスタンスが使っていれば新しいIDにする
+//! - replace: 今のインスタンスをすべて削除して置き換える
+//!
+//! `DependsOn` はファイルのキーで書かれているので、読み込んだ後のキーに書き換える（ファイルにないIDは知らせる）。
+//!
+//! パスと `WinePrefix` は `C:\=E:\` のような置き換えの規則（[`PathRule`]）で書き換えられる。
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigFile, InstanceConfig, InstanceOptions};
+use crate::instance::{self, is_instance_key, is_option_of};
+use crate::{paths, t};
+
+/// 必ず書く列
+const FIXED_COLUMNS: [&str; 4] = ["Key", "Enabled", "Name", "Path"];
+
+/// UTF-8 の BOM（Excelが文字コードを判別するのに使う）
+const BOM: char = '\u{feff}';
+
+/// 一覧のファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Csv,
+    Json,
+}
+
+impl ListFormat {
+    /// 拡張子（.csv / .json）から決める
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(ListFormat::Csv),
+            "json" => Some(ListFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// JSON の形（設定ファイルの JSON 形式と同じく `Instances` に並べる）
+#[derive(Debug, Serialize, Deserialize)]
+struct InstanceList {
+    #[serde(rename = "Instances", default)]
+    instances: Vec<InstanceConfig>,
+}
+
+/// 設定のインスタンスの定義（番号順。形式や値が正しくなければエラー）
+pub fn instance_configs(config: &HashMap<String, String>) -> Result<Vec<InstanceConfig>, String> {
+    let instances: HashMap<String, String> = config
+        .iter()
+        .filter(|(k, _)| k.starts_with("MT_"))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    Ok(ConfigFile::from_map(&instances)?.instances)
+}
+
+/// インスタンスの一覧を書き出す
+pub fn export(instances: &[InstanceConfig], format: ListFormat) -> Result<String, String> {
+    match format {
+        ListFormat::Csv => to_csv(instances),
+        ListFormat::Json => serde_json::to_string_pretty(&InstanceList {
+            instances: instances.to_vec(),
+        })
+        .map(|json| json + "\n")
+        .map_err(|e| e.to_string()),
+    }
+}
+
+/// 書き出した一覧を読む
+pub fn parse(format: ListFormat, content: &str) -> Result<Vec<InstanceConfig>, String> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    match format {
+        ListFormat::Csv => from_csv(content),
+        ListFormat::Json => serde_json::from_str::<InstanceList>(content)
+            .map(|list| list.instances)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+fn to_csv(instances: &[InstanceConfig]) -> Result<String, String> {
+    let rows: Vec<BTreeMap<String, String>> = instances
+        .iter()
+        .map(|i| i.options.to_values().into_iter().collect())
+        .collect();
+    // 使われている追加項目だけを列にする（型付きの項目は決まった順、それ以外は名前順）
+    let mut columns: Vec<&str> = InstanceOptions::KEYS
+        .iter()
+        .copied()
+        .filter(|key| rows.iter().any(|row| row.contains_key(*key)))
+        .collect();
+    for row in &rows {
+        for key in row.keys() {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_error = |e: csv::Error| e.to_string();
+    writer
+        .write_record(FIXED_COLUMNS.iter().chain(columns.iter()))
+        .map_err(csv_error)?;
+    for (instance, row) in instances.iter().zip(&rows) {
+        let enabled = if instance.enabled { "ON" } else { "OFF" };
+        let mut record = vec![instance.key.as_str(), enabled, &instance.name, &instance.path];
+        record.extend(columns.iter().map(|c| row.get(*c).map(|v| v.as_str()).unwrap_or("")));
+        writer.write_record(&record).map_err(csv_error)?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    let text = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+    Ok(format!("{}{}", BOM, text))
+}
+
+fn from_csv(content: &str) -> Result<Vec<InstanceConfig>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::Headers)
+        .from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    for column in ["Name", "Path"] {
+        if !headers.iter().any(|h| h == column) {
+            return Err(t!("transfer.missing_column", column = column));
+        }
+    }
+
+    let mut instances = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = record.position().map_or(0, |p| p.line());
+        let mut values: BTreeMap<String, String> = headers
+            .iter()
+            .zip(record.iter())
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(column, value)| (column.to_string(), value.to_string()))
+            .collect();
+        let row_error = |error: String| t!("transfer.invalid_row", line = line, error = error);
+
+        let key = values.remove("Key").map(|k| k.trim().to_string()).unwrap_or_default();
+        let enabled = match values.remove("Enabled") {
+            None => true,
+            Some(value) => match value.trim().to_uppercase().as_str() {
+                "ON" | "TRUE" | "1" => true,
+                "OFF" | "FALSE" | "0" => false,
+                _ => return Err(row_error(t!("config.invalid_value", key = "Enabled", value = value))),
+            },
+        };
+        let name = values.remove("Name").unwrap_or_default();
+        let path = values.remove("Path").unwrap_or_default();
+        let options = InstanceOptions::from_values(values, "").map_err(row_error)?;
+        instances.push(InstanceConfig {
+            key,
+            enabled,
+            name,
+            path,
+            options,
+        });
+    }
+    Ok(instances)
+}
+
+/// パスの置き換えの規則（`C:\=E:\` なら `C:\` で始まるパスを `E:\` で始まるようにする）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRule {
+    pub from: String,
+    pub to: String,
+}
+
+impl PathRule {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.split_once('=') {
+            Some((from, to)) if !from.trim().is_empty() => Ok(PathRule {
+                from: from.trim().to_string(),
+                to: to.trim().to_string(),
+            }),
+            _ => Err(t!("transfer.invalid_rule", rule = text)),
+        }
+    }
+
+    /// 前方が一致したら置き換える（大文字・小文字は区別しない）
+    fn apply(&self, path: &str) -> Option<String> {
+        let head = path.get(..self.from.len())?;
+        head.eq_ignore_ascii_case(&self.from)
+            .then(|| format!("{}{}", self.to, &path[self.from.len()..]))
+    }
+}
+
+/// 最初に当てはまった規則でパスを置き換える
+pub fn remap(path: &str, rules: &[PathRule]) -> String {
+    rules
+        .iter()
+        .find_map(|rule| rule.apply(path))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// 読み込み方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// 同じパスのものは上書きし、それ以外は追加する
+    Merge,
+    /// 今のインスタンスをすべて置き換える
+    Replace,
+}
+
+/// 読み込んだ結果（書き込む前に表示する）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportPlan {
+    /// 追加するインスタンス（キー・表示名）
+    pub added: Vec<(String, String)>,
+    /// 上書きするインスタンス（キー・表示名）
+    pub updated: Vec<(String, String)>,
+    /// 削除するインスタンス（replace のとき）
+    pub removed: Vec<(String, String)>,
+    /// ファイルのIDが使われていたため変えたもの（ファイルのキー・新しいキー）
+    pub renumbered: Vec<(String, String)>,
+    /// 書き込む前に知らせること（ファイルにない依存先など）
+    pub warnings: Vec<String>,
+    /// 読み込んだ後の設定
+    pub config: HashMap<String, String>,
+}
+
+/// 読み込んだ一覧を設定に当てた結果を作る（ファイルの内容が正しくなければエラー）
+pub fn plan_import(
+    config: &HashMap<String, String>,
+    imported: Vec<InstanceConfig>,
+    mode: ImportMode,
+    rules: &[PathRule],
+) -> Result<ImportPlan, String> {
+    // 先に全体を確かめる
+    let mut imported = imported;
+    let mut seen = Vec::new();
+    for instance in &mut imported {
+        if !instance.key.is_empty() {
+            instance.key = instance::parse_instance_id(&instance.key)
+                .ok_or_else(|| t!("config.invalid_key", key = instance.key))?;
+            if seen.contains(&instance.key) {
+                return Err(t!("config.duplicate_key", key = instance.key));
+            }
+            seen.push(instance.key.clone());
+        }
+        for (field, value) in [("Name", &instance.name), ("Path", &instance.path)] {
+            if value.trim().is_empty() {
+                return Err(t!("transfer.missing_field", key = instance.key, field = field));
+            }
+        }
+    }
+    // IDのあるものを先に置き、IDのないものはその後の番号にする
+    imported.sort_by_key(|i| i.key.is_empty());
+
+    let mut plan = ImportPlan {
+        config: config.clone(),
+        ..ImportPlan::default()
+    };
+    if mode == ImportMode::Replace {
+        for existing in instance_keys(config) {
+            plan.removed.push((existing.clone(), name_of(config, &existing)));
+            remove_instance(&mut plan.config, &existing);
+        }
+    }
+
+    // ファイルのキーから読み込んだ後のキーへ（上書き・番号の振り直しで変わる）
+    let mut moved = HashMap::new();
+    let mut placed = Vec::new();
+    for mut instance in imported {
+        instance.path = remap(&instance.path, rules);
+        if let Some(prefix) = instance.options.wine_prefix.as_mut() {
+            *prefix = remap(prefix, rules);
+        }
+
+        let same_path = match mode {
+            ImportMode::Merge => instance_keys(&plan.config)
+                .into_iter()
+                .find(|key| path_of(&plan.config, key).is_some_and(|p| paths::same_path(p, &instance.path))),
+            ImportMode::Replace => None,
+        };
+        let key = match same_path {
+            Some(key) => {
+                plan.updated.push((key.clone(), instance.name.clone()));
+                remove_instance(&mut plan.config, &key);
+                key
+            }
+            None => {
+                let wanted = Some(instance.key.clone()).filter(|k| !k.is_empty());
+                let key = match &wanted {
+                    Some(key) if !plan.config.contains_key(key) => key.clone(),
+                    _ => instance::next_key(&plan.config),
+                };
+                if let Some(wanted) = wanted.filter(|w| *w != key) {
+                    plan.renumbered.push((wanted, key.clone()));
+                }
+                plan.added.push((key.clone(), instance.name.clone()));
+                key
+            }
+        };
+        if !instance.key.is_empty() {
+            moved.insert(instance.key.clone(), key.clone());
+        }
+        // 後のインスタンスが同じ番号を取らないよう、中身を書くまで取っておく
+        plan.config.insert(key.clone(), String::new());
+        instance.key = key;
+        placed.push(instance);
+    }
+
+    for mut instance in placed {
+        // 依存先もファイルのキーで書かれているので、読み込んだ後のキーに合わせる
+        if let Some(depends_on) = instance.options.depends_on.as_mut() {
+            let rewritten: Vec<String> = instance::split_list(depends_on)
+                .into_iter()
+                .map(|id| {
+                    let found = instance::parse_instance_id(&id).and_then(|k| moved.get(&k));
+                    match found {
+                        Some(key) => key.clone(),
+                        None => {
+                            plan.warnings
+                                .push(t!("transfer.unknown_dependency", key = instance.key, id = id));
+                            id
+                        }
+                    }
+                })
+                .collect();
+            *depends_on = rewritten.join(",");
+        }
+        let entries = ConfigFile {
+            instances: vec![instance],
+            ..ConfigFile::default()
+        }
+        .to_map()?;
+        plan.config.extend(entries);
+    }
+
+    // 書き込む前に、できあがったインスタンスの定義が読めることを確かめる
+    instance_configs(&plan.config)?;
+    Ok(plan)
+}
+
+/// インスタンスのキー（番号順）
+fn instance_keys(config: &HashMap<String, String>) -> Vec<String> {
+    let mut keys: Vec<String> = config.keys().filter(|k| is_instance_key(k)).cloned().collect();
+    keys.sort_by(|a, b| instance::key_order(a).cmp(&instance::key_order(b)));
+    keys
+}
+
+fn name_of(config: &HashMap<String, String>, key: &str) -> String {
+    config
+        .get(key)
+        .and_then(|v| v.split('|').nth(1))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn path_of<'a>(config: &'a HashMap<String, String>, key: &str) -> Option<&'a str> {
+    config.get(key).and_then(|v| v.split('|').nth(2))
+}
+
+fn remove_instance(config: &mut HashMap<String, String>, key: &str) {
+    config.remove(key);
+    config.retain(|k, _| !is_option_of(k, key));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_ini;
+
+    fn current() -> HashMap<String, String> {
+        parse_ini(
+            "CheckInterval=30\n\
+             MT_1=ON|XM live|C:\\MT4\\XM\\terminal.exe\n\
+             MT_1.Priority=10\n\
+             MT_2=OFF|Demo|C:\\MT4\\Demo\\terminal.exe\n",
+        )
+    }
+
+    #[test]
+    fn csv_round_trips_with_quoting() {
+        let mut config = current();
+        config.insert("MT_2.Args".to_string(), "/portable \"/config:my, config.ini\"".to_string());
+        config.insert("MT_2.Tags".to_string(), "demo,broker:XM".to_string());
+        let instances = instance_configs(&config).unwrap();
+
+        let csv = export(&instances, ListFormat::Csv).unwrap();
+        assert!(csv.starts_with('\u{feff}'));
+        assert_eq!(csv.lines().next().unwrap(), "\u{feff}Key,Enabled,Name,Path,Args,Priority,Tags");
+        assert_eq!(parse(ListFormat::Csv, &csv).unwrap(), instances);
+
+        let json = export(&instances, ListFormat::Json).unwrap();
+        assert_eq!(parse(ListFormat::Json, &json).unwrap(), instances);
+    }
+
+    #[test]
+    fn merge_updates_same_paths_and_renumbers_taken_ids() {
+        let csv = "Key,Enabled,Name,Path,Priority\n\
+                   MT_1,ON,XM live,D:\\MT4\\XM\\terminal.exe,20\n\
+                   MT_2,ON,OANDA,D:\\MT4\\OANDA\\terminal.exe,\n\
+                   ,OFF,Spare,D:\\MT4\\Spare\\terminal.exe,\n";
+        let imported = parse(ListFormat::Csv, csv).unwrap();
+        let rules = [PathRule::parse(r"d:\=C:\").unwrap()];
+        let plan = plan_import(&current(), imported, ImportMode::Merge, &rules).unwrap();
+
+        assert_eq!(plan.updated, [("MT_1".to_string(), "XM live".to_string())]);
+        assert_eq!(plan.renumbered, [("MT_2".to_string(), "MT_3".to_string())]);
+        assert_eq!(
+            plan.added.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            ["MT_3", "MT_4"]
+        );
+        assert_eq!(plan.config["MT_1"], "1|XM live|C:\\MT4\\XM\\terminal.exe");
+        assert_eq!(plan.config["MT_1.Priority"], "20");
+        assert_eq!(plan.config["MT_2"], "0|Demo|C:\\MT4\\Demo\\terminal.exe");
+        assert_eq!(plan.config["MT_3"], "1|OANDA|C:\\MT4\\OANDA\\terminal.exe");
+        assert_eq!(plan.config["MT_4"], "0|Spare|C:\\MT4\\Spare\\terminal.exe");
+        assert_eq!(plan.config["CheckInterval"], "30");
+    }
+
+    #[test]
+    fn merge_rewrites_dependencies_of_renumbered_instances() {
+        let csv = "Key,Enabled,Name,Path,DependsOn\n\
+                   MT_7,ON,XM live,C:\\MT4\\XM\\terminal.exe,\n\
+                   MT_2,ON,OANDA,D:\\MT4\\OANDA\\terminal.exe,\"mt_7, 9\"\n\
+                   ,ON,Spare,D:\\MT4\\Spare\\terminal.exe,2\n";
+        let imported = parse(ListFormat::Csv, csv).unwrap();
+        let plan = plan_import(&current(), imported, ImportMode::Merge, &[]).unwrap();
+
+        // MT_7 は同じパスの MT_1 に、MT_2 は MT_3 になる
+        assert_eq!(plan.updated, [("MT_1".to_string(), "XM live".to_string())]);
+        assert_eq!(plan.renumbered, [("MT_2".to_string(), "MT_3".to_string())]);
+        assert_eq!(plan.config["MT_3.DependsOn"], "MT_1,9");
+        assert_eq!(plan.config["MT_4.DependsOn"], "MT_3");
+        // ファイルにない依存先はそのまま残して知らせる
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("MT_3") && plan.warnings[0].contains('9'));
+    }
+
+    #[test]
+    fn replace_removes_everything_else() {
+        let json = r#"{"Instances": [{"Key": "MT_5", "Name": "XM", "Path": "E:\\XM\\terminal.exe", "Interval": 10}]}"#;
+        let imported = parse(ListFormat::Json, json).unwrap();
+        let plan = plan_import(&current(), imported, ImportMode::Replace, &[]).unwrap();
+        assert_eq!(plan.removed.len(), 2);
+        assert!(!plan.config.keys().any(|k| k.starts_with("MT_1") || k.starts_with("MT_2")));
+        assert_eq!(plan.config["MT_5"], "1|XM|E:\\XM\\terminal.exe");
+        assert_eq!(plan.config["MT_5.Interval"], "10");
+    }
+
+    #[test]
+    fn invalid_files_are_rejected_before_writing() {
+        let config = current();
+        let plan = |csv: &str| plan_import(&config, parse(ListFormat::Csv, csv)?, ImportMode::Merge, &[]);
+        // 同じIDが2回
+        assert!(plan("Key,Name,Path\nMT_3,A,a.exe\nmt_3,B,b.exe\n").is_err());
+        // 数値の項目に読めない値
+        assert!(plan("Name,Path,Interval\nA,a.exe,often\n").is_err());
+        // パスがない・パスに区切り文字
+        assert!(plan("Name,Path\nA,\n").is_err());
+        assert!(plan("Name,Path\nA,a|b.exe\n").is_err());
+        assert!(plan("Name\nA\n").is_err());
+        assert!(PathRule::parse(r"=E:\").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Wine上で動くMT4/MT5のためのヘルパー
+//!
+//! Linux VPSではterminal.exeを `wine terminal.exe` として起動する。
+//! 起動したプロセスの実行ファイルは wine-preloader になるため、
+//! 判定はコマンドライン（argv[0]）と環境変数 WINEPREFIX で行う。
+
+use std::path::{Path, PathBuf};
+
+/// 既定のwine実行ファイル
+pub const DEFAULT_WINE_BINARY: &str = "wine";
+
+/// WINEPREFIX未指定時の既定プレフィックス（~/.wine）
+pub fn default_prefix() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".wine"))
+}
+
+/// `C:\...` 形式のパスをプレフィックス内のUnixパスに変換する
+///
+/// すでにUnixパスであればそのまま返す。
+pub fn to_unix_path(prefix: Option<&Path>, path: &str) -> PathBuf {
+    let path = path.trim();
+    let bytes = path.as_bytes();
+    let is_drive_path = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if !is_drive_path {
+        return PathBuf::from(path);
+    }
+
+    let drive = (bytes[0] as char).to_ascii_lowercase();
+    let rest: Vec<&str> = path[2..]
+        .split(['\\', '/'])
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    // Z: はルートファイルシステムに割り当てられている
+    let mut unix = if drive == 'z' {
+        PathBuf::from("/")
+    } else {
+        let prefix = prefix.map(Path::to_path_buf).or_else(default_prefix).unwrap_or_default();
+        prefix.join(format!("drive_{}", drive))
+    };
+    for part in rest {
+        unix.push(part);
+    }
+    unix
+}
+
+/// Wineプロセスのコマンドラインに現れうるパスの候補
+///
+/// 設定されたパスそのもの、`drive_c` 配下なら `C:\...`、
+/// それ以外のUnixパスなら `Z:\...` の形を返す。
+pub fn path_candidates(path: &str) -> Vec<String> {
+    let path = path.trim();
+    let mut candidates = vec![path.to_string()];
+
+    if path.starts_with('/') {
+        // to_lowercase は ASCII 以外で長さが変わることがあるため、位置がずれない ASCII だけを小文字にする
+        let lower = path.to_ascii_lowercase();
+        if let Some(pos) = lower.find("/drive_c/") {
+            candidates.push(format!(r"C:\{}", path[pos + "/drive_c/".len()..].replace('/', "\\")));
+        }
+        candidates.push(format!("Z:{}", path.replace('/', "\\")));
+    }
+
+    candidates
+}
+
+/// プロセスの環境変数からWINEPREFIXを取り出す
+pub fn prefix_from_environ(environ: &[String]) -> Option<String> {
+    environ
+        .iter()
+        .find_map(|var| var.strip_prefix("WINEPREFIX="))
+        .map(|s| s.to_string())
+}
+
+/// MT4/MT5がインストールされていそうなWineプレフィックスを列挙する
+///
+/// `$WINEPREFIX`、`~/.wine`、`~/.wine*`、MetaQuotes公式スクリプトが使う
+/// `~/.mt4` / `~/.mt5` のうち、drive_c を持つものを返す。
+pub fn find_prefixes() -> Vec<PathBuf> {
+    let mut prefixes = Vec::new();
+
+    if let Some(prefix) = std::env::var_os("WINEPREFIX") {
+        prefixes.push(PathBuf::from(prefix));
+    }
+
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        if let Ok(entries) = std::fs::read_dir(&home) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                if name.starts_with(".wine") || name == ".mt4" || name == ".mt5" {
+                    prefixes.push(entry.path());
+                }
+            }
+        }
+    }
+
+    prefixes.sort();
+    prefixes.dedup();
+    prefixes.retain(|p| p.join("drive_c").is_dir());
+    prefixes
+}
+
+/// プレフィックス内でMT4/MT5を探すディレクトリ
+pub fn search_dirs(prefix: &Path) -> Vec<PathBuf> {
+    let drive_c = prefix.join("drive_c");
+    let mut dirs = vec![
+        drive_c.join("Program Files (x86)"),
+        drive_c.join("Program Files"),
+    ];
+
+    if let Ok(entries) = std::fs::read_dir(drive_c.join("users")) {
+        for entry in entries.flatten() {
+            dirs.push(entry.path().join("AppData").join("Roaming"));
+        }
+    }
+
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drive_paths_map_into_the_prefix() {
+        assert_eq!(
+            to_unix_path(Some(Path::new("/home/fx/.mt4")), r"C:\Program Files\MT4\terminal.exe"),
+            PathBuf::from("/home/fx/.mt4/drive_c/Program Files/MT4/terminal.exe")
+        );
+        assert_eq!(
+            to_unix_path(None, r"Z:\opt\mt4\terminal.exe"),
+            PathBuf::from("/opt/mt4/terminal.exe")
+        );
+        assert_eq!(
+            to_unix_path(None, "/opt/mt4/terminal.exe"),
+            PathBuf::from("/opt/mt4/terminal.exe")
+        );
+    }
+
+    #[test]
+    fn candidates_cover_wine_drive_forms() {
+        let candidates = path_candidates("/home/fx/.wine/drive_c/Program Files/MT4/terminal.exe");
+        assert!(candidates.contains(&r"C:\Program Files\MT4\terminal.exe".to_string()));
+        assert!(candidates.contains(
+            &r"Z:\home\fx\.wine\drive_c\Program Files\MT4\terminal.exe".to_string()
+        ));
+    }
+
+    #[test]
+    fn non_ascii_paths_keep_their_drive_c_form() {
+        // İ は小文字にすると長さが変わる
+        let candidates = path_candidates("/home/İİ/.wine/Drive_C/MT4/terminal.exe");
+        assert!(candidates.contains(&r"C:\MT4\terminal.exe".to_string()), "{:?}", candidates);
+    }
+
+    #[test]
+    fn prefix_is_read_from_environ() {
+        let environ = vec!["HOME=/home/fx".to_string(), "WINEPREFIX=/home/fx/.mt5".to_string()];
+        assert_eq!(prefix_from_environ(&environ).as_deref(), Some("/home/fx/.mt5"));
+        assert_eq!(prefix_from_environ(&[]), None);
+    }
+}
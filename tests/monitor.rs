@@ -0,0 +1,906 @@
+//! 監視エンジンの動作確認（プロセス一覧・起動・時計はすべて偽物）
+
+use std::collections::HashMap;
+
+use chrono::{Duration, TimeZone, Utc};
+use metaguard::clock::{Clock, FakeClock};
+use metaguard::instance::{parse_instances, MtInstance};
+use metaguard::launcher::FakeLauncher;
+use metaguard::monitor::{EventKind, InstanceState, Monitor, RestartPolicy};
+use metaguard::process::FakeProcessTable;
+use metaguard::resources::ResourceUsage;
+use metaguard::state;
+use metaguard::stop::{self, MonitorRequest, StopResult};
+
+const XM: &str = r"C:\Program Files\XM MT4\terminal.exe";
+const DEMO: &str = r"D:\MT4_Demo\terminal.exe";
+
+struct Harness {
+    table: FakeProcessTable,
+    launcher: FakeLauncher,
+    clock: FakeClock,
+    monitor: Monitor<FakeProcessTable, FakeLauncher, FakeClock>,
+}
+
+fn harness() -> Harness {
+    let table = FakeProcessTable::new();
+    let launcher = FakeLauncher::new(table.clone());
+    let clock = FakeClock::new(Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap());
+    let monitor = Monitor::new(table.clone(), launcher.clone(), clock.clone());
+    Harness {
+        table,
+        launcher,
+        clock,
+        monitor,
+    }
+}
+
+fn instances(entries: &[(&str, &str)]) -> Vec<MtInstance> {
+    let config: HashMap<String, String> = entries
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    parse_instances(&config)
+}
+
+fn kinds(events: &[metaguard::monitor::MonitorEvent], key: &str) -> Vec<EventKind> {
+    events
+        .iter()
+        .filter(|e| e.key == key)
+        .map(|e| e.kind.clone())
+        .collect()
+}
+
+#[test]
+fn running_terminal_is_left_alone() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+    assert!(h.launcher.launched().is_empty());
+}
+
+#[test]
+fn path_spelling_does_not_cause_a_duplicate_launch() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[("MT_1", r"1|XM|c:/program files//xm mt4/TERMINAL.EXE ")]);
+
+    h.monitor.check(&list);
+    assert!(h.launcher.launched().is_empty());
+}
+
+#[test]
+fn stopped_terminal_is_launched_once_and_then_seen_running() {
+    let mut h = harness();
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+
+    let events = h.monitor.check(&list);
+    let pid = match kinds(&events, "MT_1").as_slice() {
+        [EventKind::Stopped, EventKind::Launched { pid }] => *pid,
+        other => panic!("unexpected events: {:?}", other),
+    };
+
+    h.clock.advance(Duration::seconds(30));
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid }]);
+    assert_eq!(h.launcher.launched(), vec!["MT_1"]);
+}
+
+#[test]
+fn disabled_terminals_are_not_launched() {
+    let mut h = harness();
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_2", &format!("0|Demo|{}", DEMO)),
+    ]);
+
+    let events = h.monitor.check(&list);
+    assert!(kinds(&events, "MT_2").is_empty());
+    assert_eq!(h.launcher.launched(), vec!["MT_1"]);
+    assert!(h.monitor.runtime("MT_2").is_none());
+}
+
+#[test]
+fn restart_counts_survive_a_metaguard_restart() {
+    let mut h = harness();
+    let policy = RestartPolicy {
+        max_restarts_per_hour: 2,
+        ..RestartPolicy::default()
+    };
+    h.monitor.set_policy(policy.clone());
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+    ]);
+
+    h.monitor.check(&list);
+    for _ in 0..2 {
+        h.clock.advance(Duration::seconds(10));
+        h.monitor.check(&list);
+        h.table.kill_path(DEMO);
+        h.clock.advance(Duration::seconds(10));
+        h.monitor.check(&list);
+    }
+    let saved = state::parse_snapshot(&state::render_snapshot(&h.monitor.statuses(), "check"));
+
+    // 新しいMetaGuardが状態ファイルを読み込んだ場合
+    let launcher = FakeLauncher::new(h.table.clone());
+    let mut monitor = Monitor::new(h.table.clone(), launcher.clone(), h.clock.clone());
+    monitor.set_policy(policy);
+    monitor.restore(&saved);
+    h.clock.advance(Duration::seconds(10));
+    let events = monitor.check(&list);
+
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+    assert!(matches!(
+        kinds(&events, "MT_2").as_slice(),
+        [.., EventKind::CrashLoop { restarts: 2 }]
+    ));
+    assert!(launcher.launched().is_empty());
+    // 実行中になった時刻も引き継ぐ
+    assert_eq!(
+        monitor.runtime("MT_1").unwrap().state,
+        InstanceState::Running {
+            pid: 500,
+            since: Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap()
+        }
+    );
+}
+
+#[test]
+fn crashed_terminal_is_restarted() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+    h.monitor.check(&list);
+
+    h.table.kill(500);
+    h.clock.advance(Duration::seconds(30));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Stopped, EventKind::Launched { .. }]
+    ));
+}
+
+#[test]
+fn slow_start_is_not_launched_twice_before_the_timeout() {
+    let mut h = harness();
+    h.launcher.silent("MT_1", true);
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+
+    h.monitor.check(&list);
+    h.clock.advance(Duration::seconds(30));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::WaitingForStart { .. }]
+    ));
+    assert_eq!(h.launcher.launched().len(), 1);
+
+    // タイムアウト後は再起動する
+    h.clock.advance(Duration::seconds(31));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::StartTimedOut { .. }, EventKind::Launched { .. }]
+    ));
+    assert_eq!(h.launcher.launched().len(), 2);
+}
+
+#[test]
+fn repeated_launch_failures_back_off() {
+    let mut h = harness();
+    h.launcher.fail("MT_1", true);
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Stopped, EventKind::LaunchFailed { .. }]
+    ));
+
+    // 1回目の失敗後は10秒で再試行、2回目は20秒待つ
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Stopped, EventKind::LaunchFailed { .. }, EventKind::BackingOff { .. }]
+    ));
+
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check(&list);
+    assert!(matches!(kinds(&events, "MT_1").as_slice(), [EventKind::BackingOff { .. }]));
+
+    h.launcher.fail("MT_1", false);
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Stopped, EventKind::Launched { .. }]
+    ));
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().consecutive_failures, 2);
+
+    h.clock.advance(Duration::seconds(10));
+    h.monitor.check(&list);
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().consecutive_failures, 0);
+}
+
+#[test]
+fn crash_loop_stops_restarting_until_the_window_passes() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        max_restarts_per_hour: 2,
+        ..RestartPolicy::default()
+    });
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+
+    for _ in 0..2 {
+        h.monitor.check(&list);
+        h.clock.advance(Duration::seconds(10));
+        h.monitor.check(&list);
+        h.table.kill_path(XM);
+        h.clock.advance(Duration::seconds(10));
+    }
+
+    let events = h.monitor.check(&list);
+    assert_eq!(
+        kinds(&events, "MT_1"),
+        vec![EventKind::Stopped, EventKind::CrashLoop { restarts: 2 }]
+    );
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().state, InstanceState::CrashLoop);
+    assert_eq!(h.launcher.launched().len(), 2);
+
+    h.clock.advance(Duration::hours(1));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Stopped, EventKind::Launched { .. }]
+    ));
+}
+
+#[test]
+fn statuses_reflect_the_last_check() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    h.launcher.fail("MT_2", true);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+    ]);
+    h.monitor.check(&list);
+
+    let statuses = h.monitor.statuses();
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses[0].running);
+    assert_eq!(statuses[0].pid, Some(500));
+    assert!(!statuses[1].running);
+    assert!(statuses[1].error.is_some());
+}
+
+#[test]
+fn running_terminals_are_probed_without_a_full_scan() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    h.table.start(600, DEMO);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+    ]);
+
+    h.monitor.check(&list);
+    assert_eq!(h.table.scans(), 1);
+
+    for _ in 0..5 {
+        h.clock.advance(Duration::seconds(10));
+        h.monitor.check(&list);
+    }
+    assert_eq!(h.table.scans(), 1);
+}
+
+#[test]
+fn a_vanished_pid_falls_back_to_one_full_scan() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    h.table.start(600, DEMO);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+    ]);
+    h.monitor.check(&list);
+
+    // 利用者が手動で再起動してPIDが変わった
+    h.table.kill(500);
+    h.table.start(700, XM);
+    h.table.kill(600);
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check(&list);
+
+    assert_eq!(h.table.scans(), 2);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 700 }]);
+    assert!(matches!(
+        kinds(&events, "MT_2").as_slice(),
+        [EventKind::Stopped, EventKind::Launched { .. }]
+    ));
+}
+
+#[test]
+fn exit_of_a_launched_terminal_is_handled_immediately() {
+    let mut h = harness();
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+    h.monitor.check(&list);
+    h.clock.advance(Duration::seconds(10));
+    h.monitor.check(&list);
+
+    h.launcher.exit("MT_1", Some(3));
+    let exit = h.monitor.wait_for_exit(std::time::Duration::ZERO).unwrap();
+    assert_eq!(exit.key, "MT_1");
+
+    let events = h.monitor.handle_exit(&exit, &list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Exited { code: Some(3), .. }, EventKind::Stopped, EventKind::Launched { .. }]
+    ));
+    assert_eq!(h.launcher.launched().len(), 2);
+    assert!(h.monitor.wait_for_exit(std::time::Duration::ZERO).is_none());
+}
+
+#[test]
+fn stale_exit_notices_are_ignored() {
+    let mut h = harness();
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+    h.monitor.check(&list);
+    h.clock.advance(Duration::seconds(10));
+    h.monitor.check(&list);
+
+    // 利用者が手動で再起動した後に、古いPIDの終了通知が届いた
+    h.launcher.exit("MT_1", Some(0));
+    h.table.start(900, XM);
+    h.clock.advance(Duration::seconds(10));
+    h.monitor.check(&list);
+
+    let exit = h.monitor.wait_for_exit(std::time::Duration::ZERO).unwrap();
+    assert!(h.monitor.handle_exit(&exit, &list).is_empty());
+    assert_eq!(h.launcher.launched().len(), 1);
+}
+
+#[test]
+fn exit_right_after_launch_counts_as_a_failed_start() {
+    let mut h = harness();
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+
+    h.monitor.check(&list);
+    h.launcher.exit("MT_1", Some(1));
+    let exit = h.monitor.wait_for_exit(std::time::Duration::ZERO).unwrap();
+    let events = h.monitor.handle_exit(&exit, &list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Exited { .. }, EventKind::Stopped, EventKind::Launched { .. }]
+    ));
+
+    // 2回続けて起動直後に落ちたら待機に入る
+    h.launcher.exit("MT_1", Some(1));
+    let exit = h.monitor.wait_for_exit(std::time::Duration::ZERO).unwrap();
+    let events = h.monitor.handle_exit(&exit, &list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Exited { .. }, EventKind::BackingOff { .. }]
+    ));
+    assert_eq!(h.launcher.launched().len(), 2);
+}
+
+#[test]
+fn each_instance_is_checked_on_its_own_interval() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    h.table.start(600, DEMO);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.Interval", "10"),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+        ("MT_2.Interval", "300"),
+    ]);
+
+    let events = h.monitor.check_due(&list);
+    assert_eq!(kinds(&events, "MT_1").len(), 1);
+    assert_eq!(kinds(&events, "MT_2").len(), 1);
+    assert_eq!(h.monitor.next_due(&list), Some(h.clock.now() + Duration::seconds(10)));
+
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check_due(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+    assert!(kinds(&events, "MT_2").is_empty());
+
+    // 全体のチェックはすべてのインスタンスを確認する
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_2"), vec![EventKind::Running { pid: 600 }]);
+}
+
+#[test]
+fn higher_priority_terminals_are_restarted_first() {
+    let mut h = harness();
+    let list = instances(&[
+        ("MT_1", &format!("1|Demo|{}", DEMO)),
+        ("MT_2", &format!("1|XM|{}", XM)),
+        ("MT_2.Priority", "10"),
+    ]);
+
+    h.monitor.check_due(&list);
+    assert_eq!(h.launcher.launched(), vec!["MT_2", "MT_1"]);
+}
+
+#[test]
+fn pending_start_is_checked_when_the_timeout_expires() {
+    let mut h = harness();
+    h.launcher.silent("MT_1", true);
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM)), ("MT_1.Interval", "300")]);
+    h.monitor.set_policy(RestartPolicy {
+        start_timeout: Duration::seconds(60),
+        ..RestartPolicy::default()
+    });
+
+    h.monitor.check_due(&list);
+    assert_eq!(h.monitor.next_due(&list), Some(h.clock.now() + Duration::seconds(60)));
+}
+
+#[test]
+fn launches_are_staggered() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        launch_stagger: Duration::seconds(20),
+        ..RestartPolicy::default()
+    });
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+    ]);
+
+    let events = h.monitor.check_due(&list);
+    let until = h.clock.now() + Duration::seconds(20);
+    assert_eq!(
+        kinds(&events, "MT_2"),
+        vec![EventKind::Stopped, EventKind::LaunchDelayed { until }]
+    );
+    assert_eq!(h.launcher.launched(), vec!["MT_1"]);
+    assert_eq!(h.monitor.next_due(&list), Some(until));
+
+    h.clock.advance(Duration::seconds(20));
+    h.monitor.check_due(&list);
+    assert_eq!(h.launcher.launched(), vec!["MT_1", "MT_2"]);
+}
+
+#[test]
+fn dependent_terminal_waits_until_its_dependency_runs() {
+    let mut h = harness();
+    let list = instances(&[
+        ("MT_1", &format!("1|Copier|{}", DEMO)),
+        ("MT_1.DependsOn", "MT_2"),
+        ("MT_2", &format!("1|Feed|{}", XM)),
+    ]);
+
+    let events = h.monitor.check(&list);
+    assert_eq!(
+        kinds(&events, "MT_1"),
+        vec![EventKind::Stopped, EventKind::WaitingForDependency { key: "MT_2".to_string() }]
+    );
+    assert_eq!(h.launcher.launched(), vec!["MT_2"]);
+
+    h.clock.advance(Duration::seconds(30));
+    h.monitor.check(&list);
+    assert_eq!(h.launcher.launched(), vec!["MT_2", "MT_1"]);
+    assert!(matches!(
+        h.monitor.runtime("MT_1").unwrap().state,
+        InstanceState::Starting { .. }
+    ));
+}
+
+#[test]
+fn stopped_terminal_is_not_restarted_during_maintenance() {
+    let mut h = harness();
+    // 2026-10-17 は土曜日
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("TimeZone", "UTC"),
+        ("Maintenance", "Sat 00:00-Sat 13:00"),
+    ]);
+
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Suspended { .. }]
+    ));
+    assert!(h.launcher.launched().is_empty());
+
+    h.clock.advance(Duration::hours(1));
+    h.monitor.check(&list);
+    assert_eq!(h.launcher.launched(), vec!["MT_1"]);
+}
+
+#[test]
+fn maintenance_only_reports_a_running_terminal_by_default() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("TimeZone", "UTC"),
+        ("Holidays", "2026-10-17"),
+    ]);
+
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+    assert!(h.launcher.terminated().is_empty());
+}
+
+#[test]
+fn enforce_mode_closes_the_terminal_for_the_window() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.TimeZone", "UTC"),
+        ("MT_1.Blackout", "2026-10-17 11:00~2026-10-17 12:30"),
+        ("MT_1.ScheduleMode", "enforce"),
+    ]);
+
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::StopFinished { outcome }, EventKind::StoppedForSchedule { pid: 500, .. }]
+            if outcome.result == StopResult::Closed
+    ));
+    assert_eq!(h.launcher.terminated(), vec![500]);
+    assert!(h.launcher.killed().is_empty());
+    assert!(h.table.pids().is_empty());
+
+    h.clock.advance(Duration::minutes(30));
+    h.monitor.check(&list);
+    assert_eq!(h.launcher.launched(), vec!["MT_1"]);
+}
+
+#[test]
+fn scheduled_restart_relaunches_without_counting_as_a_crash() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        max_restarts_per_hour: 1,
+        ..RestartPolicy::default()
+    });
+    h.table.start(500, XM);
+    // 2026-10-17（土）12:00 UTC から監視を始め、12:30 に定期再起動する
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.RestartSchedule", "Sat 12:30@UTC"),
+    ]);
+
+    h.monitor.check(&list);
+    assert!(h.launcher.launched().is_empty());
+
+    h.clock.advance(Duration::minutes(30));
+    let events = h.monitor.check(&list);
+    let pid = match kinds(&events, "MT_1").as_slice() {
+        [EventKind::PlannedRestart { pid: 500, .. }, EventKind::StopFinished { .. }, EventKind::Launched { pid }] => *pid,
+        other => panic!("unexpected events: {:?}", other),
+    };
+    assert_eq!(h.launcher.terminated(), vec![500]);
+    assert!(h.monitor.runtime("MT_1").unwrap().restarts.is_empty());
+
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::PlannedRestartCompleted { pid }]);
+
+    // 同じ予定で二度は再起動しない
+    h.clock.advance(Duration::minutes(10));
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid }]);
+}
+
+#[test]
+fn manual_restart_of_a_running_terminal_is_not_counted() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        max_restarts_per_hour: 1,
+        ..RestartPolicy::default()
+    });
+    h.table.start(500, XM);
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+    h.monitor.check(&list);
+
+    // 上限の1回を超えて再起動しても、クラッシュループにはならない
+    let mut current = 500;
+    for _ in 0..2 {
+        h.clock.advance(Duration::minutes(1));
+        let events = h.monitor.restart("MT_1", &list);
+        current = match kinds(&events, "MT_1").as_slice() {
+            [EventKind::ManualRestart { pid: Some(old) }, EventKind::StopFinished { .. }, EventKind::Launched { pid }]
+                if *old == current => *pid,
+            other => panic!("unexpected events: {:?}", other),
+        };
+    }
+    assert_eq!(h.launcher.terminated().len(), 2);
+    assert!(h.monitor.runtime("MT_1").unwrap().restarts.is_empty());
+
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::PlannedRestartCompleted { pid: current }]);
+}
+
+#[test]
+fn manual_restart_launches_a_crash_looped_terminal_at_once() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        max_restarts_per_hour: 2,
+        ..RestartPolicy::default()
+    });
+    let list = instances(&[("MT_1", &format!("1|XM|{}", XM))]);
+    for _ in 0..2 {
+        h.monitor.check(&list);
+        h.clock.advance(Duration::seconds(10));
+        h.monitor.check(&list);
+        h.table.kill_path(XM);
+        h.clock.advance(Duration::seconds(10));
+    }
+    h.monitor.check(&list);
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().state, InstanceState::CrashLoop);
+
+    let events = h.monitor.restart("MT_1", &list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::ManualRestart { pid: None }, EventKind::Launched { .. }]
+    ));
+    assert_eq!(h.launcher.launched().len(), 3);
+    assert!(matches!(
+        h.monitor.runtime("MT_1").unwrap().state,
+        InstanceState::Starting { .. }
+    ));
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().consecutive_failures, 0);
+}
+
+#[test]
+fn requests_sent_while_taking_are_not_lost() {
+    let dir = std::env::temp_dir().join(format!("metaguard-requests-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let requests = stop::request_dir_for(&dir.join("MetaGuard.ini"));
+    let keys: Vec<String> = (1..=50).map(|n| format!("MT_{}", n)).collect();
+
+    let sender = {
+        let requests = requests.clone();
+        let keys = keys.clone();
+        std::thread::spawn(move || {
+            for key in keys {
+                stop::send_requests(&requests, &[MonitorRequest::Restart(key)]).unwrap();
+            }
+        })
+    };
+    let mut taken = Vec::new();
+    while !sender.is_finished() {
+        taken.extend(stop::take_requests(&requests).unwrap());
+    }
+    sender.join().unwrap();
+    taken.extend(stop::take_requests(&requests).unwrap());
+
+    let expected: Vec<MonitorRequest> = keys.into_iter().map(MonitorRequest::Restart).collect();
+    assert_eq!(taken, expected);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn terminal_that_ignores_the_close_request_is_killed_after_the_timeout() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    h.launcher.hang(500);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.RestartSchedule", "Sat 12:30@UTC"),
+        ("MT_1.StopTimeout", "20"),
+    ]);
+
+    h.monitor.check(&list);
+    h.clock.advance(Duration::minutes(30));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::PlannedRestart { pid: 500, .. }, EventKind::WaitingForStop { pid: 500 }]
+    ));
+    assert_eq!(h.launcher.terminated(), vec![500]);
+    // 終了待ちの間は短い間隔で確認する
+    assert_eq!(
+        h.monitor.next_due(&list),
+        Some(h.clock.now() + Duration::seconds(2))
+    );
+
+    h.clock.advance(Duration::seconds(10));
+    h.monitor.check_due(&list);
+    assert!(h.launcher.killed().is_empty());
+
+    h.clock.advance(Duration::seconds(10));
+    let events = h.monitor.check_due(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::WaitingForStop { pid: 500 }]);
+    assert_eq!(h.launcher.killed(), vec![500]);
+
+    h.clock.advance(Duration::seconds(2));
+    let events = h.monitor.check_due(&list);
+    match kinds(&events, "MT_1").as_slice() {
+        [EventKind::StopFinished { outcome }, EventKind::Launched { .. }] => {
+            assert_eq!(outcome.result, StopResult::Killed);
+            assert_eq!(outcome.waited, Duration::seconds(22));
+        }
+        other => panic!("unexpected events: {:?}", other),
+    }
+}
+
+#[test]
+fn restart_times_before_monitoring_started_are_skipped() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.RestartSchedule", "Sat 11:00@UTC"),
+    ]);
+
+    h.monitor.check(&list);
+    h.clock.advance(Duration::minutes(5));
+    h.monitor.check(&list);
+    assert!(h.launcher.terminated().is_empty());
+}
+
+#[test]
+fn sustained_memory_breach_restarts_the_terminal() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.MaxMemoryMB", "1500"),
+        ("MT_1.ThresholdMinutes", "5"),
+        ("MT_1.ThresholdAction", "restart"),
+    ]);
+    let bloated = ResourceUsage {
+        memory_mb: 1834,
+        ..ResourceUsage::default()
+    };
+    h.table.set_usage(500, bloated);
+
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+
+    h.clock.advance(Duration::minutes(5));
+    let events = h.monitor.check(&list);
+    match kinds(&events, "MT_1").as_slice() {
+        [EventKind::Running { pid: 500 }, EventKind::ThresholdExceeded {
+            pid: 500,
+            usage,
+            restart: true,
+            ..
+        }, EventKind::StopFinished { .. }, EventKind::Launched { .. }] => assert_eq!(*usage, bloated),
+        other => panic!("unexpected events: {:?}", other),
+    }
+    assert_eq!(h.launcher.terminated(), vec![500]);
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().restarts.len(), 1);
+}
+
+#[test]
+fn threshold_restarts_count_towards_the_crash_loop_limit() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        max_restarts_per_hour: 2,
+        ..RestartPolicy::default()
+    });
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.MaxMemoryMB", "1500"),
+        ("MT_1.ThresholdMinutes", "5"),
+        ("MT_1.ThresholdAction", "restart"),
+    ]);
+    // 起動し直しても使用量は上限を超えたまま
+    let bloated = ResourceUsage {
+        memory_mb: 1834,
+        ..ResourceUsage::default()
+    };
+    let mut pid = 500;
+    h.table.set_usage(pid, bloated);
+    h.monitor.check(&list);
+
+    for _ in 0..2 {
+        h.clock.advance(Duration::minutes(5));
+        let events = h.monitor.check(&list);
+        pid = match kinds(&events, "MT_1").as_slice() {
+            [EventKind::Running { .. }, EventKind::ThresholdExceeded { restart: true, .. }, EventKind::StopFinished { .. }, EventKind::Launched { pid }] => *pid,
+            other => panic!("unexpected events: {:?}", other),
+        };
+        h.table.set_usage(pid, bloated);
+        h.clock.advance(Duration::seconds(10));
+        h.monitor.check(&list);
+    }
+
+    h.clock.advance(Duration::minutes(5));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Running { .. }, EventKind::ThresholdExceeded { restart: true, .. }, EventKind::StopFinished { .. }, EventKind::CrashLoop { restarts: 2 }]
+    ));
+    assert_eq!(h.monitor.runtime("MT_1").unwrap().state, InstanceState::CrashLoop);
+    assert_eq!(h.launcher.launched().len(), 2);
+
+    h.clock.advance(Duration::minutes(5));
+    h.monitor.check(&list);
+    assert_eq!(h.launcher.launched().len(), 2);
+}
+
+#[test]
+fn threshold_restarts_are_staggered() {
+    let mut h = harness();
+    h.monitor.set_policy(RestartPolicy {
+        launch_stagger: Duration::seconds(20),
+        ..RestartPolicy::default()
+    });
+    h.table.start(500, XM);
+    h.table.start(501, DEMO);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.MaxMemoryMB", "1500"),
+        ("MT_1.ThresholdMinutes", "1"),
+        ("MT_1.ThresholdAction", "restart"),
+        ("MT_2", &format!("1|Demo|{}", DEMO)),
+        ("MT_2.MaxMemoryMB", "1500"),
+        ("MT_2.ThresholdMinutes", "1"),
+        ("MT_2.ThresholdAction", "restart"),
+    ]);
+    let bloated = ResourceUsage {
+        memory_mb: 1834,
+        ..ResourceUsage::default()
+    };
+    h.table.set_usage(500, bloated);
+    h.table.set_usage(501, bloated);
+
+    // 同じ回のチェックで両方が上限を超えても、続けては起動しない
+    h.monitor.check(&list);
+    h.clock.advance(Duration::minutes(1));
+    let events = h.monitor.check(&list);
+    assert!(matches!(kinds(&events, "MT_1").last(), Some(EventKind::Launched { .. })));
+    assert!(matches!(kinds(&events, "MT_2").last(), Some(EventKind::LaunchDelayed { .. })));
+    assert_eq!(h.launcher.launched(), vec!["MT_1"]);
+
+    h.clock.advance(Duration::seconds(20));
+    h.monitor.check_due(&list);
+    assert_eq!(h.launcher.launched(), vec!["MT_1", "MT_2"]);
+}
+
+#[test]
+fn breach_that_recovers_is_not_reported() {
+    let mut h = harness();
+    h.table.start(500, XM);
+    let list = instances(&[
+        ("MT_1", &format!("1|XM|{}", XM)),
+        ("MT_1.MaxCpuPercent", "80"),
+        ("MT_1.ThresholdMinutes", "2"),
+    ]);
+    let busy = ResourceUsage {
+        cpu_percent: 95.0,
+        ..ResourceUsage::default()
+    };
+
+    h.table.set_usage(500, busy);
+    h.monitor.check(&list);
+    h.table.set_usage(500, ResourceUsage::default());
+    h.clock.advance(Duration::minutes(1));
+    h.monitor.check(&list);
+    h.table.set_usage(500, busy);
+    h.clock.advance(Duration::minutes(1));
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+
+    // 2分続いたら警告だけ行い、同じ超過では繰り返さない
+    h.clock.advance(Duration::minutes(2));
+    let events = h.monitor.check(&list);
+    assert!(matches!(
+        kinds(&events, "MT_1").as_slice(),
+        [EventKind::Running { .. }, EventKind::ThresholdExceeded { restart: false, .. }]
+    ));
+    h.clock.advance(Duration::minutes(1));
+    let events = h.monitor.check(&list);
+    assert_eq!(kinds(&events, "MT_1"), vec![EventKind::Running { pid: 500 }]);
+    assert!(h.launcher.terminated().is_empty());
+}